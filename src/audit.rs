@@ -0,0 +1,173 @@
+use crate::filters;
+use crate::ssh_config::SshConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    High,
+}
+
+impl Severity {
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::High => "high",
+        }
+    }
+}
+
+// A risky setting, advisory only
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    // Index of the host entry in `config.lines`, None for global options
+    pub host: Option<usize>,
+    pub key: String,
+    pub value: String,
+    pub explanation: &'static str,
+}
+
+// Looks for known-risky settings in every host and in the global options,
+// most severe first
+pub fn security_findings(config: &SshConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let globals: Vec<(String, String)> = config
+        .globals()
+        .map(|(key, value, _)| (key.to_string(), value.to_string()))
+        .collect();
+    check_options(&globals, None, &mut findings);
+
+    for host in config.hosts() {
+        check_options(host.options, Some(host.index), &mut findings);
+    }
+
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    findings
+}
+
+fn check_options(options: &[(String, String)], host: Option<usize>, findings: &mut Vec<Finding>) {
+    let no_host_key_checking = options
+        .iter()
+        .any(|(key, value)| key.eq_ignore_ascii_case("StrictHostKeyChecking") && value_in(value, &["no", "off"]));
+
+    for (key, value) in options {
+        let value_is = |values: &[&str]| value_in(value, values);
+        let finding = if key.eq_ignore_ascii_case("StrictHostKeyChecking") && value_is(&["no", "off"]) {
+            Some((
+                Severity::High,
+                "Host keys are accepted without checking, so a man-in-the-middle goes unnoticed",
+            ))
+        } else if key.eq_ignore_ascii_case("UserKnownHostsFile") && value_is(&["/dev/null", "NUL"]) {
+            Some((
+                if no_host_key_checking { Severity::High } else { Severity::Warning },
+                "Known host keys are thrown away, so changed host keys can never be detected",
+            ))
+        } else if key.eq_ignore_ascii_case("CheckHostIP") && value_is(&["no"]) && no_host_key_checking {
+            Some((
+                Severity::Warning,
+                "Combined with StrictHostKeyChecking no, DNS spoofing isn't detected either",
+            ))
+        } else if key.eq_ignore_ascii_case("ForwardAgent") && value_is(&["yes"]) {
+            Some((
+                Severity::Warning,
+                "Anyone with root on the remote host can use your agent's keys while you're connected",
+            ))
+        } else if key.eq_ignore_ascii_case("ForwardX11Trusted") && value_is(&["yes"]) {
+            Some((
+                Severity::Warning,
+                "The remote host gets full access to your X display, including keystrokes",
+            ))
+        } else if filters::enables_legacy_crypto(key, value) {
+            Some((
+                Severity::Warning,
+                "Enables algorithms OpenSSH disabled by default because they're considered weak",
+            ))
+        } else {
+            None
+        };
+
+        if let Some((severity, explanation)) = finding {
+            findings.push(Finding {
+                severity,
+                host,
+                key: key.clone(),
+                value: value.clone(),
+                explanation,
+            });
+        }
+    }
+}
+
+fn value_in(value: &str, values: &[&str]) -> bool {
+    let value = value.trim_matches('"');
+    values.iter().any(|v| v.eq_ignore_ascii_case(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (severity, key) of every finding for a single host with `options`
+    fn findings(options: &str) -> Vec<(Severity, String)> {
+        let text = format!("Host h\n{}", options);
+        let config = SshConfig::parse_str(&text, "config").unwrap();
+        security_findings(&config)
+            .into_iter()
+            .map(|finding| (finding.severity, finding.key))
+            .collect()
+    }
+
+    fn finding(severity: Severity, key: &str) -> (Severity, String) {
+        (severity, key.to_string())
+    }
+
+    #[test]
+    fn strict_host_key_checking_off_is_high() {
+        assert_eq!(findings("StrictHostKeyChecking no\n"), [finding(Severity::High, "StrictHostKeyChecking")]);
+        assert_eq!(findings("StrictHostKeyChecking off\n"), [finding(Severity::High, "StrictHostKeyChecking")]);
+        assert_eq!(findings("StrictHostKeyChecking accept-new\n"), []);
+    }
+
+    #[test]
+    fn discarded_known_hosts_is_high_only_without_host_key_checking() {
+        assert_eq!(findings("UserKnownHostsFile /dev/null\n"), [finding(Severity::Warning, "UserKnownHostsFile")]);
+        assert_eq!(
+            findings("StrictHostKeyChecking no\nUserKnownHostsFile /dev/null\n"),
+            [finding(Severity::High, "StrictHostKeyChecking"), finding(Severity::High, "UserKnownHostsFile")]
+        );
+    }
+
+    #[test]
+    fn check_host_ip_off_only_counts_without_host_key_checking() {
+        assert_eq!(findings("CheckHostIP no\n"), []);
+        assert_eq!(
+            findings("StrictHostKeyChecking no\nCheckHostIP no\n"),
+            [finding(Severity::High, "StrictHostKeyChecking"), finding(Severity::Warning, "CheckHostIP")]
+        );
+    }
+
+    #[test]
+    fn forwarding_agent_and_trusted_x11_are_warnings() {
+        assert_eq!(findings("ForwardAgent yes\n"), [finding(Severity::Warning, "ForwardAgent")]);
+        assert_eq!(findings("ForwardAgent no\n"), []);
+        assert_eq!(findings("ForwardX11Trusted yes\n"), [finding(Severity::Warning, "ForwardX11Trusted")]);
+    }
+
+    #[test]
+    fn legacy_algorithms_are_warnings_unless_removed() {
+        assert_eq!(findings("HostKeyAlgorithms +ssh-rsa\n"), [finding(Severity::Warning, "HostKeyAlgorithms")]);
+        assert_eq!(findings("Ciphers aes256-gcm@openssh.com,aes128-cbc\n"), [finding(Severity::Warning, "Ciphers")]);
+        assert_eq!(findings("HostKeyAlgorithms -ssh-rsa\n"), []);
+    }
+
+    #[test]
+    fn global_options_are_checked_without_a_host() {
+        let config = SshConfig::parse_str("ForwardAgent yes\nHost h\n    Port 22\n", "config").unwrap();
+        let findings = security_findings(&config);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].host, None);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{keywords, ssh_config, tags};
+
+// What a `#` comment line stands for. Configs often disable settings by
+// commenting them out, which should not be confused with prose comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentKind {
+    // `# @tags: ...` above a Host line
+    Tags(Vec<String>),
+    // `#Host old`, a commented-out host header, recognized anywhere
+    DisabledHost(String),
+    // `# Port 22` within a host block (the comment directly follows the
+    // host's options or another disabled option)
+    DisabledOption { key: String, value: String },
+    Text,
+}
+
+// Classifies a comment line. A disabled option needs a known keyword
+// followed by a value, and only counts inside a host block, so that e.g.
+// `# User settings below` above the first host stays a plain comment. A
+// value ending like a sentence is taken as prose as well.
+pub fn classify_comment(line: &str, in_host_block: bool) -> CommentKind {
+    let Some(body) = line.trim().strip_prefix('#') else {
+        return CommentKind::Text;
+    };
+    if let Some(tags) = tags::parse_tags_comment(line) {
+        return CommentKind::Tags(tags);
+    }
+
+    let body = body.trim_start_matches('#').trim();
+    let (key, rest) = ssh_config::split_keyword(body);
+    // A trailing `# note` belongs to the comment, not to the value
+    let value = rest.split(" #").next().unwrap_or(rest).trim();
+    if value.is_empty() {
+        return CommentKind::Text;
+    }
+
+    if key.eq_ignore_ascii_case("host") {
+        return CommentKind::DisabledHost(value.to_string());
+    }
+    if !in_host_block || key.eq_ignore_ascii_case("match") || value.ends_with(['.', ':', '!']) {
+        return CommentKind::Text;
+    }
+    match keywords::canonical_keyword(key) {
+        Some(_) => CommentKind::DisabledOption {
+            key: key.to_string(),
+            value: value.to_string(),
+        },
+        None => CommentKind::Text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disabled_option(key: &str, value: &str) -> CommentKind {
+        CommentKind::DisabledOption {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn commented_out_host_headers_are_recognized_anywhere() {
+        for in_host_block in [false, true] {
+            assert_eq!(classify_comment("#Host old", in_host_block), CommentKind::DisabledHost("old".to_string()));
+            assert_eq!(
+                classify_comment("  # Host old # retired", in_host_block),
+                CommentKind::DisabledHost("old".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn commented_out_options_only_count_inside_a_host_block() {
+        assert_eq!(classify_comment("    # Port 2222", true), disabled_option("Port", "2222"));
+        assert_eq!(classify_comment("## ForwardAgent yes # for now", true), disabled_option("ForwardAgent", "yes"));
+        assert_eq!(classify_comment("# Port 2222", false), CommentKind::Text);
+    }
+
+    #[test]
+    fn prose_stays_text() {
+        assert_eq!(classify_comment("# User settings below:", true), CommentKind::Text);
+        assert_eq!(classify_comment("# Bump this once the VPN is fixed", true), CommentKind::Text);
+        assert_eq!(classify_comment("# Match host *.internal", true), CommentKind::Text);
+        assert_eq!(classify_comment("# Host", true), CommentKind::Text);
+        assert_eq!(classify_comment("# @tags: prod", true), CommentKind::Tags(vec!["prod".to_string()]));
+    }
+}
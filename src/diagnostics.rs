@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::keywords;
+use crate::ssh_config::{ConfigLine, SshConfig};
+
+// Builds a bug-report friendly description of a loaded config. Only the
+// structure and option keys are kept: host patterns, option values, comment
+// text and file paths are replaced with placeholders so no hostnames, users
+// or key paths can leak. Option keys are only printed when they are known
+// ssh keywords, since a malformed line may have a hostname in key position.
+pub fn redacted_summary(config: &SshConfig) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "egui-ssh-config {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(out, "OS: {} ({})", std::env::consts::OS, std::env::consts::ARCH);
+
+    // Number files in the order they first appear
+    let mut file_ids: HashMap<PathBuf, usize> = HashMap::new();
+    for line in &config.lines {
+        let next_id = file_ids.len() + 1;
+        file_ids.entry(line.source_file().to_path_buf()).or_insert(next_id);
+    }
+    for path in config.included_files.keys() {
+        let next_id = file_ids.len() + 1;
+        file_ids.entry(path.clone()).or_insert(next_id);
+    }
+    let file_label = |path: &Path| match file_ids.get(path) {
+        Some(id) => format!("file{}", id),
+        None => "file?".to_string(),
+    };
+
+    let host_count = config.hosts().count();
+    let _ = writeln!(
+        out,
+        "Files: {} ({} included), lines: {}, hosts: {}",
+        file_ids.len(),
+        config.included_files.len(),
+        config.lines.len(),
+        host_count
+    );
+
+    let _ = writeln!(out, "\nWarnings: {}", config.warnings.len());
+    for warning in &config.warnings {
+        let mut message = redact_quoted(&warning.message);
+        for (path, id) in &file_ids {
+            message = message.replace(&path.display().to_string(), &format!("file{}", id));
+        }
+        if let Some(home) = config.home() {
+            message = message.replace(&home.display().to_string(), "~");
+        }
+        let _ = writeln!(out, "  [{}] {}", file_label(&warning.file), message);
+    }
+
+    let _ = writeln!(out, "\nStructure:");
+    let mut host_number = 0;
+    for line in &config.lines {
+        let file = file_label(line.source_file());
+        match line {
+            ConfigLine::Comment { .. } => {
+                let _ = writeln!(out, "  [{}] # <comment>", file);
+            }
+            ConfigLine::Empty { .. } => {
+                let _ = writeln!(out, "  [{}] <blank>", file);
+            }
+            ConfigLine::Include { comment, .. } => {
+                let _ = writeln!(
+                    out,
+                    "  [{}] Include <path>{}",
+                    file,
+                    if comment.is_some() { " # <comment>" } else { "" }
+                );
+            }
+            ConfigLine::GlobalOption { key, .. } => {
+                let _ = writeln!(out, "  [{}] {} <value>", file, key_label(key));
+            }
+            ConfigLine::HostEntry { comment, options, .. } => {
+                host_number += 1;
+                let _ = writeln!(
+                    out,
+                    "  [{}] Host <host{}>{}",
+                    file,
+                    host_number,
+                    if comment.is_some() { " # <comment>" } else { "" }
+                );
+                for (key, _) in options {
+                    let _ = writeln!(out, "  [{}]     {} <value>", file, key_label(key));
+                }
+            }
+            ConfigLine::MatchEntry { comment, options, .. } => {
+                let _ = writeln!(
+                    out,
+                    "  [{}] Match <criteria>{}",
+                    file,
+                    if comment.is_some() { " # <comment>" } else { "" }
+                );
+                for (key, _) in options {
+                    let _ = writeln!(out, "  [{}]     {} <value>", file, key_label(key));
+                }
+            }
+        }
+    }
+
+    out
+}
+
+fn key_label(key: &str) -> &'static str {
+    keywords::canonical_keyword(key).unwrap_or("<unknown keyword>")
+}
+
+// Warning messages quote the offending text in single quotes; keep it only
+// when it is a known keyword
+fn redact_quoted(message: &str) -> String {
+    message
+        .split('\'')
+        .enumerate()
+        .map(|(idx, part)| {
+            if idx % 2 == 1 {
+                key_label(part).to_string()
+            } else {
+                part.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_summary_hides_hostnames_and_key_paths() {
+        let text = "IdentityFile ~/.ssh/id_global\n\
+                    Host prod-db # rack 7\n\
+                    \x20   HostName db.secret.example.com\n\
+                    \x20   IdentityFile ~/.ssh/id_prod\n\
+                    \x20   db.other.example.com yes\n\
+                    \x20   HostName backup.secret.example.com\n";
+        let config = SshConfig::parse_str(text, "/home/someone/.ssh/config").unwrap();
+        assert!(!config.warnings.is_empty());
+        let summary = redacted_summary(&config);
+
+        for secret in ["secret.example", "other.example", "id_global", "id_prod", "prod-db", "rack 7", "someone"] {
+            assert!(!summary.contains(secret), "{} leaked into:\n{}", secret, summary);
+        }
+        assert!(summary.contains("HostName <value>"));
+        assert!(summary.contains("IdentityFile <value>"));
+        assert!(summary.contains("<unknown keyword> <value>"));
+    }
+}
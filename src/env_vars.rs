@@ -0,0 +1,127 @@
+// SetEnv and SendEnv values hold several variables in one line, e.g.
+// `SetEnv LANG=C TZ="Europe/Berlin"` or `SendEnv LC_* -LC_PAPER`. Values stay
+// plain strings in the config; these helpers split them into lists for
+// editing and join them back.
+
+// Splits a value into words like ssh does: on whitespace, except inside
+// double quotes, which are removed
+pub fn split_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+// `NAME=value` pairs of a SetEnv value. A word without `=` becomes a name
+// with an empty value.
+pub fn split_set_env(value: &str) -> Vec<(String, String)> {
+    split_words(value)
+        .into_iter()
+        .map(|word| match word.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (word, String::new()),
+        })
+        .collect()
+}
+
+// Joins pairs back into a SetEnv value, quoting values with spaces
+pub fn join_set_env(pairs: &[(String, String)]) -> String {
+    pairs
+        .iter()
+        .filter(|(name, value)| !name.is_empty() || !value.is_empty())
+        .map(|(name, value)| {
+            if value.contains(char::is_whitespace) {
+                format!("{}=\"{}\"", name, value)
+            } else {
+                format!("{}={}", name, value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn split_send_env(value: &str) -> Vec<String> {
+    split_words(value)
+}
+
+pub fn join_send_env(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Whether `name` can be an environment variable name: letters, digits and
+// underscores, not starting with a digit
+pub fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Whether `name` is valid in SendEnv, which also takes `*` and `?` wildcards
+// and a leading `-` to stop sending matching variables
+pub fn is_valid_send_env_name(name: &str) -> bool {
+    let name = name.strip_prefix('-').unwrap_or(name);
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '*' || c == '?')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn set_env_splits_into_pairs_and_joins_back() {
+        let split = split_set_env("A=1 B=2");
+        assert_eq!(split, pairs(&[("A", "1"), ("B", "2")]));
+        assert_eq!(join_set_env(&split), "A=1 B=2");
+    }
+
+    #[test]
+    fn set_env_values_with_spaces_stay_quoted() {
+        let split = split_set_env("LANG=C  TZ=\"Europe/Berlin time\" EMPTY");
+        assert_eq!(split, pairs(&[("LANG", "C"), ("TZ", "Europe/Berlin time"), ("EMPTY", "")]));
+        assert_eq!(join_set_env(&split), "LANG=C TZ=\"Europe/Berlin time\" EMPTY=");
+        // Rows left blank in the editor are dropped
+        assert_eq!(join_set_env(&pairs(&[("A", "1"), ("", ""), ("B", "2")])), "A=1 B=2");
+    }
+
+    #[test]
+    fn send_env_splits_and_joins_names() {
+        let split = split_send_env("LC_* -LC_PAPER");
+        assert_eq!(split, ["LC_*", "-LC_PAPER"]);
+        assert_eq!(join_send_env(&split), "LC_* -LC_PAPER");
+        assert!(split.iter().all(|name| is_valid_send_env_name(name)));
+        assert!(!is_valid_env_name("1A"));
+    }
+}
@@ -0,0 +1,92 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+use crate::post_save::shell_quote;
+
+// Opens `path` in `editor`, falling back to $VISUAL, $EDITOR and then the
+// system's default application for the file. The system opener can't jump
+// to a line.
+pub fn open(editor: &str, path: &Path, line: Option<usize>) -> Result<(), String> {
+    let editor = Some(editor.trim().to_string())
+        .filter(|editor| !editor.is_empty())
+        .or_else(|| std::env::var("VISUAL").ok().filter(|v| !v.trim().is_empty()))
+        .or_else(|| std::env::var("EDITOR").ok().filter(|v| !v.trim().is_empty()));
+
+    let mut command = match editor {
+        Some(editor) => shell(&command_line(&editor, path, line)),
+        None => system_opener(path),
+    };
+    command.spawn().map(|_| ()).map_err(|e| e.to_string())
+}
+
+// Shell command line opening `path` at `line`. VS Code (and its builds like
+// codium) takes `-g path:line` and Sublime Text `path:line`; vi, emacs, nano
+// and most others understand `+line path`.
+fn command_line(editor: &str, path: &Path, line: Option<usize>) -> String {
+    let path = path.display().to_string();
+    let Some(line) = line else {
+        return format!("{} {}", editor, shell_quote(&path));
+    };
+
+    let program = editor.split_whitespace().next().unwrap_or_default();
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let located = shell_quote(&format!("{}:{}", path, line));
+    match name.as_str() {
+        "code" | "code-insiders" | "codium" | "vscodium" => {
+            let goto = editor.split_whitespace().any(|arg| arg == "-g" || arg == "--goto");
+            format!("{}{} {}", editor, if goto { "" } else { " -g" }, located)
+        }
+        "subl" | "sublime_text" => format!("{} {}", editor, located),
+        _ => format!("{} +{} {}", editor, line, shell_quote(&path)),
+    }
+}
+
+// Opens `url` in the default browser
+pub fn open_url(url: &str) -> Result<(), String> {
+    system_opener(url).spawn().map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn shell(command_line: &str) -> Command {
+    let mut command = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    command.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command_line);
+    command
+}
+
+fn system_opener(target: impl AsRef<OsStr>) -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(target);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn editors_are_told_the_line_their_own_way() {
+        let path = Path::new("/home/me/.ssh/config");
+        assert_eq!(command_line("vim", path, Some(12)), "vim +12 '/home/me/.ssh/config'");
+        assert_eq!(command_line("code", path, Some(12)), "code -g '/home/me/.ssh/config:12'");
+        assert_eq!(command_line("code --goto -w", path, Some(12)), "code --goto -w '/home/me/.ssh/config:12'");
+        assert_eq!(
+            command_line("/usr/bin/codium --wait", path, Some(3)),
+            "/usr/bin/codium --wait -g '/home/me/.ssh/config:3'"
+        );
+        assert_eq!(command_line("subl", path, Some(7)), "subl '/home/me/.ssh/config:7'");
+        assert_eq!(command_line("code", path, None), "code '/home/me/.ssh/config'");
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+// Predicates over a host's options, used for the sidebar's quick filters
+
+// Options whose values are algorithm lists
+const ALGORITHM_KEYWORDS: &[&str] = &[
+    "Ciphers",
+    "HostKeyAlgorithms",
+    "HostbasedAcceptedAlgorithms",
+    "KexAlgorithms",
+    "MACs",
+    "PubkeyAcceptedAlgorithms",
+    "PubkeyAcceptedKeyTypes",
+];
+
+// Algorithms OpenSSH disabled by default because they're considered weak
+const LEGACY_ALGORITHMS: &[&str] = &[
+    "ssh-rsa",
+    "ssh-rsa-cert-v01@openssh.com",
+    "ssh-dss",
+    "ssh-dss-cert-v01@openssh.com",
+    "3des-cbc",
+    "aes128-cbc",
+    "aes192-cbc",
+    "aes256-cbc",
+    "blowfish-cbc",
+    "cast128-cbc",
+    "arcfour",
+    "hmac-md5",
+    "hmac-md5-96",
+    "hmac-sha1",
+    "hmac-sha1-96",
+    "diffie-hellman-group1-sha1",
+    "diffie-hellman-group14-sha1",
+    "diffie-hellman-group-exchange-sha1",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuickFilter {
+    HasProxyJump,
+    HasIdentityFile,
+    UsesLegacyCrypto,
+    NoHostName,
+}
+
+impl QuickFilter {
+    pub const ALL: [QuickFilter; 4] = [
+        QuickFilter::HasProxyJump,
+        QuickFilter::HasIdentityFile,
+        QuickFilter::UsesLegacyCrypto,
+        QuickFilter::NoHostName,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            QuickFilter::HasProxyJump => "ProxyJump",
+            QuickFilter::HasIdentityFile => "IdentityFile",
+            QuickFilter::UsesLegacyCrypto => "Legacy crypto",
+            QuickFilter::NoHostName => "No HostName",
+        }
+    }
+
+    pub fn matches(self, options: &[(String, String)]) -> bool {
+        match self {
+            QuickFilter::HasProxyJump => has_option(options, "ProxyJump"),
+            QuickFilter::HasIdentityFile => has_option(options, "IdentityFile"),
+            QuickFilter::UsesLegacyCrypto => uses_legacy_crypto(options),
+            QuickFilter::NoHostName => !has_option(options, "HostName"),
+        }
+    }
+}
+
+pub fn has_option(options: &[(String, String)], key: &str) -> bool {
+    options.iter().any(|(k, _)| k.eq_ignore_ascii_case(key))
+}
+
+// True if any algorithm list enables a legacy algorithm. Lists starting with
+// `-` remove algorithms, so they never count.
+pub fn uses_legacy_crypto(options: &[(String, String)]) -> bool {
+    options.iter().any(|(key, value)| enables_legacy_crypto(key, value))
+}
+
+pub fn enables_legacy_crypto(key: &str, value: &str) -> bool {
+    if !ALGORITHM_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(key)) || value.starts_with('-') {
+        return false;
+    }
+    value
+        .trim_start_matches(['+', '^'])
+        .split(',')
+        .any(|alg| LEGACY_ALGORITHMS.iter().any(|legacy| legacy.eq_ignore_ascii_case(alg.trim())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn presence_filters_ignore_keyword_case() {
+        let host = options(&[("proxyjump", "bastion"), ("IdentityFile", "~/.ssh/id_ed25519")]);
+        assert!(QuickFilter::HasProxyJump.matches(&host));
+        assert!(QuickFilter::HasIdentityFile.matches(&host));
+        assert!(QuickFilter::NoHostName.matches(&host));
+        assert!(!QuickFilter::HasProxyJump.matches(&options(&[("ProxyCommand", "ssh -W %h:%p bastion")])));
+        assert!(!QuickFilter::NoHostName.matches(&options(&[("HostName", "web.example.com")])));
+    }
+
+    #[test]
+    fn legacy_crypto_filter_only_counts_enabled_legacy_algorithms() {
+        let uses = |key, value| QuickFilter::UsesLegacyCrypto.matches(&options(&[(key, value)]));
+        assert!(uses("HostKeyAlgorithms", "+ssh-rsa"));
+        assert!(uses("ciphers", "aes256-gcm@openssh.com, AES128-CBC"));
+        assert!(uses("KexAlgorithms", "^diffie-hellman-group1-sha1"));
+        // Removing a legacy algorithm, or naming one outside of a list
+        assert!(!uses("HostKeyAlgorithms", "-ssh-rsa"));
+        assert!(!uses("User", "ssh-rsa"));
+        assert!(!uses("Ciphers", "chacha20-poly1305@openssh.com"));
+    }
+}
@@ -0,0 +1,170 @@
+// Scaffolding host entries from a plain list of machines, e.g. exported from
+// an inventory tool. One machine per line, in any of these shapes:
+//
+//   web.example.com                  Host web.example.com, HostName the same
+//   me@web.example.com:2222          as above, plus User and Port
+//   web web.example.com [port] [user]
+//   web=web.example.com[:port[:user]]
+//
+// Blank lines and lines starting with `#` are skipped.
+use crate::lint;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryHost {
+    pub alias: String,
+    pub hostname: String,
+    pub port: Option<String>,
+    pub user: Option<String>,
+}
+
+impl InventoryHost {
+    // Options of the host entry to create, HostName first
+    pub fn options(&self) -> Vec<(String, String)> {
+        let mut options = vec![("HostName".to_string(), self.hostname.clone())];
+        if let Some(user) = &self.user {
+            options.push(("User".to_string(), user.clone()));
+        }
+        if let Some(port) = &self.port {
+            options.push(("Port".to_string(), port.clone()));
+        }
+        options
+    }
+}
+
+// Parses every line of `text`. Lines that can't be read are reported as
+// `line N: reason` and left out.
+pub fn parse_inventory(text: &str) -> (Vec<InventoryHost>, Vec<String>) {
+    let mut hosts = Vec::new();
+    let mut errors = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        match parse_inventory_line(line) {
+            Some(Ok(host)) => hosts.push(host),
+            Some(Err(e)) => errors.push(format!("line {}: {}", number + 1, e)),
+            None => {}
+        }
+    }
+    (hosts, errors)
+}
+
+// None for a blank or comment line
+pub fn parse_inventory_line(line: &str) -> Option<Result<InventoryHost, String>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    Some(match line.split_once('=') {
+        Some((alias, rest)) => parse_assignment(alias.trim(), rest.trim()),
+        None => parse_fields(&line.split_whitespace().collect::<Vec<_>>()),
+    })
+}
+
+// `alias=host[:port[:user]]`, with `[...]` around an IPv6 host
+fn parse_assignment(alias: &str, rest: &str) -> Result<InventoryHost, String> {
+    if alias.is_empty() || alias.contains(char::is_whitespace) {
+        return Err(format!("'{}' is not a valid alias", alias));
+    }
+    let (hostname, rest) = match rest.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, rest) = bracketed.split_once(']').ok_or("missing ']' after the IPv6 address")?;
+            (host, rest.strip_prefix(':').unwrap_or(rest))
+        }
+        None => rest.split_once(':').unwrap_or((rest, "")),
+    };
+    let (port, user) = rest.split_once(':').unwrap_or((rest, ""));
+    host(alias, hostname, Some(port).filter(|p| !p.is_empty()), Some(user).filter(|u| !u.is_empty()))
+}
+
+// `hostname` alone or `alias hostname [port] [user]`
+fn parse_fields(fields: &[&str]) -> Result<InventoryHost, String> {
+    match *fields {
+        [hostname] => match lint::split_hostname(hostname) {
+            Some(split) => host(&split.host, &split.host, split.port.as_deref(), split.user.as_deref()),
+            None => host(hostname, hostname, None, None),
+        },
+        [alias, hostname] => match lint::split_hostname(hostname) {
+            Some(split) => host(alias, &split.host, split.port.as_deref(), split.user.as_deref()),
+            None => host(alias, hostname, None, None),
+        },
+        [alias, hostname, port] => host(alias, hostname, Some(port), None),
+        [alias, hostname, port, user] => host(alias, hostname, Some(port), Some(user)),
+        _ => Err(format!("expected at most 4 fields, found {}", fields.len())),
+    }
+}
+
+fn host(alias: &str, hostname: &str, port: Option<&str>, user: Option<&str>) -> Result<InventoryHost, String> {
+    if hostname.is_empty() {
+        return Err("missing host name".to_string());
+    }
+    if hostname.contains(char::is_whitespace) {
+        return Err(format!("'{}' is not a valid host name", hostname));
+    }
+    if let Some(port) = port
+        && !port.parse::<u16>().is_ok_and(|p| p > 0)
+    {
+        return Err(format!("'{}' is not a valid port", port));
+    }
+    Ok(InventoryHost {
+        alias: alias.to_string(),
+        hostname: hostname.to_string(),
+        port: port.map(str::to_string),
+        user: user.map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(line: &str) -> InventoryHost {
+        parse_inventory_line(line).unwrap().unwrap()
+    }
+
+    fn inventory_host(alias: &str, hostname: &str, port: Option<&str>, user: Option<&str>) -> InventoryHost {
+        host(alias, hostname, port, user).unwrap()
+    }
+
+    #[test]
+    fn every_line_shape_is_understood() {
+        assert_eq!(parsed("web.example.com"), inventory_host("web.example.com", "web.example.com", None, None));
+        assert_eq!(
+            parsed("  me@web.example.com:2222 "),
+            inventory_host("web.example.com", "web.example.com", Some("2222"), Some("me"))
+        );
+        assert_eq!(parsed("web web.example.com"), inventory_host("web", "web.example.com", None, None));
+        assert_eq!(
+            parsed("web\tweb.example.com 2222 deploy"),
+            inventory_host("web", "web.example.com", Some("2222"), Some("deploy"))
+        );
+        assert_eq!(parsed("web = web.example.com"), inventory_host("web", "web.example.com", None, None));
+        assert_eq!(
+            parsed("web=web.example.com:2222:deploy"),
+            inventory_host("web", "web.example.com", Some("2222"), Some("deploy"))
+        );
+        assert_eq!(
+            parsed("v6=[2001:db8::1]:2222"),
+            inventory_host("v6", "2001:db8::1", Some("2222"), None)
+        );
+    }
+
+    #[test]
+    fn blank_and_comment_lines_are_skipped() {
+        assert_eq!(parse_inventory_line(""), None);
+        assert_eq!(parse_inventory_line("   "), None);
+        assert_eq!(parse_inventory_line("# web web.example.com"), None);
+    }
+
+    #[test]
+    fn bad_lines_say_what_is_wrong() {
+        let error = |line: &str| parse_inventory_line(line).unwrap().unwrap_err();
+        assert_eq!(error("web web.example.com http"), "'http' is not a valid port");
+        assert_eq!(error("web=web.example.com:0"), "'0' is not a valid port");
+        assert_eq!(error("my web=web.example.com"), "'my web' is not a valid alias");
+        assert_eq!(error("web="), "missing host name");
+        assert_eq!(error("v6=[2001:db8::1"), "missing ']' after the IPv6 address");
+        assert_eq!(error("a b 22 me extra"), "expected at most 4 fields, found 5");
+
+        let (hosts, errors) = parse_inventory("web web.example.com\n\nweb=host:http\n# done\n");
+        assert_eq!(hosts, [inventory_host("web", "web.example.com", None, None)]);
+        assert_eq!(errors, ["line 3: 'http' is not a valid port"]);
+    }
+}
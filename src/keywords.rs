@@ -0,0 +1,301 @@
+// Keywords understood by OpenSSH's ssh_config(5), in their documented
+// spelling. Matching is case-insensitive like in ssh itself.
+pub const KNOWN_KEYWORDS: &[&str] = &[
+    "Host",
+    "Match",
+    "AddKeysToAgent",
+    "AddressFamily",
+    "BatchMode",
+    "BindAddress",
+    "BindInterface",
+    "CanonicalDomains",
+    "CanonicalizeFallbackLocal",
+    "CanonicalizeHostname",
+    "CanonicalizeMaxDots",
+    "CanonicalizePermittedCNAMEs",
+    "CASignatureAlgorithms",
+    "CertificateFile",
+    "ChallengeResponseAuthentication",
+    "ChannelTimeout",
+    "CheckHostIP",
+    "Ciphers",
+    "ClearAllForwardings",
+    "Compression",
+    "ConnectionAttempts",
+    "ConnectTimeout",
+    "ControlMaster",
+    "ControlPath",
+    "ControlPersist",
+    "DynamicForward",
+    "EnableEscapeCommandline",
+    "EnableSSHKeysign",
+    "EscapeChar",
+    "ExitOnForwardFailure",
+    "FingerprintHash",
+    "ForkAfterAuthentication",
+    "ForwardAgent",
+    "ForwardX11",
+    "ForwardX11Timeout",
+    "ForwardX11Trusted",
+    "GatewayPorts",
+    "GlobalKnownHostsFile",
+    "GSSAPIAuthentication",
+    "GSSAPIDelegateCredentials",
+    "HashKnownHosts",
+    "HostbasedAcceptedAlgorithms",
+    "HostbasedAuthentication",
+    "HostbasedKeyTypes",
+    "HostKeyAlgorithms",
+    "HostKeyAlias",
+    "HostName",
+    "IdentitiesOnly",
+    "IdentityAgent",
+    "IdentityFile",
+    "IgnoreUnknown",
+    "Include",
+    "IPQoS",
+    "KbdInteractiveAuthentication",
+    "KbdInteractiveDevices",
+    "KexAlgorithms",
+    "KnownHostsCommand",
+    "LocalCommand",
+    "LocalForward",
+    "LogLevel",
+    "LogVerbose",
+    "MACs",
+    "NoHostAuthenticationForLocalhost",
+    "NumberOfPasswordPrompts",
+    "ObscureKeystrokeTiming",
+    "PasswordAuthentication",
+    "PermitLocalCommand",
+    "PermitRemoteOpen",
+    "PKCS11Provider",
+    "Port",
+    "PreferredAuthentications",
+    "ProxyCommand",
+    "ProxyJump",
+    "ProxyUseFdpass",
+    "PubkeyAcceptedAlgorithms",
+    "PubkeyAcceptedKeyTypes",
+    "PubkeyAuthentication",
+    "RekeyLimit",
+    "RemoteCommand",
+    "RemoteForward",
+    "RequestTTY",
+    "RequiredRSASize",
+    "RevokedHostKeys",
+    "SecurityKeyProvider",
+    "SendEnv",
+    "ServerAliveCountMax",
+    "ServerAliveInterval",
+    "SessionType",
+    "SetEnv",
+    "StdinNull",
+    "StreamLocalBindMask",
+    "StreamLocalBindUnlink",
+    "StrictHostKeyChecking",
+    "SyslogFacility",
+    "Tag",
+    "TCPKeepAlive",
+    "Tunnel",
+    "TunnelDevice",
+    "UpdateHostKeys",
+    "UseKeychain",
+    "User",
+    "UserKnownHostsFile",
+    "VerifyHostKeyDNS",
+    "VisualHostKey",
+    "XAuthLocation",
+];
+
+// Keywords that may be given several times in one block, each occurrence
+// adding another entry (key, forward, variable) instead of being ignored
+pub const REPEATABLE_KEYWORDS: &[&str] = &[
+    "CertificateFile",
+    "DynamicForward",
+    "IdentityFile",
+    "LocalForward",
+    "RemoteForward",
+    "SendEnv",
+    "SetEnv",
+];
+
+// Keywords that only take yes or no. Keywords with extra values (e.g.
+// `ControlMaster auto`, `StrictHostKeyChecking accept-new`) aren't listed.
+pub const BOOLEAN_KEYWORDS: &[&str] = &[
+    "BatchMode",
+    "CanonicalizeFallbackLocal",
+    "ChallengeResponseAuthentication",
+    "CheckHostIP",
+    "ClearAllForwardings",
+    "Compression",
+    "EnableEscapeCommandline",
+    "EnableSSHKeysign",
+    "ExitOnForwardFailure",
+    "ForkAfterAuthentication",
+    "ForwardX11",
+    "ForwardX11Trusted",
+    "GatewayPorts",
+    "GSSAPIAuthentication",
+    "GSSAPIDelegateCredentials",
+    "HashKnownHosts",
+    "HostbasedAuthentication",
+    "IdentitiesOnly",
+    "KbdInteractiveAuthentication",
+    "NoHostAuthenticationForLocalhost",
+    "PasswordAuthentication",
+    "PermitLocalCommand",
+    "ProxyUseFdpass",
+    "StdinNull",
+    "StreamLocalBindUnlink",
+    "TCPKeepAlive",
+    "VisualHostKey",
+];
+
+pub fn is_boolean(key: &str) -> bool {
+    BOOLEAN_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(key))
+}
+
+// Comma-separated algorithm lists. A value starting with `+` appends to
+// ssh's default list, `-` removes from it and `^` puts its entries first.
+pub const ALGORITHM_LIST_KEYWORDS: &[&str] = &[
+    "CASignatureAlgorithms",
+    "Ciphers",
+    "HostbasedAcceptedAlgorithms",
+    "HostbasedKeyTypes",
+    "HostKeyAlgorithms",
+    "KexAlgorithms",
+    "MACs",
+    "PubkeyAcceptedAlgorithms",
+    "PubkeyAcceptedKeyTypes",
+];
+
+pub fn is_algorithm_list(key: &str) -> bool {
+    ALGORITHM_LIST_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(key))
+}
+
+// Values ssh uses when a keyword isn't set, per ssh_config(5). Keywords whose
+// default depends on the build or other settings are left out, so their
+// values always count as customized.
+pub const DEFAULT_VALUES: &[(&str, &str)] = &[
+    ("AddKeysToAgent", "no"),
+    ("AddressFamily", "any"),
+    ("BatchMode", "no"),
+    ("CanonicalizeFallbackLocal", "yes"),
+    ("CanonicalizeHostname", "no"),
+    ("CanonicalizeMaxDots", "1"),
+    ("CheckHostIP", "no"),
+    ("ClearAllForwardings", "no"),
+    ("Compression", "no"),
+    ("ConnectionAttempts", "1"),
+    ("ControlMaster", "no"),
+    ("ControlPersist", "no"),
+    ("EnableEscapeCommandline", "no"),
+    ("EscapeChar", "~"),
+    ("ExitOnForwardFailure", "no"),
+    ("ForkAfterAuthentication", "no"),
+    ("ForwardAgent", "no"),
+    ("ForwardX11", "no"),
+    ("ForwardX11Timeout", "20m"),
+    ("ForwardX11Trusted", "no"),
+    ("GatewayPorts", "no"),
+    ("GSSAPIAuthentication", "no"),
+    ("HashKnownHosts", "no"),
+    ("HostbasedAuthentication", "no"),
+    ("IdentitiesOnly", "no"),
+    ("KbdInteractiveAuthentication", "yes"),
+    ("LogLevel", "INFO"),
+    ("NumberOfPasswordPrompts", "3"),
+    ("PasswordAuthentication", "yes"),
+    ("PermitLocalCommand", "no"),
+    ("Port", "22"),
+    ("ProxyUseFdpass", "no"),
+    ("PubkeyAuthentication", "yes"),
+    ("RequestTTY", "auto"),
+    ("ServerAliveCountMax", "3"),
+    ("ServerAliveInterval", "0"),
+    ("SessionType", "default"),
+    ("StdinNull", "no"),
+    ("StreamLocalBindUnlink", "no"),
+    ("StrictHostKeyChecking", "ask"),
+    ("TCPKeepAlive", "yes"),
+    ("Tunnel", "no"),
+    ("VerifyHostKeyDNS", "no"),
+    ("VisualHostKey", "no"),
+];
+
+// Whether `value` is what ssh would use anyway if `key` wasn't set
+pub fn is_default_value(key: &str, value: &str) -> bool {
+    let value = value.trim().trim_matches('"');
+    DEFAULT_VALUES
+        .iter()
+        .any(|(k, default)| k.eq_ignore_ascii_case(key) && default.eq_ignore_ascii_case(value))
+}
+
+// Documented spelling of `key` if it is a known keyword
+pub fn canonical_keyword(key: &str) -> Option<&'static str> {
+    KNOWN_KEYWORDS.iter().copied().find(|k| k.eq_ignore_ascii_case(key))
+}
+
+// OpenBSD's copy of ssh_config(5) has an anchor named after each keyword
+const MANUAL_URL: &str = "https://man.openbsd.org/ssh_config";
+
+// Keywords documented under another name, or not at all (None)
+const MANUAL_ANCHORS: &[(&str, Option<&str>)] = &[
+    ("ChallengeResponseAuthentication", Some("KbdInteractiveAuthentication")),
+    ("HostbasedKeyTypes", Some("HostbasedAcceptedAlgorithms")),
+    ("PubkeyAcceptedKeyTypes", Some("PubkeyAcceptedAlgorithms")),
+    // Apple's ssh only
+    ("UseKeychain", None),
+];
+
+// Link to the manual's entry for a known keyword
+pub fn manual_url(key: &str) -> Option<String> {
+    let keyword = canonical_keyword(key)?;
+    let anchor = match MANUAL_ANCHORS.iter().find(|(k, _)| *k == keyword) {
+        Some((_, anchor)) => (*anchor)?,
+        None => keyword,
+    };
+    Some(format!("{}#{}", MANUAL_URL, anchor))
+}
+
+pub fn is_known_keyword(key: &str) -> bool {
+    canonical_keyword(key).is_some()
+}
+
+pub fn is_repeatable(key: &str) -> bool {
+    REPEATABLE_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(key))
+}
+
+// `none` explicitly disables a setting (e.g. `ProxyJump none` overrides a
+// jump host inherited from `Host *`), so it must not be treated as a file
+// name or host
+pub fn is_none_value(value: &str) -> bool {
+    value.trim().trim_matches('"').eq_ignore_ascii_case("none")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_values_are_recognized() {
+        assert!(is_default_value("Port", "22"));
+        assert!(!is_default_value("Port", "2222"));
+        assert!(is_default_value("port", "\"22\""));
+        assert!(is_default_value("StrictHostKeyChecking", "ASK"));
+        // No well-defined default: always custom
+        assert!(!is_default_value("HostName", "localhost"));
+    }
+
+    #[test]
+    fn algorithm_list_keywords_are_known() {
+        for keyword in ALGORITHM_LIST_KEYWORDS {
+            assert_eq!(canonical_keyword(keyword), Some(*keyword));
+        }
+        assert_eq!(
+            manual_url("hostbasedkeytypes").as_deref(),
+            Some("https://man.openbsd.org/ssh_config#HostbasedAcceptedAlgorithms")
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Parser and serializer for OpenSSH client config files, used by the
+//! egui-ssh-config editor and usable on its own.
+//!
+//! ```
+//! use egui_ssh_config::SshConfig;
+//!
+//! let text = "Compression yes\n\nHost web\n    HostName web.example.com\n\nHost db\n    Port 2222\n";
+//! let config = SshConfig::parse_str(text, "config").unwrap();
+//!
+//! let hosts: Vec<&str> = config.hosts().map(|host| host.pattern).collect();
+//! assert_eq!(hosts, ["web", "db"]);
+//! assert_eq!(config.find_host_index("DB"), Some(4));
+//! assert_eq!(config.globals().map(|(key, value, _)| (key, value)).collect::<Vec<_>>(), [("Compression", "yes")]);
+//! assert_eq!(config.to_string("config".as_ref()), text);
+//! ```
+
+pub mod audit;
+pub mod comments;
+pub mod diagnostics;
+pub mod env_vars;
+pub mod filters;
+pub mod inventory;
+pub mod keywords;
+pub mod lint;
+pub mod matrix;
+pub mod pattern;
+pub mod search;
+pub mod ssh_config;
+pub mod system_ssh;
+pub mod tags;
+
+pub use ssh_config::{ConfigLine, HostRef, SshConfig};
@@ -0,0 +1,756 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::{filters, keywords, pattern};
+use crate::ssh_config::{self, ConfigLine, ParseWarning, SshConfig, trim_value};
+
+// A global option set to different values in different files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: String,
+    // Every (file, value) setting the key, in the order ssh reads them. The
+    // first one is the value that takes effect.
+    pub settings: Vec<(PathBuf, String)>,
+}
+
+impl Conflict {
+    pub fn winner(&self) -> &(PathBuf, String) {
+        &self.settings[0]
+    }
+}
+
+// Structural mistakes the lenient parser accepts but that leave options
+// without effect:
+// - a Host line directly followed by another Host line (no blank line or
+//   comment in between) ends up with no options, usually because the two
+//   were meant to be one `Host a b` line
+// - a keyword repeated within one block, where ssh only uses the first value
+//   (except for repeatable keywords like IdentityFile, which accumulate)
+pub fn structural_warnings(config: &SshConfig) -> Vec<ParseWarning> {
+    let mut warnings = Vec::new();
+
+    for (idx, line) in config.lines.iter().enumerate() {
+        let ConfigLine::HostEntry { pattern, options, source_file, .. } = line else {
+            continue;
+        };
+
+        if options.is_empty()
+            && let Some(ConfigLine::HostEntry { pattern: next, source_file: next_file, .. }) =
+                config.lines.get(idx + 1)
+            && next_file == source_file
+        {
+            warnings.push(ParseWarning {
+                file: source_file.clone(),
+                message: format!(
+                    "'Host {}' has no options because 'Host {}' directly follows it; \
+                     list both patterns on one Host line if they should share options",
+                    pattern, next
+                ),
+            });
+        }
+
+        for (option_idx, (key, value)) in options.iter().enumerate() {
+            if !keywords::is_repeatable(key)
+                && let Some((first_key, first_value)) = options[..option_idx]
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            {
+                let message = if keywords::is_algorithm_list(key) && value.starts_with(['+', '-', '^']) {
+                    format!(
+                        "'{} {}' in 'Host {}' is ignored; ssh uses the first {} ('{}') and the modifier \
+                         would only change ssh's default list, not that value",
+                        key, value, pattern, first_key, first_value
+                    )
+                } else {
+                    format!(
+                        "'{} {}' in 'Host {}' is ignored; ssh uses the first {} ('{}')",
+                        key, value, pattern, first_key, first_value
+                    )
+                };
+                warnings.push(ParseWarning {
+                    file: source_file.clone(),
+                    message,
+                });
+            }
+        }
+    }
+
+    warnings
+}
+
+// Global options (compared case-insensitively) that more than one file sets
+// to different values. ssh uses the first value it reads, so the later ones
+// silently have no effect. Repeatable keywords add up instead.
+pub fn conflicting_globals(config: &SshConfig) -> Vec<Conflict> {
+    let mut groups: Vec<Conflict> = Vec::new();
+
+    for (key, value, source_file) in config.globals() {
+        if keywords::is_repeatable(key) {
+            continue;
+        }
+        let setting = (source_file.to_path_buf(), value.to_string());
+        match groups.iter_mut().find(|c| c.key.eq_ignore_ascii_case(key)) {
+            Some(group) => group.settings.push(setting),
+            None => groups.push(Conflict {
+                key: key.to_string(),
+                settings: vec![setting],
+            }),
+        }
+    }
+
+    groups.retain(|group| {
+        let (first_file, first_value) = group.winner();
+        group
+            .settings
+            .iter()
+            .any(|(file, value)| file != first_file && value != first_value)
+    });
+    groups
+}
+
+// Host patterns (compared like duplicate hosts) defined in more than one
+// file, with those files in order. ssh reads all of them and the first file
+// wins for every option, which is rarely what was meant. Wildcard blocks
+// like `Host *` are left out, since spreading those is common on purpose.
+pub fn cross_file_duplicate_patterns(config: &SshConfig) -> Vec<(String, Vec<PathBuf>)> {
+    let mut groups: Vec<(String, Vec<PathBuf>)> = Vec::new();
+
+    for line in &config.lines {
+        let ConfigLine::HostEntry { pattern, source_file, .. } = line else {
+            continue;
+        };
+        if pattern.contains(['*', '?']) {
+            continue;
+        }
+        match groups.iter_mut().find(|(p, _)| pattern::same_pattern_list(p, pattern)) {
+            Some((_, files)) => {
+                if !files.contains(source_file) {
+                    files.push(source_file.clone());
+                }
+            }
+            None => groups.push((pattern.clone(), vec![source_file.clone()])),
+        }
+    }
+
+    groups.retain(|(_, files)| files.len() > 1);
+    groups
+}
+
+// Hosts carrying an OpenSSH `Tag` and the `Match tagged` lines selecting it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagUsage {
+    pub tag: String,
+    // Indices of the host entries with `Tag <tag>`
+    pub hosts: Vec<usize>,
+    // Criteria of every `Match` line whose `tagged` pattern list matches the tag
+    pub match_lines: Vec<String>,
+}
+
+// Cross-references `Tag` options of hosts with `Match tagged` criteria
+pub fn tag_usage(config: &SshConfig) -> Vec<TagUsage> {
+    let mut usages: Vec<TagUsage> = Vec::new();
+    let mut match_lines: Vec<&str> = Vec::new();
+
+    for (idx, line) in config.lines.iter().enumerate() {
+        let options = match line {
+            ConfigLine::HostEntry { options, .. } => options,
+            ConfigLine::MatchEntry { criteria, .. } => {
+                match_lines.push(criteria);
+                continue;
+            }
+            _ => continue,
+        };
+
+        for (key, value) in options {
+            if key.eq_ignore_ascii_case("tag") {
+                match usages.iter_mut().find(|u| u.tag == *value) {
+                    Some(usage) => usage.hosts.push(idx),
+                    None => usages.push(TagUsage {
+                        tag: value.to_string(),
+                        hosts: vec![idx],
+                        match_lines: Vec::new(),
+                    }),
+                }
+            }
+        }
+    }
+
+    for usage in &mut usages {
+        usage.match_lines = match_lines
+            .iter()
+            .filter(|criteria| {
+                tagged_patterns(criteria).into_iter().any(|patterns| pattern::matches_pattern_list(patterns, &usage.tag))
+            })
+            .map(|criteria| criteria.to_string())
+            .collect();
+    }
+
+    usages
+}
+
+// Pattern lists following each `tagged` keyword in Match criteria such as
+// `host db* tagged prod,staging`
+fn tagged_patterns(criteria: &str) -> Vec<&str> {
+    let tokens: Vec<&str> = criteria.split_whitespace().collect();
+    tokens
+        .windows(2)
+        .filter(|pair| pair[0].eq_ignore_ascii_case("tagged"))
+        .map(|pair| pair[1])
+        .collect()
+}
+
+// A HostName value with a port and/or user packed into it, e.g.
+// `me@host:2222`. ssh would try to resolve the whole text as a host name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostNameSplit {
+    pub host: String,
+    pub port: Option<String>,
+    pub user: Option<String>,
+}
+
+impl HostNameSplit {
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("HostName {}", self.host)];
+        if let Some(user) = &self.user {
+            parts.push(format!("User {}", user));
+        }
+        if let Some(port) = &self.port {
+            parts.push(format!("Port {}", port));
+        }
+        parts.join(" + ")
+    }
+
+    fn parts(&self) -> [(&'static str, &Option<String>); 2] {
+        [("User", &self.user), ("Port", &self.port)]
+    }
+}
+
+// Splits `user@host`, `host:port` and `[v6-address]:port` HostName values.
+// A bare IPv6 address has several colons and is left alone.
+pub fn split_hostname(value: &str) -> Option<HostNameSplit> {
+    let value = value.trim().trim_matches('"');
+    let (user, rest) = match value.rsplit_once('@') {
+        Some((user, rest)) if !user.is_empty() && !rest.is_empty() => (Some(user.to_string()), rest),
+        _ => (None, value),
+    };
+
+    let (host, port) = if let Some(bracketed) = rest.strip_prefix('[') {
+        match bracketed.split_once("]:") {
+            Some((host, port)) => (host, Some(port)),
+            None => (rest, None),
+        }
+    } else if rest.matches(':').count() == 1 {
+        let (host, port) = rest.split_once(':').unwrap_or((rest, ""));
+        (host, Some(port))
+    } else {
+        (rest, None)
+    };
+    let port = port.filter(|port| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()));
+    // A colon that isn't followed by a port isn't something to split off
+    let host = if port.is_some() { host } else { rest };
+
+    if user.is_none() && port.is_none() || host.is_empty() {
+        return None;
+    }
+    Some(HostNameSplit {
+        host: host.to_string(),
+        port: port.map(str::to_string),
+        user,
+    })
+}
+
+// User/Port options that are already set to something other than what
+// `split` would set them to, as `Key value`
+pub fn hostname_split_conflicts(options: &[(String, String)], split: &HostNameSplit) -> Vec<String> {
+    split
+        .parts()
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = value.as_ref()?;
+            let (existing_key, existing) = options.iter().find(|(k, _)| k.eq_ignore_ascii_case(key))?;
+            (existing != value).then(|| format!("{} {}", existing_key, existing))
+        })
+        .collect()
+}
+
+// Applies `split` to the HostName at `index`: HostName keeps just the host
+// and User/Port are added right after it unless already set to the same
+// value. Returns the indices of the options added, or None without changing
+// anything if User or Port is already set differently (see
+// `hostname_split_conflicts`).
+pub fn apply_hostname_split(options: &mut Vec<(String, String)>, index: usize, split: &HostNameSplit) -> Option<Vec<usize>> {
+    if !hostname_split_conflicts(options, split).is_empty() {
+        return None;
+    }
+    let mut inserted = Vec::new();
+    options[index].1 = split.host.clone();
+    let mut position = index + 1;
+    for (key, value) in split.parts() {
+        let Some(value) = value else {
+            continue;
+        };
+        if !filters::has_option(options, key) {
+            options.insert(position, (key.to_string(), value.clone()));
+            inserted.push(position);
+            position += 1;
+        }
+    }
+    Some(inserted)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BooleanReport {
+    pub changed: usize,
+    // `Key value` of boolean options whose value isn't a recognized spelling
+    pub unrecognized: Vec<String>,
+}
+
+// Canonical spelling of a boolean value: yes/true/1 become `yes`, no/false/0
+// become `no`, in any case. None for anything else.
+pub fn canonical_boolean(value: &str) -> Option<&'static str> {
+    match value.trim().trim_matches('"').to_lowercase().as_str() {
+        "yes" | "true" | "1" => Some("yes"),
+        "no" | "false" | "0" => Some("no"),
+        _ => None,
+    }
+}
+
+// Rewrites the values of yes/no keywords to lowercase `yes`/`no`. Values
+// that aren't a known spelling are left as they are and reported.
+pub fn normalize_booleans(config: &mut SshConfig) -> BooleanReport {
+    let mut report = BooleanReport::default();
+    let mut normalize = |key: &str, value: &mut String| {
+        if !keywords::is_boolean(key) {
+            return;
+        }
+        match canonical_boolean(value) {
+            Some(canonical) if value != canonical => {
+                *value = canonical.to_string();
+                report.changed += 1;
+            }
+            Some(_) => {}
+            None => report.unrecognized.push(format!("{} {}", key, value)),
+        }
+    };
+
+    for line in &mut config.lines {
+        match line {
+            ConfigLine::HostEntry { options, .. } => {
+                for (key, value) in options {
+                    normalize(key, value);
+                }
+            }
+            ConfigLine::GlobalOption { key, value, .. } => normalize(key, value),
+            _ => {}
+        }
+    }
+    report
+}
+
+// Whitespace problem on one line of a file as it would be saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattingFinding {
+    pub file: PathBuf,
+    // 1-based
+    pub line: usize,
+    pub problem: &'static str,
+}
+
+// Trailing whitespace and indentation that doesn't match the file's indent
+// (see `SshConfig::format_for`). Options are always written with that indent
+// and trimmed values, so in practice this finds comments and blank lines
+// kept verbatim; whitespace inside an unterminated quote is never reported.
+pub fn formatting_findings(config: &SshConfig) -> Vec<FormattingFinding> {
+    let mut findings = Vec::new();
+    for file in config_files(config) {
+        let indent = config.format_for(&file).indent;
+        for (idx, line) in config.to_string(&file).lines().enumerate() {
+            let body = line.trim_start();
+            let leading = &line[..line.len() - body.len()];
+            let mut report = |problem| {
+                findings.push(FormattingFinding {
+                    file: file.clone(),
+                    line: idx + 1,
+                    problem,
+                })
+            };
+            let is_comment = body.is_empty() || body.starts_with('#') || body.starts_with(';');
+            if (is_comment && line.len() != line.trim_end().len()) || trim_value(body).len() != body.len() {
+                report("trailing whitespace");
+            }
+            if !body.trim_end().is_empty() && !leading.is_empty() && leading != indent {
+                report(if leading.contains(' ') && leading.contains('\t') {
+                    "indentation mixes tabs and spaces"
+                } else if leading.contains('\t') != indent.contains('\t') {
+                    if indent.contains('\t') {
+                        "indented with spaces, the file uses tabs"
+                    } else {
+                        "indented with tabs, the file uses spaces"
+                    }
+                } else {
+                    "indented differently from the options"
+                });
+            }
+        }
+    }
+    findings
+}
+
+// Fixes everything `formatting_findings` reports: trims trailing whitespace
+// off comment and blank lines and indents indented comments like the
+// options of their file. Returns the number of lines changed; running it a
+// second time changes nothing.
+pub fn clean_up_formatting(config: &mut SshConfig) -> usize {
+    let indents: HashMap<PathBuf, String> = config_files(config)
+        .into_iter()
+        .map(|file| {
+            let indent = config.format_for(&file).indent;
+            (file, indent)
+        })
+        .collect();
+
+    let mut changed = 0;
+    for line in &mut config.lines {
+        match line {
+            ConfigLine::Comment { text, source_file } => {
+                let clean = clean_comment_line(text, &indents[source_file.as_path()]);
+                if clean != *text {
+                    *text = clean;
+                    changed += 1;
+                }
+            }
+            ConfigLine::HostEntry {
+                option_comments,
+                source_file,
+                ..
+            } => {
+                let indent = &indents[source_file.as_path()];
+                for comment in option_comments {
+                    let mut clean = String::new();
+                    for text in comment.lines() {
+                        let line = clean_comment_line(text, indent);
+                        if line != text {
+                            changed += 1;
+                        }
+                        clean.push_str(&line);
+                        clean.push('\n');
+                    }
+                    *comment = clean;
+                }
+            }
+            _ => {}
+        }
+    }
+    changed
+}
+
+fn clean_comment_line(text: &str, indent: &str) -> String {
+    let body = text.trim();
+    if body.is_empty() || body.len() == text.trim_end().len() {
+        body.to_string()
+    } else {
+        format!("{}{}", indent, body)
+    }
+}
+
+// Every file with lines, in the order they first appear
+fn config_files(config: &SshConfig) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for line in &config.lines {
+        if !files.iter().any(|file| file == line.source_file()) {
+            files.push(line.source_file().to_path_buf());
+        }
+    }
+    files
+}
+
+// Repeated algorithm list keyword in one host whose later values use the
+// `+`/`-`/`^` modifiers. ssh only reads the first value, so the later ones
+// have no effect; `combined` is what they were presumably meant to add up to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListMerge {
+    pub key: String,
+    // Option indices of the values, the first one being the one ssh uses
+    pub indices: Vec<usize>,
+    pub combined: String,
+}
+
+// Folds algorithm list values into one, applying each later value to the
+// result so far: `+` appends the entries not yet listed, `-` removes the
+// entries matching its patterns (`*` and `?` allowed, like in ssh) and `^`
+// moves its entries to the front. Values that modify ssh's default list
+// only combine with the same modifier. None if the values can't be combined,
+// e.g. two full lists or `+` after `-`.
+pub fn merge_algorithm_lists(values: &[&str]) -> Option<String> {
+    let split = |value: &str| -> (Option<char>, Vec<String>) {
+        let value = value.trim().trim_matches('"');
+        let modifier = value.chars().next().filter(|c| matches!(c, '+' | '-' | '^'));
+        let list = &value[modifier.map_or(0, char::len_utf8)..];
+        let entries = list.split(',').map(str::trim).filter(|e| !e.is_empty()).map(str::to_string).collect();
+        (modifier, entries)
+    };
+
+    let (first, rest) = values.split_first()?;
+    let (base_modifier, mut merged) = split(first);
+    for value in rest {
+        let (modifier, entries) = split(value);
+        let new_entries: Vec<String> = entries
+            .iter()
+            .filter(|e| !merged.iter().any(|m| m.eq_ignore_ascii_case(e)))
+            .cloned()
+            .collect();
+        match (base_modifier, modifier?) {
+            (None | Some('+'), '+') => merged.extend(new_entries),
+            (None, '-') => merged.retain(|m| !entries.iter().any(|e| pattern::matches_pattern_list(e, m))),
+            (Some('-'), '-') => merged.extend(new_entries),
+            (None | Some('^'), '^') => {
+                merged.retain(|m| !entries.iter().any(|e| e.eq_ignore_ascii_case(m)));
+                merged.splice(0..0, entries);
+            }
+            _ => return None,
+        }
+    }
+    Some(format!("{}{}", base_modifier.map(String::from).unwrap_or_default(), merged.join(",")))
+}
+
+// Algorithm list keywords set more than once in `options` with modifiers
+// that can be combined into a single value
+pub fn mergeable_lists(options: &[(String, String)]) -> Vec<ListMerge> {
+    let mut merges: Vec<ListMerge> = Vec::new();
+    for (idx, (key, _)) in options.iter().enumerate() {
+        if !keywords::is_algorithm_list(key) || merges.iter().any(|m| m.key.eq_ignore_ascii_case(key)) {
+            continue;
+        }
+        let indices: Vec<usize> = (idx..options.len()).filter(|&i| options[i].0.eq_ignore_ascii_case(key)).collect();
+        let values: Vec<&str> = indices.iter().map(|&i| options[i].1.as_str()).collect();
+        if values.len() > 1
+            && values[1..].iter().all(|v| v.trim_start_matches('"').starts_with(['+', '-', '^']))
+            && let Some(combined) = merge_algorithm_lists(&values)
+        {
+            merges.push(ListMerge {
+                key: key.clone(),
+                indices,
+                combined,
+            });
+        }
+    }
+    merges
+}
+
+// Replaces the first value of `merge` with the combined list and removes the
+// others. Comments above the removed options move above the combined one.
+pub fn apply_list_merge(options: &mut Vec<(String, String)>, option_comments: &mut Vec<String>, merge: &ListMerge) {
+    let Some((&first, others)) = merge.indices.split_first() else {
+        return;
+    };
+    options[first].1 = merge.combined.clone();
+    let mut moved_comments = Vec::new();
+    for &idx in others.iter().rev() {
+        options.remove(idx);
+        moved_comments.push(ssh_config::remove_option_comment(option_comments, idx));
+    }
+    let moved: String = moved_comments.into_iter().rev().collect();
+    if !moved.is_empty() {
+        if option_comments.len() <= first {
+            option_comments.resize(first + 1, String::new());
+        }
+        option_comments[first].push_str(&moved);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> SshConfig {
+        SshConfig::parse_str(text, "config").unwrap()
+    }
+
+    #[test]
+    fn repeated_local_forwards_are_not_duplicates() {
+        let config = parse("Host h\n    LocalForward 8080 localhost:80\n    LocalForward 8443 localhost:443\n");
+        assert_eq!(structural_warnings(&config), []);
+    }
+
+    #[test]
+    fn repeated_ports_are_duplicates() {
+        let config = parse("Host h\n    Port 22\n    Port 2222\n");
+        let warnings = structural_warnings(&config);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.starts_with("'Port 2222' in 'Host h' is ignored"));
+    }
+
+    fn options(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn hostname_with_user_and_port_is_split() {
+        let split = split_hostname("me@web.example.com:2222").unwrap();
+        assert_eq!(split.describe(), "HostName web.example.com + User me + Port 2222");
+        assert_eq!(split_hostname("web.example.com"), None);
+        // A bare IPv6 address isn't a port
+        assert_eq!(split_hostname("fe80::1"), None);
+        assert_eq!(split_hostname("[fe80::1]:2222").unwrap().host, "fe80::1");
+
+        let mut opts = options(&[("HostName", "me@web.example.com:2222"), ("IdentityFile", "~/.ssh/id")]);
+        assert_eq!(apply_hostname_split(&mut opts, 0, &split), Some(vec![1, 2]));
+        assert_eq!(
+            opts,
+            options(&[("HostName", "web.example.com"), ("User", "me"), ("Port", "2222"), ("IdentityFile", "~/.ssh/id")])
+        );
+    }
+
+    #[test]
+    fn hostname_split_keeps_matching_options() {
+        let split = split_hostname("web.example.com:2222").unwrap();
+        let mut opts = options(&[("Port", "2222"), ("HostName", "web.example.com:2222")]);
+        assert_eq!(hostname_split_conflicts(&opts, &split), Vec::<String>::new());
+        assert_eq!(apply_hostname_split(&mut opts, 1, &split), Some(vec![]));
+        assert_eq!(opts, options(&[("Port", "2222"), ("HostName", "web.example.com")]));
+    }
+
+    #[test]
+    fn hostname_split_never_overwrites_a_different_value() {
+        let split = split_hostname("me@web.example.com:2222").unwrap();
+        let original = options(&[("HostName", "me@web.example.com:2222"), ("port", "22")]);
+        let mut opts = original.clone();
+        assert_eq!(hostname_split_conflicts(&opts, &split), ["port 22"]);
+        assert_eq!(apply_hostname_split(&mut opts, 0, &split), None);
+        assert_eq!(opts, original);
+    }
+
+    #[test]
+    fn host_line_directly_followed_by_another_is_flagged() {
+        let config = parse("Host web\nHost web.example.com\n    User me\n\nHost db\n\nHost cache\n    User me\n");
+        let messages: Vec<String> = structural_warnings(&config).into_iter().map(|w| w.message).collect();
+        // `db` is followed by a blank line, so it's just empty, not a mistake
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("'Host web' has no options because 'Host web.example.com' directly follows it"));
+    }
+
+    #[test]
+    fn globals_set_differently_in_two_files_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let foo = dir.path().join("10-foo");
+        let bar = dir.path().join("20-bar");
+        std::fs::write(&foo, "Compression yes\nUser me\nIdentityFile ~/.ssh/a\n").unwrap();
+        std::fs::write(&bar, "compression no\nUser me\nIdentityFile ~/.ssh/b\n").unwrap();
+        let main = dir.path().join("config");
+        std::fs::write(&main, format!("Include {}/*-*\nHost web\n", dir.path().display())).unwrap();
+        let config = SshConfig::parse_file(&main).unwrap();
+
+        // Same value (User) or repeatable (IdentityFile) doesn't conflict
+        let conflicts = conflicting_globals(&config);
+        assert_eq!(
+            conflicts,
+            [Conflict {
+                key: "Compression".to_string(),
+                settings: vec![(foo.clone(), "yes".to_string()), (bar, "no".to_string())],
+            }]
+        );
+        assert_eq!(conflicts[0].winner(), &(foo, "yes".to_string()));
+
+        // Different values within one file are the duplicate lint's business
+        assert_eq!(conflicting_globals(&parse("Port 22\nPort 2222\n")), []);
+    }
+
+    #[test]
+    fn patterns_defined_in_two_files_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = dir.path().join("extra");
+        std::fs::write(&extra, "Host WEB db\n\nHost *\n\nHost only-here\n").unwrap();
+        let main = dir.path().join("config");
+        std::fs::write(&main, format!("Host web,db\n\nHost *\n\nHost web\n\nInclude {}\n", extra.display())).unwrap();
+        let config = SshConfig::parse_file(&main).unwrap();
+
+        // `Host *` is spread on purpose, and `web` twice in one file is the
+        // within-file check's business
+        assert_eq!(cross_file_duplicate_patterns(&config), [("web,db".to_string(), vec![main, extra])]);
+    }
+
+    #[test]
+    fn booleans_are_normalized_and_odd_values_reported() {
+        let mut config = parse(
+            "Compression TRUE\n\nHost web\n    BatchMode \"Yes\"\n    ForwardX11 0\n    IdentitiesOnly no\n    PasswordAuthentication maybe\n    User True\n",
+        );
+        let report = normalize_booleans(&mut config);
+        assert_eq!(
+            report,
+            BooleanReport {
+                changed: 3,
+                unrecognized: vec!["PasswordAuthentication maybe".to_string()],
+            }
+        );
+        assert_eq!(
+            config.to_string(std::path::Path::new("config")),
+            "Compression yes\n\nHost web\n    BatchMode yes\n    ForwardX11 no\n    IdentitiesOnly no\n    PasswordAuthentication maybe\n    User True\n"
+        );
+        // Running it again finds nothing left to change
+        assert_eq!(normalize_booleans(&mut config).changed, 0);
+    }
+
+    #[test]
+    fn match_tagged_blocks_are_found_for_host_tags() {
+        let config = parse(
+            "Host web\n    Tag prod\n\nHost db\n    Tag prod\n\nMatch tagged prod,staging\n    User deploy\n\nMatch host other\n",
+        );
+        let usage = tag_usage(&config);
+        assert_eq!(
+            usage,
+            [TagUsage {
+                tag: "prod".to_string(),
+                hosts: vec![0, 2],
+                match_lines: vec!["tagged prod,staging".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn cleaning_up_formatting_fixes_every_finding_once() {
+        let mut config = parse(
+            "# top   \n\nHost web\n    User me\n\t# tabbed\n  # narrow  \n    Port 22\n\nHost db\n    User you\n",
+        );
+        let problems = |config: &SshConfig| -> Vec<(usize, &'static str)> {
+            formatting_findings(config).iter().map(|finding| (finding.line, finding.problem)).collect()
+        };
+        assert_eq!(
+            problems(&config),
+            [
+                (1, "trailing whitespace"),
+                (5, "indented with tabs, the file uses spaces"),
+                (6, "trailing whitespace"),
+                (6, "indented differently from the options"),
+            ]
+        );
+
+        assert_eq!(clean_up_formatting(&mut config), 3);
+        assert_eq!(problems(&config), []);
+        let cleaned = config.to_string(std::path::Path::new("config"));
+        assert_eq!(
+            cleaned,
+            "# top\n\nHost web\n    User me\n    # tabbed\n    # narrow\n    Port 22\n\nHost db\n    User you\n"
+        );
+
+        // A second run finds nothing left to change
+        assert_eq!(clean_up_formatting(&mut config), 0);
+        assert_eq!(config.to_string(std::path::Path::new("config")), cleaned);
+    }
+
+    #[test]
+    fn algorithm_lists_fold_later_modifiers_into_the_first() {
+        let merge = |values: &[&str]| merge_algorithm_lists(values);
+        assert_eq!(merge(&["aes256-ctr", "+aes128-ctr,aes256-ctr"]).as_deref(), Some("aes256-ctr,aes128-ctr"));
+        assert_eq!(merge(&["+ssh-rsa", "+ssh-dss"]).as_deref(), Some("+ssh-rsa,ssh-dss"));
+        assert_eq!(merge(&["-ssh-rsa", "-ssh-dss,ssh-rsa"]).as_deref(), Some("-ssh-rsa,ssh-dss"));
+        assert_eq!(merge(&["a,b-sha1,c-sha1,d", "-*-sha1"]).as_deref(), Some("a,d"));
+        assert_eq!(merge(&["a,b,c", "^c,b"]).as_deref(), Some("c,b,a"));
+        assert_eq!(merge(&["\"a,b\"", "+c"]).as_deref(), Some("a,b,c"));
+
+        // Nothing to combine into one value
+        assert_eq!(merge(&["a,b", "c,d"]), None);
+        assert_eq!(merge(&["-a", "+b"]), None);
+        assert_eq!(merge(&["+a", "^b"]), None);
+        assert_eq!(merge(&[]), None);
+    }
+}
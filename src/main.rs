@@ -1,14 +1,84 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod ssh_config;
+mod external_editor;
+mod options_table;
+mod post_save;
+mod settings;
+mod shortcuts;
+mod status;
+mod unsaved;
 
 use eframe::{egui, CreationContext};
-use ssh_config::{ConfigLine, SshConfig};
+use egui_ssh_config::ssh_config::{self, ConfigLine, HostHandle, MergePolicy, MoveDirection, ParseWarning, SaveReport, SshConfig, UpsertPolicy};
+use egui_ssh_config::audit::{self, Severity};
+use egui_ssh_config::filters::QuickFilter;
+use egui_ssh_config::inventory::{self, InventoryHost};
+use egui_ssh_config::system_ssh::{self, Difference};
+use egui_ssh_config::comments::{self, CommentKind};
+use egui_ssh_config::{diagnostics, env_vars, keywords, lint, matrix, pattern, search, tags};
+use options_table::OptionsTable;
+use settings::{AppSettings, HostSort, Theme};
+use shortcuts::Command;
+use status::StatusLog;
+use unsaved::{Choice, PendingAction, Step};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 use egui::{ViewportCommand, WindowLevel};
 
+// What to open on the first frame
+#[derive(Default)]
+enum StartupFile {
+    // ~/.ssh/config
+    #[default]
+    Default,
+    Path(PathBuf),
+    // Started with `--`: open nothing
+    None,
+}
+
+impl StartupFile {
+    // `egui-ssh-config [PATH]` opens PATH instead of ~/.ssh/config, and
+    // `egui-ssh-config --` starts without opening anything
+    fn from_args(mut args: impl Iterator<Item = std::ffi::OsString>) -> Self {
+        match args.next() {
+            Some(arg) if arg == "--" => match args.next() {
+                Some(path) => StartupFile::Path(PathBuf::from(path)),
+                None => StartupFile::None,
+            },
+            Some(path) => StartupFile::Path(PathBuf::from(path)),
+            None => StartupFile::Default,
+        }
+    }
+}
+
+// `egui-ssh-config --export-json PATH` prints the parsed lines of PATH (and
+// everything it includes) as a JSON array on stdout instead of starting the
+// GUI. Each line is an object whose "type" is its `ConfigLine` variant.
+fn export_json(path: Option<std::ffi::OsString>) -> Result<String, String> {
+    let path = path.ok_or("--export-json needs the path of a config file")?;
+    let config = SshConfig::parse_file(PathBuf::from(path))?;
+    for warning in &config.warnings {
+        eprintln!("warning: {}: {}", warning.file.display(), warning.message);
+    }
+    serde_json::to_string_pretty(&config.lines).map_err(|e| e.to_string())
+}
+
 fn main() -> Result<(), eframe::Error> {
+    let mut args = std::env::args_os().skip(1).peekable();
+    if args.next_if(|arg| arg == "--export-json").is_some() {
+        match export_json(args.next()) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    let startup_file = StartupFile::from_args(args);
+
     // Set up panic handler to allocate console on Windows if needed
     #[cfg(all(windows, not(debug_assertions)))]
     {
@@ -37,199 +107,2586 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "SSH Config Editor",
         options,
-        Box::new(|cc| Ok(Box::new(SshConfigApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(SshConfigApp::new(cc, startup_file)))),
     )
 }
 
+// One row of the hosts list; host variants hold indices into `config.lines`
+enum SidebarRow {
+    FileHeader(usize),
+    Host { idx: usize, score: i32 },
+    OptionMatch(String),
+    Include(usize),
+    // One of the files an Include line resolved to: (include index, file index)
+    IncludedFile(usize, usize),
+}
+
+// What the hosts list shows before the filters applied every frame (tags,
+// quick filters, changed only, collapsed files). Rebuilt only when `key`
+// changes, so a large config isn't searched, sorted and audited each frame.
+struct SidebarCache {
+    key: SidebarKey,
+    // Lines to list in display order, each with its fuzzy score and the
+    // options matching the search
+    listed: Vec<(usize, i32, Vec<String>)>,
+    changed_files: HashSet<PathBuf>,
+    // Only worked out while the list is limited to changed hosts
+    changed_hosts: HashSet<usize>,
+    insecure_hosts: HashSet<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SidebarKey {
+    // Lowercase search text
+    query: String,
+    fuzzy: bool,
+    search_options: bool,
+    sort: HostSort,
+    changed_only: bool,
+    // `SshConfigApp::generation` the cache was built at
+    generation: u64,
+}
+
+impl SidebarCache {
+    fn build(config: &SshConfig, saved_lines: &[ConfigLine], key: SidebarKey) -> Self {
+        let listed = if key.fuzzy {
+            // Hosts are listed best match first
+            let mut option_text = String::new();
+            let mut ranked: Vec<(usize, i32, Vec<String>)> = config
+                .hosts()
+                .filter_map(|host| {
+                    let mut best = search::fuzzy_score(host.pattern, &key.query);
+                    for (k, v) in host.options.iter().filter(|_| key.search_options) {
+                        option_text.clear();
+                        option_text.push_str(k);
+                        option_text.push(' ');
+                        option_text.push_str(v);
+                        best = best.max(search::fuzzy_score(&option_text, &key.query));
+                    }
+                    best.map(|score| (host.index, score, Vec::new()))
+                })
+                .collect();
+            ranked.sort_by_key(|&(_, score, _)| std::cmp::Reverse(score));
+            ranked
+        } else {
+            let mut order: Vec<usize> = (0..config.lines.len()).collect();
+            // Hosts first, Include lines after them
+            let pattern_key = |idx: usize| match &config.lines[idx] {
+                ConfigLine::HostEntry { pattern, .. } => (false, pattern.to_lowercase()),
+                _ => (true, String::new()),
+            };
+            match key.sort {
+                HostSort::FileOrder => {}
+                HostSort::Pattern => order.sort_by_cached_key(|&idx| pattern_key(idx)),
+                HostSort::FileThenPattern => {
+                    // Files keep the order they first appear in
+                    let mut file_rank: HashMap<&std::path::Path, usize> = HashMap::new();
+                    for line in &config.lines {
+                        let next = file_rank.len();
+                        file_rank.entry(line.source_file()).or_insert(next);
+                    }
+                    order.sort_by_cached_key(|&idx| (file_rank[config.lines[idx].source_file()], pattern_key(idx)));
+                }
+            }
+            // Filter by search query, keeping the options whose text matches
+            // to show under the row
+            order
+                .into_iter()
+                .filter_map(|idx| match &config.lines[idx] {
+                    ConfigLine::HostEntry { pattern, options, .. } if !key.query.is_empty() => {
+                        search::host_search_matches(pattern, options, &key.query, key.search_options)
+                            .map(|matching| (idx, 0, matching))
+                    }
+                    ConfigLine::HostEntry { .. } | ConfigLine::Include { .. } => Some((idx, 0, Vec::new())),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let changed_hosts = if key.changed_only { config.changed_hosts(saved_lines) } else { HashSet::new() };
+        Self {
+            listed,
+            changed_files: config.changed_files(saved_lines),
+            changed_hosts,
+            insecure_hosts: audit::security_findings(config)
+                .iter()
+                .filter(|finding| finding.severity >= Severity::Warning)
+                .filter_map(|finding| finding.host)
+                .collect(),
+            key,
+        }
+    }
+}
+
+// Narrowest the hosts panel can be dragged or restored to
+const MIN_SIDEBAR_WIDTH: f32 = 150.0;
+
+// How long the "Removed … — Undo" toast stays up
+const UNDO_TOAST_DURATION: Duration = Duration::from_secs(6);
+
+// Room left next to an option's value for its delete and repeat buttons
+const OPTION_BUTTONS_WIDTH: f32 = 70.0;
+
+// Names of machine-wide files (e.g. under /etc/ssh) in the hosts list
+const SYSTEM_FILE_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 126, 34);
+
+// Options that re-enable the algorithms old servers still need
+const LEGACY_OPTIONS: [(&str, &str); 5] = [
+    ("HostKeyAlgorithms", "+ssh-rsa,ssh-rsa-cert-v01@openssh.com,ssh-dss"),
+    ("PubkeyAcceptedAlgorithms", "+ssh-rsa,ssh-rsa-cert-v01@openssh.com"),
+    ("Ciphers", "+aes256-cbc,aes128-cbc,3des-cbc"),
+    ("MACs", "+hmac-sha1,hmac-md5"),
+    ("KexAlgorithms", "+diffie-hellman-group14-sha1,diffie-hellman-group1-sha1"),
+];
+
+// Last option deleted from a host, kept so the deletion can be undone
+struct RemovedOption {
+    host: HostHandle,
+    option_idx: usize,
+    key: String,
+    value: String,
+    option_comment: String,
+    removed_at: Instant,
+}
+
+// What started a background load, which decides the status message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadKind {
+    Open,
+    Default,
+    Reload,
+}
+
+// A config open in a tab other than the active one. The active tab's state
+// lives in the `SshConfigApp` fields of the same names and is swapped in and
+// out when switching tabs.
+#[derive(Default)]
+struct Document {
+    config: Option<SshConfig>,
+    config_path: Option<PathBuf>,
+    selected_host: Option<usize>,
+    selected_handle: Option<HostHandle>,
+    is_dirty: bool,
+    saved_lines: Vec<ConfigLine>,
+    removed_option: Option<RemovedOption>,
+    file_preview: Option<PathBuf>,
+}
+
+impl Document {
+    fn title(&self) -> String {
+        tab_title(self.config.as_ref(), self.config_path.as_ref(), self.is_dirty)
+    }
+}
+
+// Hosts read from a list of machines, previewed before they're created
+struct InventoryImport {
+    source: PathBuf,
+    hosts: Vec<InventoryHost>,
+    // Lines that couldn't be read
+    errors: Vec<String>,
+    target_file: Option<PathBuf>,
+}
+
+// Hosts picked by a glob, previewed before they're moved to another file
+struct HostMove {
+    glob: String,
+    target_file: Option<PathBuf>,
+}
+
+// Config being parsed on a worker thread, so a large include tree doesn't
+// freeze the UI
+struct PendingLoad {
+    path: PathBuf,
+    kind: LoadKind,
+    receiver: mpsc::Receiver<Result<SshConfig, String>>,
+}
+
 struct SshConfigApp {
     config: Option<SshConfig>,
     config_path: Option<PathBuf>,
+    // Index of the selected host, re-resolved through `selected_handle`
+    // whenever lines are reordered or the config is reloaded
     selected_host: Option<usize>,
-    status_message: String,
+    selected_handle: Option<HostHandle>,
+    status: StatusLog,
+    show_history: bool,
     initialized: bool,
+    startup_file: StartupFile,
+    loading: Option<PendingLoad>,
     search_query: String,
     search_focused: bool,
+    tag_filter: Option<String>,
     new_option_key: String,
     new_option_value: String,
     show_shortcuts: bool,
     is_dirty: bool,
-    show_quit_dialog: bool,
+    // Lines as they were last loaded or saved, for reverting single hosts
+    saved_lines: Vec<ConfigLine>,
+    unsaved_prompt: Option<PendingAction>,
+    // Set once quitting was confirmed, so the resulting close request isn't
+    // intercepted again
+    quit_confirmed: bool,
     show_new_host_dialog: bool,
+    show_include_dialog: bool,
+    new_include_path: String,
+    new_include_target_file: Option<PathBuf>,
     new_host_pattern: String,
     new_host_target_file: Option<PathBuf>,
-    always_on_top: bool,
+    merge_source: Option<(PathBuf, SshConfig)>,
+    merge_target_file: Option<PathBuf>,
+    merge_policy: MergePolicy,
+    inventory_import: Option<InventoryImport>,
+    host_move: Option<HostMove>,
+    // Host about to be turned into a Match block, asking whether to keep it
+    match_conversion: Option<HostHandle>,
+    // Ctrl+Shift+L was pressed with no host selected
+    show_legacy_prompt: bool,
+    // Top host of the hosts list as last drawn, e.g. the best search match
+    first_listed_host: Option<usize>,
+    settings: AppSettings,
+    show_settings: bool,
+    show_options_table: bool,
+    show_audit: bool,
+    show_matrix_export: bool,
+    // Keywords chosen as columns of the option matrix export
+    matrix_keys: Vec<String>,
+    show_ssh_compare: bool,
+    // File whose to-be-saved text is shown read-only
+    file_preview: Option<PathBuf>,
+    // File whose duplicate Host blocks are previewed merged
+    duplicate_merge_file: Option<PathBuf>,
+    compare_alias: String,
+    compare_result: Option<Result<Vec<Difference>, String>>,
+    // Checked the first time the comparison is opened
+    ssh_available: Option<bool>,
+    options_table: OptionsTable,
+    show_warnings: bool,
+    pending_create_dir: Option<PathBuf>,
+    // Patterns defined in several files, found when trying to save
+    duplicate_warning: Option<Vec<(String, Vec<PathBuf>)>>,
+    duplicates_confirmed: bool,
+    // Machine-wide files about to be written, found when trying to save;
+    // `system_save_all` if it was Save All Tabs that asked
+    system_save_warning: Option<Vec<PathBuf>>,
+    system_save_all: bool,
+    system_save_confirmed: bool,
+    // Edited files with invalid UTF-8 that saving would rewrite, found when
+    // trying to save; `lossy_save_all` if it was Save All Tabs that asked
+    lossy_save_warning: Option<Vec<PathBuf>>,
+    lossy_save_all: bool,
+    lossy_save_confirmed: bool,
+    // Why each file checked so far can't be written, None if it can. Filled
+    // as hosts are shown and cleared on load and save.
+    unwritable_files: HashMap<PathBuf, Option<String>>,
+    // Hosts list filter, not remembered between runs
+    show_changed_only: bool,
+    // Results of the running post-save commands
+    post_save_results: Vec<mpsc::Receiver<String>>,
+    removed_option: Option<RemovedOption>,
+    // Host built from a pasted ssh:// URL, waiting for confirmation
+    pasted_host: Option<ConfigLine>,
+    // Every open tab except the active one, in tab order; the active tab
+    // sits at `active_tab` among them
+    tabs: Vec<Document>,
+    active_tab: usize,
+    // Machine-wide config read as a read-only overlay, see
+    // `AppSettings::system_overlay`. Shared by all tabs and never saved.
+    system_config: Option<SshConfig>,
+    show_system_config: bool,
+    // Bumped on every edit, load, save and tab switch, so `sidebar_cache`
+    // knows when to rebuild
+    generation: u64,
+    sidebar_cache: Option<SidebarCache>,
+    // Home directory `~` expands to in every config this app loads
+    home: Option<PathBuf>,
 }
 
-impl SshConfigApp {
-    fn new(_cc: &CreationContext) -> Self {
-        Self {
-            config: None,
-            config_path: None,
-            selected_host: None,
-            status_message: String::new(),
-            initialized: false,
-            search_query: String::new(),
-            search_focused: false,
-            new_option_key: String::new(),
-            new_option_value: String::new(),
-            show_shortcuts: false,
-            is_dirty: false,
-            show_quit_dialog: false,
-            show_new_host_dialog: false,
-            new_host_pattern: String::new(),
-            new_host_target_file: None,
-            always_on_top: false,
+impl SshConfigApp {
+    fn new(cc: &CreationContext, startup_file: StartupFile) -> Self {
+        let settings = AppSettings::load(cc.storage);
+        cc.egui_ctx.set_theme(settings.theme.preference());
+        if settings.always_on_top {
+            cc.egui_ctx.send_viewport_cmd(ViewportCommand::WindowLevel(WindowLevel::AlwaysOnTop));
+        }
+
+        Self {
+            config: None,
+            config_path: None,
+            selected_host: None,
+            selected_handle: None,
+            status: StatusLog::default(),
+            show_history: false,
+            initialized: false,
+            startup_file,
+            loading: None,
+            search_query: String::new(),
+            search_focused: false,
+            tag_filter: None,
+            new_option_key: String::new(),
+            new_option_value: String::new(),
+            show_shortcuts: false,
+            is_dirty: false,
+            saved_lines: Vec::new(),
+            unsaved_prompt: None,
+            quit_confirmed: false,
+            show_new_host_dialog: false,
+            show_include_dialog: false,
+            new_include_path: String::new(),
+            new_include_target_file: None,
+            new_host_pattern: String::new(),
+            new_host_target_file: None,
+            merge_source: None,
+            merge_target_file: None,
+            merge_policy: MergePolicy::Skip,
+            inventory_import: None,
+            host_move: None,
+            match_conversion: None,
+            show_legacy_prompt: false,
+            first_listed_host: None,
+            settings,
+            show_settings: false,
+            show_options_table: false,
+            show_audit: false,
+            show_matrix_export: false,
+            matrix_keys: Vec::new(),
+            show_ssh_compare: false,
+            file_preview: None,
+            duplicate_merge_file: None,
+            compare_alias: String::new(),
+            compare_result: None,
+            ssh_available: None,
+            options_table: OptionsTable::default(),
+            show_warnings: false,
+            pending_create_dir: None,
+            duplicate_warning: None,
+            duplicates_confirmed: false,
+            system_save_warning: None,
+            system_save_all: false,
+            system_save_confirmed: false,
+            lossy_save_warning: None,
+            lossy_save_all: false,
+            lossy_save_confirmed: false,
+            unwritable_files: HashMap::new(),
+            show_changed_only: false,
+            post_save_results: Vec::new(),
+            removed_option: None,
+            pasted_host: None,
+            tabs: Vec::new(),
+            active_tab: 0,
+            system_config: None,
+            show_system_config: false,
+            generation: 0,
+            sidebar_cache: None,
+            home: dirs::home_dir(),
+        }
+    }
+
+    // Moves the active tab's state out, leaving an empty document behind
+    fn take_document(&mut self) -> Document {
+        Document {
+            config: self.config.take(),
+            config_path: self.config_path.take(),
+            selected_host: self.selected_host.take(),
+            selected_handle: self.selected_handle.take(),
+            is_dirty: std::mem::take(&mut self.is_dirty),
+            saved_lines: std::mem::take(&mut self.saved_lines),
+            removed_option: self.removed_option.take(),
+            file_preview: self.file_preview.take(),
+        }
+    }
+
+    fn put_document(&mut self, document: Document) {
+        self.config = document.config;
+        self.config_path = document.config_path;
+        self.selected_host = document.selected_host;
+        self.selected_handle = document.selected_handle;
+        self.is_dirty = document.is_dirty;
+        self.generation += 1;
+        self.saved_lines = document.saved_lines;
+        self.removed_option = document.removed_option;
+        self.file_preview = document.file_preview;
+        self.compare_result = None;
+        self.match_conversion = None;
+        self.sync_selection();
+    }
+
+    fn tab_count(&self) -> usize {
+        self.tabs.len() + 1
+    }
+
+    fn switch_tab(&mut self, index: usize) {
+        // A load in progress lands in whichever tab is active when it
+        // finishes, so stay put until then
+        if index == self.active_tab || index >= self.tab_count() || self.loading.is_some() {
+            return;
+        }
+        let current = self.take_document();
+        self.tabs.insert(self.active_tab, current);
+        let document = self.tabs.remove(index);
+        self.put_document(document);
+        self.active_tab = index;
+    }
+
+    // Opens an empty tab right after the active one and switches to it
+    fn new_tab(&mut self) {
+        let current = self.take_document();
+        self.tabs.insert(self.active_tab, current);
+        self.active_tab += 1;
+        self.put_document(Document::default());
+    }
+
+    // Drops the active tab, unsaved changes included, and activates the
+    // next one. Closing the last tab leaves an empty one.
+    fn close_tab(&mut self) {
+        if self.tabs.is_empty() {
+            self.put_document(Document::default());
+            return;
+        }
+        let next = self.active_tab.min(self.tabs.len() - 1);
+        let document = self.tabs.remove(next);
+        self.put_document(document);
+        self.active_tab = next;
+    }
+
+    fn any_dirty(&self) -> bool {
+        self.is_dirty || self.tabs.iter().any(|document| document.is_dirty)
+    }
+
+    fn show_tab_bar(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        let mut close = false;
+        ui.horizontal(|ui| {
+            for index in 0..self.tab_count() {
+                let title = match index.cmp(&self.active_tab) {
+                    std::cmp::Ordering::Less => self.tabs[index].title(),
+                    std::cmp::Ordering::Equal => tab_title(self.config.as_ref(), self.config_path.as_ref(), self.is_dirty),
+                    std::cmp::Ordering::Greater => self.tabs[index - 1].title(),
+                };
+                let is_active = index == self.active_tab;
+                if ui.selectable_label(is_active, title).clicked() {
+                    switch_to = Some(index);
+                }
+                if is_active && ui.small_button("✖").on_hover_text("Close tab  (Ctrl+W)").clicked() {
+                    close = true;
+                }
+                ui.separator();
+            }
+        });
+        if let Some(index) = switch_to {
+            self.switch_tab(index);
+        }
+        if close {
+            self.guard_unsaved(ctx, PendingAction::CloseTab);
+        }
+    }
+
+    fn save_config(&mut self, ctx: &egui::Context) {
+        // Hosts defined in several files are almost always a mistake made
+        // while moving hosts around, so ask before writing them
+        if !self.duplicates_confirmed && let Some(config) = &self.config {
+            let duplicates = lint::cross_file_duplicate_patterns(config);
+            if !duplicates.is_empty() {
+                self.duplicate_warning = Some(duplicates);
+                return;
+            }
+        }
+
+        // Changing machine-wide config is rarely what was meant
+        if !self.system_save_confirmed && let Some(config) = &self.config {
+            let system_files = config.changed_system_files(&self.saved_lines);
+            if !system_files.is_empty() {
+                self.system_save_warning = Some(system_files);
+                self.system_save_all = false;
+                return;
+            }
+        }
+
+        // Saving a file that had invalid UTF-8 loses those bytes for good
+        if !self.lossy_save_confirmed && let Some(config) = &self.config {
+            let lossy_files = config.modified_lossy_files();
+            if !lossy_files.is_empty() {
+                self.lossy_save_warning = Some(lossy_files);
+                self.lossy_save_all = false;
+                return;
+            }
+        }
+
+        // Offer to create a missing target directory (e.g. ~/.ssh) first
+        if let (Some(config), Some(path)) = (&self.config, &self.config_path) {
+            let missing_dir = config
+                .save_order(path)
+                .iter()
+                .filter_map(|p| p.parent())
+                .find(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+                .map(|dir| dir.to_path_buf());
+            if missing_dir.is_some() {
+                self.pending_create_dir = missing_dir;
+                return;
+            }
+        }
+
+        self.duplicates_confirmed = false;
+        self.system_save_confirmed = false;
+        let write_lossy = std::mem::take(&mut self.lossy_save_confirmed);
+        self.unwritable_files.clear();
+        if let (Some(config), Some(path)) = (&mut self.config, &self.config_path) {
+            let (report, lossy_written) = write_document(config, path, &mut self.saved_lines, write_lossy);
+
+            let mut message = format!("Saved {} file(s)", report.written.len());
+            // The files on disk now hold the replacement characters
+            if lossy_written > 0 {
+                message.push_str(&format!(
+                    " (invalid UTF-8 in {} file(s) was replaced with U+FFFD)",
+                    lossy_written
+                ));
+            }
+            for (file, reason) in &report.skipped {
+                message.push_str(&format!("; skipped {}: {}", file.display(), reason));
+            }
+            self.status.set(message);
+            self.is_dirty = !report.skipped.is_empty();
+            self.generation += 1;
+
+            if report.written.contains(path) {
+                let path = path.clone();
+                self.run_post_save(ctx, &path);
+            }
+        } else {
+            self.status.set("No file loaded");
+        }
+    }
+
+    // Saves every tab with unsaved changes. Unlike `save_config` this doesn't
+    // stop to ask about duplicate hosts or missing directories; files that
+    // can't be written, including those in a missing directory, are skipped
+    // and reported instead. Machine-wide files still need confirming.
+    fn save_all_documents(&mut self, ctx: &egui::Context) {
+        if !self.system_save_confirmed {
+            let active = (self.is_dirty, &self.config, &self.saved_lines);
+            let mut system_files: Vec<PathBuf> = self
+                .tabs
+                .iter()
+                .map(|document| (document.is_dirty, &document.config, &document.saved_lines))
+                .chain(std::iter::once(active))
+                .filter_map(|(dirty, config, saved_lines)| dirty.then_some(config.as_ref()?.changed_system_files(saved_lines)))
+                .flatten()
+                .collect();
+            if !system_files.is_empty() {
+                system_files.sort();
+                system_files.dedup();
+                self.system_save_warning = Some(system_files);
+                self.system_save_all = true;
+                return;
+            }
+        }
+        if !self.lossy_save_confirmed {
+            let active = (self.is_dirty, &self.config);
+            let mut lossy_files: Vec<PathBuf> = self
+                .tabs
+                .iter()
+                .map(|document| (document.is_dirty, &document.config))
+                .chain(std::iter::once(active))
+                .filter_map(|(dirty, config)| dirty.then_some(config.as_ref()?.modified_lossy_files()))
+                .flatten()
+                .collect();
+            if !lossy_files.is_empty() {
+                lossy_files.sort();
+                lossy_files.dedup();
+                self.lossy_save_warning = Some(lossy_files);
+                self.lossy_save_all = true;
+                return;
+            }
+        }
+        self.system_save_confirmed = false;
+        let write_lossy = std::mem::take(&mut self.lossy_save_confirmed);
+        self.unwritable_files.clear();
+
+        let active = self.take_document();
+        self.tabs.insert(self.active_tab, active);
+        let summary = save_documents(&mut self.tabs, write_lossy);
+        let active = self.tabs.remove(self.active_tab);
+        self.put_document(active);
+
+        self.status.set(summary.message());
+        for path in summary.written_mains {
+            self.run_post_save(ctx, &path);
+        }
+    }
+
+    // Points `selected_host` back at the host remembered in `selected_handle`
+    // after lines moved, or clears it if that host is gone. Never leaves it on
+    // a different host or on a line that isn't a host.
+    fn sync_selection(&mut self) {
+        let Some(config) = &self.config else {
+            self.selected_host = None;
+            return;
+        };
+        let still_valid = match (self.selected_host, &self.selected_handle) {
+            (Some(idx), Some(handle)) => config.host_handle(idx).as_ref() == Some(handle),
+            (Some(idx), None) => config.host_handle(idx).is_some(),
+            (None, _) => true,
+        };
+        if !still_valid {
+            self.selected_host = self.selected_handle.as_ref().and_then(|h| config.resolve_host(h));
+        }
+    }
+
+    // Parses `path` on a worker thread; `poll_load` swaps the result in once
+    // it arrives. Starting another load drops the previous receiver, so a
+    // stale result is discarded, and a thread still running when the window
+    // closes just fails to send.
+    fn start_load(&mut self, ctx: &egui::Context, path: PathBuf, kind: LoadKind) {
+        let (sender, receiver) = mpsc::channel();
+        let thread_path = path.clone();
+        let options = self.settings.parse_options();
+        let home = self.home.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(SshConfig::parse_file_with_home(&thread_path, options, home));
+            ctx.request_repaint();
+        });
+        self.loading = Some(PendingLoad { path, kind, receiver });
+    }
+
+    fn poll_load(&mut self) {
+        let Some(pending) = self.loading.take() else {
+            return;
+        };
+        let result = match pending.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => {
+                self.loading = Some(pending);
+                return;
+            }
+            Err(mpsc::TryRecvError::Disconnected) => Err("parser thread stopped unexpectedly".to_string()),
+        };
+        let PendingLoad { path, kind, .. } = pending;
+
+        match result {
+            Ok(mut config) => {
+                let included_count = config.included_files.len();
+                if kind == LoadKind::Default
+                    && let Some(ssh_dir) = path.parent()
+                    && let Some(message) = ssh_config::loose_permissions_warning(ssh_dir)
+                {
+                    config.warnings.push(ParseWarning { file: ssh_dir.to_path_buf(), message });
+                }
+                self.saved_lines = config.lines.clone();
+                self.config = Some(config);
+                self.config_path = Some(path.clone());
+                self.is_dirty = false;
+                self.generation += 1;
+                self.unwritable_files.clear();
+                self.sync_selection();
+                let verb = if kind == LoadKind::Reload { "Reloaded" } else { "Loaded" };
+                self.status.set(if included_count > 0 {
+                    format!("{}: {} ({} included files)", verb, path.display(), included_count)
+                } else {
+                    format!("{}: {}", verb, path.display())
+                });
+            }
+            Err(e) => {
+                self.status.set(match kind {
+                    LoadKind::Open => format!("Error loading file: {}", e),
+                    LoadKind::Default => format!("Error loading default config: {}", e),
+                    LoadKind::Reload => format!("Error reloading: {}", e),
+                });
+            }
+        }
+    }
+
+    fn poll_post_save(&mut self) {
+        let status = &mut self.status;
+        self.post_save_results.retain(|receiver| match receiver.try_recv() {
+            Ok(message) => {
+                status.set(message);
+                false
+            }
+            Err(mpsc::TryRecvError::Empty) => true,
+            Err(mpsc::TryRecvError::Disconnected) => false,
+        });
+    }
+
+    fn run_post_save(&mut self, ctx: &egui::Context, path: &std::path::Path) {
+        let command = self.settings.post_save_command.trim();
+        if !command.is_empty() {
+            self.post_save_results.push(post_save::spawn(ctx, post_save::expand_command(command, path)));
+        }
+    }
+
+    fn load_default_config(&mut self, ctx: &egui::Context) {
+        if let Some(home) = &self.home {
+            let default_path = home.join(".ssh").join("config");
+            if default_path.exists() {
+                self.start_load(ctx, default_path, LoadKind::Default);
+            } else {
+                self.status.set(format!("Default config not found: {}", default_path.display()));
+            }
+        } else {
+            self.status.set("Home directory unknown, open a config with File → Open");
+        }
+    }
+
+    fn show_shortcuts_popup(&mut self, ctx: &egui::Context) {
+        egui::Window::new("⌨ Keyboard Shortcuts")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(400.0);
+
+                for (index, (category, shortcuts)) in shortcuts::by_category().into_iter().enumerate() {
+                    if index > 0 {
+                        ui.add_space(10.0);
+                    }
+                    ui.heading(category);
+                    ui.separator();
+                    let mut shown: Vec<Command> = Vec::new();
+                    for shortcut in &shortcuts {
+                        if shown.contains(&shortcut.command) {
+                            continue;
+                        }
+                        shown.push(shortcut.command);
+                        // All keys of a command on one line
+                        let keys: Vec<String> = shortcuts
+                            .iter()
+                            .filter(|other| other.command == shortcut.command)
+                            .map(|other| other.label())
+                            .collect();
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(keys.join(" / ")).monospace().strong());
+                            ui.label(shortcut.description);
+                        });
+                    }
+                }
+
+                ui.add_space(10.0);
+                ui.heading("Legacy SSH Options");
+                ui.separator();
+                ui.label(egui::RichText::new("Adds these options:").color(egui::Color32::GRAY));
+                ui.label(egui::RichText::new("  • HostKeyAlgorithms +ssh-rsa,ssh-rsa-cert-v01@openssh.com").monospace().small());
+                ui.label(egui::RichText::new("  • PubkeyAcceptedAlgorithms +ssh-rsa,ssh-rsa-cert-v01@openssh.com").monospace().small());
+                ui.label(egui::RichText::new("  • Ciphers +aes256-cbc,aes128-cbc").monospace().small());
+                ui.label(egui::RichText::new("  • MACs +aes256-cbc,hmac-sha1").monospace().small());
+                ui.label(egui::RichText::new("  • KexAlgorithms +diffie-hellman-group1-sha1").monospace().small());
+                ui.add_space(15.0);
+                ui.separator();
+                if ui.button("Close").clicked() {
+                    self.show_shortcuts = false;
+                }
+            });
+    }
+
+    fn show_warnings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_warnings;
+        let mut clean_up = false;
+        egui::Window::new("⚠ Warnings")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                let Some(config) = &self.config else {
+                    return;
+                };
+                let conflicts = lint::conflicting_globals(config);
+                let tag_usage = lint::tag_usage(config);
+                let formatting = lint::formatting_findings(config);
+                if config.warnings.is_empty() && conflicts.is_empty() && tag_usage.is_empty() && formatting.is_empty() {
+                    ui.label("No warnings");
+                    return;
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for warning in &config.warnings {
+                        ui.label(
+                            egui::RichText::new(warning.file.display().to_string())
+                                .color(egui::Color32::GRAY),
+                        );
+                        ui.label(format!("  {}", warning.message));
+                    }
+
+                    if !conflicts.is_empty() {
+                        ui.add_space(10.0);
+                        ui.label(egui::RichText::new("Conflicting global options").strong());
+                        for conflict in &conflicts {
+                            ui.label(format!("  {} is set differently in several files:", conflict.key));
+                            for (idx, (file, value)) in conflict.settings.iter().enumerate() {
+                                let text = format!("    {} {}  ({})", conflict.key, value, file.display());
+                                if idx == 0 {
+                                    ui.label(egui::RichText::new(format!("{}  ✓ in effect", text)).strong());
+                                } else {
+                                    ui.label(egui::RichText::new(text).color(egui::Color32::GRAY));
+                                }
+                            }
+                        }
+                    }
+
+                    if !tag_usage.is_empty() {
+                        ui.add_space(10.0);
+                        ui.label(egui::RichText::new("SSH tags (Tag / Match tagged)").strong());
+                        for usage in &tag_usage {
+                            let hosts: Vec<&str> = usage
+                                .hosts
+                                .iter()
+                                .filter_map(|&idx| match config.lines.get(idx) {
+                                    Some(ConfigLine::HostEntry { pattern, .. }) => Some(pattern.as_str()),
+                                    _ => None,
+                                })
+                                .collect();
+                            ui.label(format!("  Tag {}: {}", usage.tag, hosts.join(", ")));
+                            if usage.match_lines.is_empty() {
+                                ui.label(
+                                    egui::RichText::new("    no Match tagged block selects this tag")
+                                        .color(egui::Color32::GRAY),
+                                );
+                            }
+                            for criteria in &usage.match_lines {
+                                ui.label(
+                                    egui::RichText::new(format!("    Match {}", criteria)).color(egui::Color32::GRAY),
+                                );
+                            }
+                        }
+                    }
+
+                    if !formatting.is_empty() {
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new("Formatting").strong());
+                            if ui
+                                .button("Clean up formatting")
+                                .on_hover_text("Trim trailing whitespace and indent comments like the options")
+                                .clicked()
+                            {
+                                clean_up = true;
+                            }
+                        });
+                        for finding in &formatting {
+                            ui.label(format!("  {}:{}: {}", finding.file.display(), finding.line, finding.problem));
+                        }
+                    }
+                });
+            });
+        self.show_warnings = open;
+
+        if clean_up && let Some(config) = &mut self.config {
+            let changed = lint::clean_up_formatting(config);
+            if changed > 0 {
+                self.is_dirty = true;
+                self.generation += 1;
+            }
+            self.status.set(format!("Cleaned up formatting of {} line(s)", changed));
+        }
+    }
+
+    // (Re)reads the system config if the overlay is enabled and it exists
+    fn load_system_config(&mut self) {
+        self.system_config = None;
+        if !self.settings.system_overlay {
+            return;
+        }
+        let Some(path) = ssh_config::system_config_path().filter(|path| path.is_file()) else {
+            return;
+        };
+        match SshConfig::parse_file_with_home(&path, self.settings.parse_options(), self.home.clone()) {
+            Ok(config) => self.system_config = Some(config),
+            Err(e) => self.status.set(format!("Error loading {}: {}", path.display(), e)),
+        }
+    }
+
+    // The system config overlay, greyed out since it is never edited or saved
+    fn show_system_config_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_system_config;
+        egui::Window::new("🔒 System Config")
+            .open(&mut open)
+            .default_size([600.0, 500.0])
+            .show(ctx, |ui| {
+                let (Some(system), Some(main_path)) = (&self.system_config, ssh_config::system_config_path()) else {
+                    ui.label("Not loaded. Enable it under Settings → System config.");
+                    return;
+                };
+                ui.label(
+                    egui::RichText::new("Read-only. ssh reads it after your config, so your settings take precedence.")
+                        .color(egui::Color32::GRAY),
+                );
+                ui.separator();
+                egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
+                    for file in system.save_order(&main_path) {
+                        ui.label(egui::RichText::new(file.display().to_string()).color(SYSTEM_FILE_COLOR));
+                        ui.label(egui::RichText::new(system.to_string(&file)).monospace().weak());
+                        ui.add_space(10.0);
+                    }
+                });
+            });
+        self.show_system_config = open;
+    }
+
+    // Exactly what saving would write to the previewed file, read-only
+    fn show_file_preview(&mut self, ctx: &egui::Context) {
+        let (Some(config), Some(file)) = (&self.config, &self.file_preview) else {
+            self.file_preview = None;
+            return;
+        };
+        let mut open = true;
+        let text = config.to_string(file);
+        let changed = config.changed_files(&self.saved_lines).contains(file);
+        let system = config.is_system_file(file);
+        let title = file
+            .file_name()
+            .map_or_else(|| file.display().to_string(), |n| n.to_string_lossy().into_owned());
+
+        egui::Window::new(format!("{} {}", if system { "🔒" } else { "📄" }, title))
+            .id(egui::Id::new("file_preview"))
+            .open(&mut open)
+            .default_size([600.0, 500.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(file.display().to_string()).color(egui::Color32::GRAY));
+                    if system {
+                        ui.label(egui::RichText::new("machine-wide file").color(SYSTEM_FILE_COLOR));
+                    }
+                    if changed {
+                        ui.label(egui::RichText::new("● unsaved changes").color(egui::Color32::YELLOW));
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("📋 Copy").clicked() {
+                            ctx.copy_text(text.clone());
+                        }
+                    });
+                });
+                ui.separator();
+                egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
+                    ui.label(config_text_job(ui, &text));
+                });
+            });
+
+        if !open {
+            self.file_preview = None;
+        }
+    }
+
+    fn show_ssh_compare_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_ssh_compare;
+        let ssh_available = *self.ssh_available.get_or_insert_with(system_ssh::ssh_available);
+
+        egui::Window::new("🔍 Compare with ssh -G")
+            .open(&mut open)
+            .default_width(550.0)
+            .show(ctx, |ui| {
+                if !ssh_available {
+                    ui.label("No ssh binary found in PATH");
+                    return;
+                }
+                let (Some(config), Some(config_path)) = (&self.config, &self.config_path) else {
+                    ui.label("No file loaded");
+                    return;
+                };
+                // ssh only reads the system config next to the default one
+                let system = self
+                    .system_config
+                    .as_ref()
+                    .filter(|_| config.home().is_some_and(|home| *config_path == home.join(".ssh").join("config")));
+                ui.label(
+                    egui::RichText::new(if system.is_some() {
+                        "Checks this editor's host matching, system config included, against `ssh -G <alias>`."
+                    } else {
+                        "Checks this editor's host matching against `ssh -G -F <config> <alias>`."
+                    })
+                    .color(egui::Color32::GRAY),
+                );
+                if self.is_dirty {
+                    ui.label(
+                        egui::RichText::new("⚠ ssh reads the saved files; save first to compare unsaved changes")
+                            .color(egui::Color32::YELLOW),
+                    );
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Alias:");
+                    let response = ui.text_edit_singleline(&mut self.compare_alias);
+                    let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    let alias = self.compare_alias.trim();
+                    if (ui.add_enabled(!alias.is_empty(), egui::Button::new("Compare")).clicked() || enter)
+                        && !alias.is_empty()
+                    {
+                        self.compare_result = Some(
+                            system_ssh::resolve_with_ssh(system.is_none().then_some(config_path.as_path()), alias)
+                                .map(|resolved| system_ssh::compare(config, system, alias, &resolved)),
+                        );
+                    }
+                });
+                ui.separator();
+
+                match &self.compare_result {
+                    None => {}
+                    Some(Err(e)) => {
+                        ui.label(egui::RichText::new(format!("ssh failed: {}", e)).color(egui::Color32::RED));
+                    }
+                    Some(Ok(differences)) if differences.is_empty() => {
+                        ui.label("✔ ssh resolves every option set here to the same value");
+                    }
+                    Some(Ok(differences)) => {
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            egui::Grid::new("ssh_compare_grid").num_columns(3).striped(true).show(ui, |ui| {
+                                ui.label(egui::RichText::new("Keyword").strong());
+                                ui.label(egui::RichText::new("This editor").strong());
+                                ui.label(egui::RichText::new("ssh -G").strong());
+                                ui.end_row();
+                                for difference in differences {
+                                    ui.label(&difference.key);
+                                    ui.label(difference.ours.as_deref().unwrap_or("(not set)"));
+                                    ui.label(
+                                        egui::RichText::new(difference.ssh.as_deref().unwrap_or("(not set)"))
+                                            .color(egui::Color32::YELLOW),
+                                    );
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                    }
+                }
+            });
+
+        self.show_ssh_compare = open;
+    }
+
+    // Shows what a file looks like with its duplicate Host blocks merged,
+    // and applies it on confirmation
+    fn show_duplicate_merge_window(&mut self, ctx: &egui::Context) {
+        let (Some(config), Some(file)) = (&self.config, &self.duplicate_merge_file) else {
+            self.duplicate_merge_file = None;
+            return;
+        };
+        let files = config.files_with_duplicate_hosts();
+        let mut preview = config.clone();
+        let report = preview.merge_duplicate_hosts(file);
+        let text = preview.to_string(file);
+        let mut chosen = file.clone();
+        let mut apply = false;
+        let mut cancel = false;
+
+        egui::Window::new("Merge Duplicate Hosts")
+            .collapsible(false)
+            .default_size([600.0, 500.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    egui::ComboBox::from_id_salt("duplicate_merge_file")
+                        .selected_text(chosen.display().to_string())
+                        .show_ui(ui, |ui| {
+                            for path in &files {
+                                ui.selectable_value(&mut chosen, path.clone(), path.display().to_string());
+                            }
+                        });
+                });
+                ui.label(format!("{} Host block(s) will be folded into the first block with the same pattern.", report.merged));
+                for conflict in &report.conflicts {
+                    ui.label(egui::RichText::new(format!("⚠ {}", conflict)).color(egui::Color32::YELLOW));
+                }
+                ui.separator();
+                egui::ScrollArea::both().max_height(350.0).auto_shrink(false).show(ui, |ui| {
+                    ui.label(config_text_job(ui, &text));
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    apply = ui.add_enabled(report.merged > 0, egui::Button::new("Merge")).clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if apply && let Some(config) = &mut self.config {
+            let report = config.merge_duplicate_hosts(&chosen);
+            self.is_dirty = true;
+            self.generation += 1;
+            self.sync_selection();
+            self.status.set(format!(
+                "Merged {} duplicate host block(s), dropped {} conflicting option(s)",
+                report.merged,
+                report.conflicts.len()
+            ));
+            self.duplicate_merge_file = None;
+        } else if cancel {
+            self.duplicate_merge_file = None;
+        } else {
+            self.duplicate_merge_file = Some(chosen);
+        }
+    }
+
+    // Hosts × chosen keywords table, saved as CSV or Markdown
+    fn show_matrix_export_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_matrix_export;
+        let mut export = None;
+
+        egui::Window::new("📊 Export Option Matrix")
+            .open(&mut open)
+            .default_width(350.0)
+            .show(ctx, |ui| {
+                let Some(config) = &self.config else {
+                    ui.label("No file loaded");
+                    return;
+                };
+                let keys = matrix::keys_in_use(config);
+                if keys.is_empty() {
+                    ui.label("No host sets any options");
+                    return;
+                }
+                ui.label("Columns:");
+                ui.horizontal(|ui| {
+                    if ui.small_button("All").clicked() {
+                        self.matrix_keys = keys.clone();
+                    }
+                    if ui.small_button("None").clicked() {
+                        self.matrix_keys.clear();
+                    }
+                });
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for key in &keys {
+                        let mut checked = self.matrix_keys.contains(key);
+                        if ui.checkbox(&mut checked, key.as_str()).changed() {
+                            if checked {
+                                self.matrix_keys.push(key.clone());
+                            } else {
+                                self.matrix_keys.retain(|k| k != key);
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+                ui.add_enabled_ui(!self.matrix_keys.is_empty(), |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Save CSV…").clicked() {
+                            export = Some("csv");
+                        }
+                        if ui.button("Save Markdown…").clicked() {
+                            export = Some("md");
+                        }
+                    });
+                });
+            });
+        self.show_matrix_export = open;
+
+        let (Some(extension), Some(config)) = (export, &self.config) else {
+            return;
+        };
+        // Columns in the order the keywords are listed, not the order ticked
+        let keys: Vec<String> = matrix::keys_in_use(config)
+            .into_iter()
+            .filter(|key| self.matrix_keys.contains(key))
+            .collect();
+        let rows = matrix::option_matrix(config, &keys);
+        let text = if extension == "csv" { matrix::to_csv(&rows) } else { matrix::to_markdown(&rows) };
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter(extension, &[extension])
+            .set_file_name(format!("ssh-options.{}", extension))
+            .save_file()
+        {
+            match std::fs::write(&path, text) {
+                Ok(()) => self.status.set(format!("Exported {} hosts to {}", rows.len() - 1, path.display())),
+                Err(e) => self.status.set(format!("Error exporting: {}", e)),
+            }
+        }
+    }
+
+    fn show_audit_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_audit;
+        let mut select = None;
+
+        egui::Window::new("🔓 Security Audit")
+            .open(&mut open)
+            .default_width(550.0)
+            .show(ctx, |ui| {
+                let Some(config) = &self.config else {
+                    return;
+                };
+                let findings = audit::security_findings(config);
+                let system_findings = self.system_config.as_ref().map(audit::security_findings).unwrap_or_default();
+                if findings.is_empty() && system_findings.is_empty() {
+                    ui.label("No risky settings found");
+                    return;
+                }
+                ui.label(
+                    egui::RichText::new("Advisory only: these settings are allowed, but weaken security.")
+                        .color(egui::Color32::GRAY),
+                );
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for finding in &findings {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new(format!("[{}]", finding.severity.label()))
+                                    .color(severity_color(finding.severity))
+                                    .strong(),
+                            );
+                            match finding.host.and_then(|idx| config.lines.get(idx).map(|line| (idx, line))) {
+                                Some((idx, ConfigLine::HostEntry { pattern, .. })) => {
+                                    if ui.link(format!("Host {}", pattern)).clicked() {
+                                        select = Some(idx);
+                                    }
+                                }
+                                _ => {
+                                    ui.label("(global)");
+                                }
+                            }
+                            ui.label(egui::RichText::new(format!("{} {}", finding.key, finding.value)).monospace());
+                        });
+                        ui.label(egui::RichText::new(format!("    {}", finding.explanation)).color(egui::Color32::GRAY));
+                    }
+
+                    if let Some(system) = &self.system_config
+                        && !system_findings.is_empty()
+                    {
+                        ui.add_space(10.0);
+                        ui.label(egui::RichText::new("🔒 System config (read-only)").strong().color(SYSTEM_FILE_COLOR));
+                        for finding in &system_findings {
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new(format!("[{}]", finding.severity.label()))
+                                        .color(severity_color(finding.severity))
+                                        .strong(),
+                                );
+                                match finding.host.and_then(|idx| system.lines.get(idx)) {
+                                    Some(ConfigLine::HostEntry { pattern, .. }) => ui.weak(format!("Host {}", pattern)),
+                                    _ => ui.weak("(global)"),
+                                };
+                                ui.label(egui::RichText::new(format!("{} {}", finding.key, finding.value)).monospace());
+                            });
+                            ui.label(egui::RichText::new(format!("    {}", finding.explanation)).color(egui::Color32::GRAY));
+                        }
+                    }
+                });
+            });
+
+        if select.is_some() {
+            self.selected_host = select;
+        }
+        self.show_audit = open;
+    }
+
+    fn show_create_dir_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dir) = self.pending_create_dir.clone() else {
+            return;
+        };
+
+        egui::Window::new("📁 Missing Directory")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(300.0);
+
+                ui.label(format!("The directory {} does not exist.", dir.display()));
+                if cfg!(unix) {
+                    ui.label("Create it (with permissions 700) and save?");
+                } else {
+                    ui.label("Create it and save?");
+                }
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Create and Save").clicked() {
+                        self.pending_create_dir = None;
+                        match ssh_config::ensure_ssh_dir(&dir) {
+                            Ok(_) => self.save_config(ctx),
+                            Err(e) => self.status.set(format!("Error creating directory: {}", e)),
+                        }
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        self.pending_create_dir = None;
+                        self.duplicates_confirmed = false;
+                        self.system_save_confirmed = false;
+                    }
+                });
+            });
+    }
+
+    fn show_duplicate_dialog(&mut self, ctx: &egui::Context) {
+        let Some(duplicates) = &self.duplicate_warning else {
+            return;
+        };
+        let mut keep: Option<(String, PathBuf)> = None;
+        let mut save = false;
+        let mut cancel = false;
+
+        egui::Window::new("⚠ Hosts in Several Files")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(400.0);
+
+                if duplicates.is_empty() {
+                    ui.label("Every host is now defined in one file only.");
+                } else {
+                    ui.label("These hosts are defined in more than one file. ssh uses the options of the first file it reads and ignores the rest.");
+                }
+                ui.add_space(10.0);
+
+                for (pattern, files) in duplicates {
+                    ui.label(egui::RichText::new(format!("Host {}", pattern)).strong());
+                    for file in files {
+                        ui.horizontal(|ui| {
+                            ui.add_space(20.0);
+                            ui.label(file.display().to_string());
+                            if ui.small_button("Keep only this one").clicked() {
+                                keep = Some((pattern.clone(), file.clone()));
+                            }
+                        });
+                    }
+                }
+
+                ui.add_space(10.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let label = if duplicates.is_empty() { "Save" } else { "Save Anyway" };
+                    save = ui.button(label).clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if let Some((pattern, keep_file)) = keep
+            && let Some(config) = &mut self.config
+        {
+            let before = config.lines.len();
+            config.lines.retain(|line| {
+                !matches!(line, ConfigLine::HostEntry { pattern: p, source_file, .. }
+                    if pattern::same_pattern_list(p, &pattern) && *source_file != keep_file)
+            });
+            self.is_dirty = true;
+            self.generation += 1;
+            self.status.set(format!(
+                "Removed {} other definition(s) of '{}', kept the one in {}",
+                before - config.lines.len(),
+                pattern,
+                keep_file.display()
+            ));
+            self.duplicate_warning = Some(lint::cross_file_duplicate_patterns(config));
+            self.sync_selection();
+        }
+        if save {
+            self.duplicate_warning = None;
+            self.duplicates_confirmed = true;
+            self.save_config(ctx);
+        } else if cancel {
+            self.duplicate_warning = None;
+        }
+    }
+
+    fn show_system_save_dialog(&mut self, ctx: &egui::Context) {
+        let Some(files) = &self.system_save_warning else {
+            return;
+        };
+        let mut save = false;
+        let mut cancel = false;
+
+        egui::Window::new("🔒 System Files")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(400.0);
+
+                ui.label("These files look like machine-wide config, shared by every user of this computer:");
+                ui.add_space(10.0);
+                for file in files {
+                    ui.label(egui::RichText::new(format!("🔒 {}", file.display())).color(SYSTEM_FILE_COLOR));
+                }
+                ui.add_space(10.0);
+                ui.label("Save your changes to them anyway?");
+
+                ui.add_space(10.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    save = ui.button("Save System Files").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if save {
+            self.system_save_warning = None;
+            self.system_save_confirmed = true;
+            if self.system_save_all {
+                self.save_all_documents(ctx);
+            } else {
+                self.save_config(ctx);
+            }
+        } else if cancel {
+            self.system_save_warning = None;
+            self.duplicates_confirmed = false;
+        }
+    }
+
+    fn show_lossy_save_dialog(&mut self, ctx: &egui::Context) {
+        let Some(files) = &self.lossy_save_warning else {
+            return;
+        };
+        let mut save = false;
+        let mut cancel = false;
+
+        egui::Window::new("⚠ Invalid UTF-8")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(400.0);
+
+                ui.label("These edited files contained invalid UTF-8 when they were loaded:");
+                ui.add_space(10.0);
+                for file in files {
+                    ui.label(egui::RichText::new(file.display().to_string()).color(egui::Color32::YELLOW));
+                }
+                ui.add_space(10.0);
+                ui.label("Saving them replaces the invalid bytes with U+FFFD for good. Save anyway?");
+
+                ui.add_space(10.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    save = ui.button("Save Anyway").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if save {
+            self.lossy_save_warning = None;
+            self.lossy_save_confirmed = true;
+            // Only asked once the system files were confirmed, if there were any
+            self.system_save_confirmed = true;
+            if self.lossy_save_all {
+                self.save_all_documents(ctx);
+            } else {
+                self.save_config(ctx);
+            }
+        } else if cancel {
+            self.lossy_save_warning = None;
+            self.duplicates_confirmed = false;
+            self.system_save_confirmed = false;
+        }
+    }
+
+    // Asks whether the host turned into a Match block stays as well
+    fn show_match_conversion_dialog(&mut self, ctx: &egui::Context) {
+        let (Some(config), Some(handle)) = (&mut self.config, &self.match_conversion) else {
+            self.match_conversion = None;
+            return;
+        };
+        let Some(index) = config.resolve_host(handle) else {
+            self.match_conversion = None;
+            return;
+        };
+        let Some(block) = ssh_config::host_to_match(&config.lines[index]) else {
+            self.match_conversion = None;
+            return;
+        };
+        let mut keep_host = None;
+        let mut cancel = false;
+
+        egui::Window::new("Convert to Match Block")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(400.0);
+
+                ui.label(format!("Host {} becomes:", handle.pattern));
+                ui.add_space(5.0);
+                ui.label(egui::RichText::new(config.line_to_string(&block)).monospace());
+                ui.add_space(5.0);
+                ui.label(
+                    egui::RichText::new(
+                        "ssh tests `Match host` against the name after HostName is applied, not the alias.",
+                    )
+                    .color(egui::Color32::GRAY),
+                );
+
+                ui.add_space(10.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Replace Host").clicked() {
+                        keep_host = Some(false);
+                    }
+                    if ui.button("Keep Host Too").clicked() {
+                        keep_host = Some(true);
+                    }
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if let Some(keep_host) = keep_host {
+            let pattern = handle.pattern.clone();
+            self.match_conversion = None;
+            if config.convert_host_to_match(index, keep_host).is_some() {
+                if !keep_host {
+                    self.selected_host = None;
+                }
+                self.is_dirty = true;
+                self.generation += 1;
+                self.status.set(format!("Converted Host {} to a Match block", pattern));
+            }
+        } else if cancel {
+            self.match_conversion = None;
+        }
+    }
+
+    // Runs `action` right away if nothing is unsaved, otherwise asks first
+    fn guard_unsaved(&mut self, ctx: &egui::Context, action: PendingAction) {
+        let dirty_tab = self.tabs.iter().position(|document| document.is_dirty);
+        let step = match action {
+            PendingAction::Quit => unsaved::guard_quit(self.is_dirty, dirty_tab.is_some(), self.loading.is_some()),
+            action => unsaved::guard(action, self.is_dirty),
+        };
+        // Quitting asks about each tab with unsaved changes in turn
+        if step == Step::Ask(PendingAction::Quit)
+            && !self.is_dirty
+            && let Some(position) = dirty_tab
+        {
+            let index = if position < self.active_tab { position } else { position + 1 };
+            self.switch_tab(index);
+        }
+        self.take_step(ctx, step);
+    }
+
+    fn take_step(&mut self, ctx: &egui::Context, step: Step) {
+        match step {
+            Step::Ask(action) => self.unsaved_prompt = Some(action),
+            Step::Perform(action) => self.perform(ctx, action),
+            Step::CloseTabAndQuit => {
+                self.close_tab();
+                self.guard_unsaved(ctx, PendingAction::Quit);
+            }
+            Step::WaitForLoad => self.status.set("Another tab has unsaved changes, quit again once loading finished"),
+            Step::Idle => {}
+        }
+    }
+
+    fn perform(&mut self, ctx: &egui::Context, action: PendingAction) {
+        match action {
+            PendingAction::Quit => {
+                self.quit_confirmed = true;
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+            }
+            PendingAction::Open(path) => self.start_load(ctx, path, LoadKind::Open),
+            // Without unsaved edits, only the files changed on disk need
+            // reading again
+            PendingAction::Reload if !self.is_dirty && self.config.is_some() => self.reload_changed(),
+            PendingAction::Reload => {
+                if let Some(path) = self.config_path.clone() {
+                    self.start_load(ctx, path, LoadKind::Reload);
+                }
+            }
+            PendingAction::CloseTab => self.close_tab(),
+        }
+    }
+
+    fn reload_changed(&mut self) {
+        let Some(config) = &mut self.config else {
+            return;
+        };
+        match config.reload_changed() {
+            Ok(changed) if changed.is_empty() => self.status.set("Reload: no files changed on disk"),
+            Ok(changed) => {
+                self.saved_lines = config.lines.clone();
+                self.generation += 1;
+                self.unwritable_files.clear();
+                self.sync_selection();
+                self.status.set(format!("Reloaded {} changed file(s)", changed.len()));
+            }
+            Err(e) => self.status.set(format!("Error reloading: {}", e)),
+        }
+    }
+
+    fn show_unsaved_dialog(&mut self, ctx: &egui::Context) {
+        let Some(action) = &self.unsaved_prompt else {
+            return;
+        };
+        let verb = action.verb();
+        let mut save = false;
+        let mut discard = false;
+        let mut cancel = false;
+
+        egui::Window::new("⚠ Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(300.0);
+
+                ui.label(format!("You have unsaved changes. Do you want to save before you {}?", verb.to_lowercase()));
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    save = ui.button(format!("Save and {}", verb)).clicked();
+                    discard = ui.button(format!("{} Without Saving", verb)).clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        let choice = if save {
+            self.save_config(ctx);
+            if self.is_dirty { Choice::SaveFailed } else { Choice::Saved }
+        } else if discard {
+            Choice::Discard
+        } else if cancel {
+            Choice::Cancel
+        } else {
+            return;
+        };
+        if let Some(action) = self.unsaved_prompt.take() {
+            let step = unsaved::after_choice(action, choice);
+            self.take_step(ctx, step);
+        }
+    }
+
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        let previous = self.settings.clone();
+
+        egui::Window::new("⚙ Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(350.0);
+
+                egui::Grid::new("settings_grid").num_columns(2).spacing([20.0, 8.0]).show(ui, |ui| {
+                    ui.label("Theme:");
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.settings.theme, Theme::System, "System");
+                        ui.radio_value(&mut self.settings.theme, Theme::Light, "Light");
+                        ui.radio_value(&mut self.settings.theme, Theme::Dark, "Dark");
+                    });
+                    ui.end_row();
+
+                    ui.label("Window:");
+                    ui.checkbox(&mut self.settings.always_on_top, "Always on top");
+                    ui.end_row();
+
+                    ui.label("Idle repaint:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.settings.idle_repaint_ms)
+                            .range(100..=5000)
+                            .suffix(" ms"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Indentation:");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.settings.reindent, "Indent options with");
+                        ui.add_enabled(
+                            self.settings.reindent,
+                            egui::DragValue::new(&mut self.settings.indent_width).range(1..=8),
+                        );
+                        ui.label("spaces");
+                    });
+                    ui.end_row();
+
+                    ui.label("New options:");
+                    ui.checkbox(&mut self.settings.new_options_at_top, "Add at the top of the host");
+                    ui.end_row();
+
+                    ui.label("Parsing:");
+                    ui.checkbox(&mut self.settings.semicolon_comments, "Treat ';' lines as comments")
+                        .on_hover_text("Not understood by ssh itself. Takes effect on the next load.");
+                    ui.end_row();
+
+                    ui.label("System config:");
+                    ui.checkbox(&mut self.settings.system_overlay, "Read as a read-only overlay").on_hover_text(
+                        "Include the machine-wide ssh_config when comparing with ssh -G and in the security audit. It is never saved.",
+                    );
+                    ui.end_row();
+
+                    ui.label("External editor:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.settings.external_editor)
+                            .hint_text("$VISUAL / $EDITOR")
+                            .desired_width(250.0),
+                    )
+                    .on_hover_text("Used by Open in Editor and middle- or Ctrl+clicking a host. Given +LINE to jump to the host.");
+                    ui.end_row();
+
+                    ui.label("After saving:");
+                    ui.vertical(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.settings.post_save_command)
+                                .hint_text("e.g. git -C ~/.ssh commit -qam update")
+                                .desired_width(250.0),
+                        );
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "⚠ Runs in your shell after every save. {} is replaced with the config path.",
+                                post_save::FILE_PLACEHOLDER
+                            ))
+                            .small()
+                            .color(egui::Color32::YELLOW),
+                        );
+                    });
+                    ui.end_row();
+
+                    ui.label("Search:");
+                    ui.vertical(|ui| {
+                        ui.checkbox(&mut self.settings.search_options, "Search in options");
+                        ui.checkbox(&mut self.settings.search_fuzzy, "Fuzzy");
+                    });
+                    ui.end_row();
+                });
+
+                ui.add_space(10.0);
+                ui.separator();
+                if ui.button("Reset to Defaults").clicked() {
+                    self.settings = AppSettings::default();
+                }
+            });
+
+        if self.settings.theme != previous.theme {
+            ctx.set_theme(self.settings.theme.preference());
+        }
+        if self.settings.system_overlay != previous.system_overlay {
+            self.load_system_config();
+        }
+        if self.settings.always_on_top != previous.always_on_top {
+            let level = if self.settings.always_on_top {
+                WindowLevel::AlwaysOnTop
+            } else {
+                WindowLevel::Normal
+            };
+            ctx.send_viewport_cmd(ViewportCommand::WindowLevel(level));
+        }
+
+        self.show_settings = open;
+    }
+
+    fn show_undo_toast(&mut self, ctx: &egui::Context) {
+        let Some(removed) = &self.removed_option else {
+            return;
+        };
+        let elapsed = removed.removed_at.elapsed();
+        if elapsed >= UNDO_TOAST_DURATION {
+            self.removed_option = None;
+            return;
+        }
+        ctx.request_repaint_after(UNDO_TOAST_DURATION - elapsed);
+
+        let mut undo = false;
+        egui::Area::new(egui::Id::new("undo_toast"))
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Removed {} —", removed.key));
+                        if ui.link("Undo").clicked() {
+                            undo = true;
+                        }
+                    });
+                });
+            });
+
+        if undo {
+            self.undo_remove_option();
+        }
+    }
+
+    // Puts the last removed option back where it was, as long as its host
+    // still exists
+    fn undo_remove_option(&mut self) {
+        let Some(removed) = self.removed_option.take() else {
+            return;
+        };
+        let Some(config) = &mut self.config else {
+            return;
+        };
+
+        let host_idx = config.resolve_host(&removed.host);
+        match host_idx.and_then(|idx| config.lines.get_mut(idx)) {
+            Some(ConfigLine::HostEntry { pattern, options, option_comments, .. }) => {
+                let idx = removed.option_idx.min(options.len());
+                options.insert(idx, (removed.key.clone(), removed.value));
+                ssh_config::insert_option_comment(option_comments, idx, removed.option_comment);
+                self.is_dirty = true;
+                self.generation += 1;
+                self.status.set(format!("Restored {} on {}", removed.key, pattern));
+            }
+            _ => {
+                self.status.set(format!("Can't undo: host '{}' has changed", removed.host.pattern));
+            }
+        }
+    }
+
+    // Main config file followed by all included files
+    fn managed_files(&self) -> Vec<PathBuf> {
+        let mut all_files = vec![];
+        if let Some(main_path) = &self.config_path {
+            all_files.push(main_path.clone());
+        }
+        if let Some(config) = &self.config {
+            for include_path in config.included_files.keys() {
+                all_files.push(include_path.clone());
+            }
+        }
+        all_files
+    }
+
+    fn start_merge(&mut self) {
+        if self.config.is_none() {
+            self.status.set("No file loaded");
+            return;
+        }
+
+        if let Some(path) = rfd::FileDialog::new().pick_file() {
+            match SshConfig::parse_file_with_home(&path, self.settings.parse_options(), self.home.clone()) {
+                Ok(other) => {
+                    self.merge_source = Some((path, other));
+                    self.merge_target_file = self.config_path.clone();
+                }
+                Err(e) => {
+                    self.status.set(format!("Error loading file: {}", e));
+                }
+            }
+        }
+    }
+
+    fn show_merge_dialog(&mut self, ctx: &egui::Context) {
+        let all_files = self.managed_files();
+        let mut close = false;
+
+        egui::Window::new("🔀 Merge Hosts")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(400.0);
+
+                if let Some((source_path, other)) = &self.merge_source {
+                    let host_count = other.hosts().count();
+                    ui.label(format!(
+                        "Merge {} host(s) from {}",
+                        host_count,
+                        source_path.display()
+                    ));
+                }
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("Target File:");
+                    egui::ComboBox::from_id_salt("merge_target_combo")
+                        .selected_text(
+                            self.merge_target_file
+                                .as_ref()
+                                .map(|p| p.display().to_string())
+                                .unwrap_or_else(|| "Select file...".to_string()),
+                        )
+                        .show_ui(ui, |ui| {
+                            for file in &all_files {
+                                let is_selected = self.merge_target_file.as_ref() == Some(file);
+                                if ui.selectable_label(is_selected, file.display().to_string()).clicked() {
+                                    self.merge_target_file = Some(file.clone());
+                                }
+                            }
+                        });
+                });
+
+                ui.add_space(5.0);
+                ui.label("When a host with the same pattern exists:");
+                ui.radio_value(&mut self.merge_policy, MergePolicy::Skip, "Skip it");
+                ui.radio_value(&mut self.merge_policy, MergePolicy::Overwrite, "Overwrite existing options");
+                ui.radio_value(&mut self.merge_policy, MergePolicy::Rename, "Add it under a new name");
+
+                ui.add_space(15.0);
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let can_merge = self.merge_target_file.is_some();
+                    if ui.add_enabled(can_merge, egui::Button::new("Merge")).clicked()
+                        && let (Some(config), Some((_, other)), Some(target_file)) =
+                            (&mut self.config, &self.merge_source, &self.merge_target_file)
+                    {
+                        let report = config.merge_from(other, target_file, self.merge_policy);
+                        // Indices may have shifted
+                        self.selected_host = self.selected_handle.as_ref().and_then(|h| config.resolve_host(h));
+                        if report.added > 0 || report.overwritten > 0 {
+                            self.is_dirty = true;
+                            self.generation += 1;
+                        }
+                        self.status.set(format!(
+                            "Merged hosts: {} added ({} renamed), {} skipped, {} overwritten",
+                            report.added, report.renamed, report.skipped, report.overwritten
+                        ));
+                        close = true;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.merge_source = None;
+            self.merge_target_file = None;
         }
     }
 
-    fn save_config(&mut self) {
-        if let (Some(config), Some(path)) = (&self.config, &self.config_path) {
-            match config.save_all(path) {
-                Ok(_) => {
-                    let file_count = config.included_files.len() + 1;
-                    self.status_message = format!("Saved {} file(s)", file_count);
-                    self.is_dirty = false;
-                }
-                Err(e) => {
-                    self.status_message = format!("Error saving: {}", e);
+    fn start_inventory_import(&mut self) {
+        if self.config.is_none() {
+            self.status.set("No file loaded");
+            return;
+        }
+
+        if let Some(path) = rfd::FileDialog::new().pick_file() {
+            match std::fs::read_to_string(&path) {
+                Ok(text) => {
+                    let (hosts, errors) = inventory::parse_inventory(&text);
+                    self.inventory_import = Some(InventoryImport {
+                        source: path,
+                        hosts,
+                        errors,
+                        target_file: self.config_path.clone(),
+                    });
                 }
+                Err(e) => self.status.set(format!("Error loading file: {}", e)),
             }
-        } else {
-            self.status_message = "No file loaded".to_string();
         }
     }
 
-    fn load_default_config(&mut self) {
-        if let Some(home) = dirs::home_dir() {
-            let default_path = home.join(".ssh").join("config");
-            if default_path.exists() {
-                match SshConfig::parse_file(&default_path) {
-                    Ok(config) => {
-                        let included_count = config.included_files.len();
-                        self.config = Some(config);
-                        self.config_path = Some(default_path.clone());
-                        self.status_message = if included_count > 0 {
-                            format!(
-                                "Loaded: {} ({} included files)",
-                                default_path.display(),
-                                included_count
-                            )
-                        } else {
-                            format!("Loaded: {}", default_path.display())
-                        };
-                    }
-                    Err(e) => {
-                        self.status_message = format!("Error loading default config: {}", e);
-                    }
+    // Upserts the hosts of a JSON document, see `SshConfig::apply_json`. New
+    // hosts without a file go into the main file.
+    fn import_json(&mut self) {
+        let (Some(config), Some(main_path)) = (&mut self.config, &self.config_path) else {
+            self.status.set("No file loaded");
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return;
+        };
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+            .and_then(|value| config.apply_json(&value, main_path, UpsertPolicy::Update));
+        match result {
+            Ok(report) => {
+                if report.created + report.updated > 0 {
+                    self.is_dirty = true;
+                    self.generation += 1;
                 }
-            } else {
-                self.status_message = format!("Default config not found: {}", default_path.display());
+                self.sync_selection();
+                self.status.set(format!(
+                    "Imported {}: {} created, {} updated, {} unchanged",
+                    path.display(),
+                    report.created,
+                    report.updated,
+                    report.unchanged
+                ));
             }
+            Err(e) => self.status.set(format!("Error importing {}: {}", path.display(), e)),
         }
     }
 
-    fn show_shortcuts_popup(&mut self, ctx: &egui::Context) {
-        egui::Window::new("⌨ Keyboard Shortcuts")
+    // Writes the whole config, Includes inlined, to a single file of the
+    // user's choosing. Nothing loaded is changed.
+    fn export_flattened(&mut self, include_comments: bool) {
+        let (Some(config), Some(main_path)) = (&self.config, &self.config_path) else {
+            self.status.set("No file loaded");
+            return;
+        };
+        let text = config.to_flattened_string(main_path, include_comments);
+        if let Some(path) = rfd::FileDialog::new().set_file_name("ssh_config").save_file() {
+            match std::fs::write(&path, text) {
+                Ok(()) => self.status.set(format!("Exported config to {}", path.display())),
+                Err(e) => self.status.set(format!("Error exporting: {}", e)),
+            }
+        }
+    }
+
+    fn show_inventory_import_dialog(&mut self, ctx: &egui::Context) {
+        let all_files = self.managed_files();
+        let (Some(config), Some(import)) = (&self.config, &mut self.inventory_import) else {
+            self.inventory_import = None;
+            return;
+        };
+
+        // Hosts already defined, or listed twice, are skipped
+        let skipped: Vec<bool> = import
+            .hosts
+            .iter()
+            .enumerate()
+            .map(|(idx, host)| {
+                import.hosts[..idx].iter().any(|earlier| earlier.alias == host.alias)
+                    || config.find_host_index(&host.alias).is_some()
+            })
+            .collect();
+        let new_count = skipped.iter().filter(|skip| !**skip).count();
+        let mut create = false;
+        let mut close = false;
+
+        egui::Window::new("📋 Import Hosts from List")
             .collapsible(false)
             .resizable(false)
             .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
             .show(ctx, |ui| {
-                ui.set_min_width(400.0);
+                ui.set_min_width(450.0);
+
+                ui.label(format!("{} host(s) read from {}", import.hosts.len(), import.source.display()));
+                ui.add_space(10.0);
 
-                ui.heading("File Operations");
-                ui.separator();
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+O").monospace().strong());
-                    ui.label("Open SSH config file");
+                    ui.label("Target File:");
+                    egui::ComboBox::from_id_salt("inventory_target_combo")
+                        .selected_text(
+                            import
+                                .target_file
+                                .as_ref()
+                                .map(|p| p.display().to_string())
+                                .unwrap_or_else(|| "Select file...".to_string()),
+                        )
+                        .show_ui(ui, |ui| {
+                            for file in &all_files {
+                                let is_selected = import.target_file.as_ref() == Some(file);
+                                if ui.selectable_label(is_selected, file.display().to_string()).clicked() {
+                                    import.target_file = Some(file.clone());
+                                }
+                            }
+                        });
                 });
-                ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+N").monospace().strong());
-                    ui.label("New host entry");
+                ui.add_space(5.0);
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("inventory_preview").num_columns(3).striped(true).show(ui, |ui| {
+                        for (host, skip) in import.hosts.iter().zip(&skipped) {
+                            let options: Vec<String> = host.options().iter().map(|(k, v)| format!("{} {}", k, v)).collect();
+                            if *skip {
+                                ui.label(egui::RichText::new(format!("Host {}", host.alias)).weak());
+                                ui.label(egui::RichText::new(options.join(", ")).weak());
+                                ui.label(egui::RichText::new("already defined, skipped").color(egui::Color32::YELLOW));
+                            } else {
+                                ui.label(format!("Host {}", host.alias));
+                                ui.label(options.join(", "));
+                                ui.label("");
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    for error in &import.errors {
+                        ui.label(egui::RichText::new(format!("✖ {}", error)).color(egui::Color32::RED));
+                    }
                 });
+
+                ui.add_space(15.0);
+                ui.separator();
+
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+S").monospace().strong());
-                    ui.label("Save all changes");
+                    let can_create = new_count > 0 && import.target_file.is_some();
+                    create = ui
+                        .add_enabled(can_create, egui::Button::new(format!("Create {} Host(s)", new_count)))
+                        .clicked();
+                    close = ui.button("Cancel").clicked();
                 });
-                ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+Q").monospace().strong());
-                    ui.label("Quit (prompts to save if dirty)");
+            });
+
+        if create
+            && let (Some(config), Some(import)) = (&mut self.config, self.inventory_import.take())
+            && let Some(target_file) = import.target_file
+        {
+            for (host, _) in import.hosts.iter().zip(&skipped).filter(|(_, skip)| !**skip) {
+                config.lines.push(ConfigLine::HostEntry {
+                    pattern: host.alias.clone(),
+                    comment: None,
+                    tags: Vec::new(),
+                    options: host.options(),
+                    option_comments: Vec::new(),
+                    source_file: target_file.clone(),
                 });
+            }
+            config.normalize_line_order();
+            self.is_dirty = true;
+            self.generation += 1;
+            self.sync_selection();
+            self.status.set(format!(
+                "Imported {} host(s) into {}, skipped {} already defined",
+                new_count,
+                target_file.display(),
+                import.hosts.len() - new_count
+            ));
+        } else if close {
+            self.inventory_import = None;
+        }
+    }
+
+    fn show_host_move_dialog(&mut self, ctx: &egui::Context) {
+        let all_files = self.managed_files();
+        let (Some(config), Some(host_move)) = (&self.config, &mut self.host_move) else {
+            self.host_move = None;
+            return;
+        };
+
+        let glob = host_move.glob.trim();
+        let matching = if glob.is_empty() { Vec::new() } else { config.hosts_matching(glob) };
+        // Hosts already in the target file stay where they are
+        let to_move: Vec<usize> = matching
+            .iter()
+            .copied()
+            .filter(|&idx| Some(config.lines[idx].source_file()) != host_move.target_file.as_deref())
+            .collect();
+        let mut apply = false;
+        let mut close = false;
+
+        egui::Window::new("📦 Move Matching Hosts to File")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(450.0);
 
-                ui.add_space(10.0);
-                ui.heading("Search & Navigation");
-                ui.separator();
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+F").monospace().strong());
-                    ui.label("Focus search box");
+                    ui.label("Hosts matching:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut host_move.glob)
+                            .hint_text("web-* db")
+                            .desired_width(f32::INFINITY),
+                    );
                 });
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Escape").monospace().strong());
-                    ui.label("Clear search / unfocus");
+                    ui.label("Target File:");
+                    egui::ComboBox::from_id_salt("host_move_target_combo")
+                        .selected_text(
+                            host_move
+                                .target_file
+                                .as_ref()
+                                .map(|p| p.display().to_string())
+                                .unwrap_or_else(|| "Select file...".to_string()),
+                        )
+                        .show_ui(ui, |ui| {
+                            for file in &all_files {
+                                let is_selected = host_move.target_file.as_ref() == Some(file);
+                                if ui.selectable_label(is_selected, file.display().to_string()).clicked() {
+                                    host_move.target_file = Some(file.clone());
+                                }
+                            }
+                        });
                 });
+                ui.add_space(5.0);
 
-                ui.add_space(10.0);
-                ui.heading("View");
+                if matching.is_empty() {
+                    ui.label(egui::RichText::new("No host matches").weak());
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("host_move_preview").num_columns(2).striped(true).show(ui, |ui| {
+                        for &idx in &matching {
+                            let ConfigLine::HostEntry { pattern, source_file, .. } = &config.lines[idx] else {
+                                continue;
+                            };
+                            if to_move.contains(&idx) {
+                                ui.label(format!("Host {}", pattern));
+                                ui.label(source_file.display().to_string());
+                            } else {
+                                ui.label(egui::RichText::new(format!("Host {}", pattern)).weak());
+                                ui.label(egui::RichText::new("already in the target file").weak());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.add_space(15.0);
                 ui.separator();
+
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+A").monospace().strong());
-                    ui.label("Toggle always on top");
+                    let can_move = !to_move.is_empty() && host_move.target_file.is_some();
+                    apply = ui
+                        .add_enabled(can_move, egui::Button::new(format!("Move {} Host(s)", to_move.len())))
+                        .clicked();
+                    close = ui.button("Cancel").clicked();
                 });
+            });
+
+        if apply
+            && let (Some(config), Some(host_move)) = (&mut self.config, self.host_move.take())
+            && let Some(target_file) = host_move.target_file
+        {
+            let new_indices = config.move_hosts_to_file(&to_move, &target_file);
+            if let Some(selected) = self.selected_host
+                && let Some(pos) = to_move.iter().position(|&idx| idx == selected)
+            {
+                // Follow the selected host to its new file
+                self.selected_host = Some(new_indices[pos]);
+                self.selected_handle = config.host_handle(new_indices[pos]);
+            }
+            self.is_dirty = true;
+            self.generation += 1;
+            self.sync_selection();
+            self.status.set(format!("Moved {} host(s) to {}", to_move.len(), target_file.display()));
+        } else if close {
+            self.host_move = None;
+        }
+    }
+
+    // Flips the window level; the setting is stored with the others
+    fn toggle_always_on_top(&mut self, ctx: &egui::Context) {
+        self.settings.always_on_top = !self.settings.always_on_top;
+        let level = if self.settings.always_on_top {
+            WindowLevel::AlwaysOnTop
+        } else {
+            WindowLevel::Normal
+        };
+        ctx.send_viewport_cmd(ViewportCommand::WindowLevel(level));
+        self.status.set(if self.settings.always_on_top {
+            "Always on top: enabled".to_string()
+        } else {
+            "Always on top: disabled".to_string()
+        });
+    }
+
+    // Where to add the legacy options when Ctrl+Shift+L found no selected host
+    fn show_legacy_prompt_dialog(&mut self, ctx: &egui::Context) {
+        let Some(config) = &self.config else {
+            self.show_legacy_prompt = false;
+            return;
+        };
+        let host_count = config.hosts().count();
+        let search_match = self
+            .first_listed_host
+            .filter(|_| !self.search_query.trim().is_empty())
+            .and_then(|idx| config.hosts().find(|host| host.index == idx));
+        let mut targets: Option<Vec<usize>> = None;
+        let mut close = false;
 
+        egui::Window::new("Add Legacy SSH Options")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("No host is selected. Add the legacy SSH options to:");
                 ui.add_space(10.0);
-                ui.heading("Quick Actions");
-                ui.separator();
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("Ctrl+Shift+L").monospace().strong());
-                    ui.label("Add legacy SSH options");
+                    if let Some(host) = search_match
+                        && ui.button(format!("Host {}", host.pattern)).on_hover_text("Best match of the search").clicked()
+                    {
+                        targets = Some(vec![host.index]);
+                    }
+                    if ui
+                        .add_enabled(host_count > 0, egui::Button::new(format!("All {} Host(s)", host_count)))
+                        .clicked()
+                    {
+                        targets = Some(config.hosts().map(|host| host.index).collect());
+                    }
+                    close = ui.button("Cancel").clicked();
+                });
+            });
+
+        if let Some(targets) = targets
+            && let Some(config) = &mut self.config
+        {
+            let mut changed = 0;
+            for idx in &targets {
+                if let Some(ConfigLine::HostEntry { options, .. }) = config.lines.get_mut(*idx)
+                    && add_legacy_options(options)
+                {
+                    changed += 1;
+                }
+            }
+            if changed > 0 {
+                self.is_dirty = true;
+                self.generation += 1;
+            }
+            if let [idx] = targets[..] {
+                self.selected_host = Some(idx);
+            }
+            self.status.set(format!("Added legacy SSH options to {} host(s)", changed));
+            self.show_legacy_prompt = false;
+        } else if close {
+            self.show_legacy_prompt = false;
+        }
+    }
+
+    // Cancels the dialog in front, modal prompts first, then closes the first
+    // open tool window. Returns false if nothing was open.
+    fn close_topmost_dialog(&mut self) -> bool {
+        if self.unsaved_prompt.is_some() {
+            self.unsaved_prompt = None;
+        } else if self.duplicate_warning.is_some() {
+            self.duplicate_warning = None;
+        } else if self.system_save_warning.is_some() {
+            self.system_save_warning = None;
+            self.duplicates_confirmed = false;
+        } else if self.lossy_save_warning.is_some() {
+            self.lossy_save_warning = None;
+            self.duplicates_confirmed = false;
+            self.system_save_confirmed = false;
+        } else if self.pending_create_dir.is_some() {
+            self.pending_create_dir = None;
+        } else if self.pasted_host.is_some() {
+            self.pasted_host = None;
+        } else if self.merge_source.is_some() {
+            self.merge_source = None;
+            self.merge_target_file = None;
+        } else if self.inventory_import.is_some() {
+            self.inventory_import = None;
+        } else if self.host_move.is_some() {
+            self.host_move = None;
+        } else if self.match_conversion.is_some() {
+            self.match_conversion = None;
+        } else if self.show_legacy_prompt {
+            self.show_legacy_prompt = false;
+        } else if self.show_new_host_dialog {
+            self.new_host_pattern.clear();
+            self.new_host_target_file = None;
+            self.show_new_host_dialog = false;
+        } else if self.show_include_dialog {
+            self.new_include_path.clear();
+            self.new_include_target_file = None;
+            self.show_include_dialog = false;
+        } else if self.show_settings {
+            self.show_settings = false;
+        } else if self.show_shortcuts {
+            self.show_shortcuts = false;
+        } else if self.show_warnings {
+            self.show_warnings = false;
+        } else if self.show_audit {
+            self.show_audit = false;
+        } else if self.show_system_config {
+            self.show_system_config = false;
+        } else if self.show_matrix_export {
+            self.show_matrix_export = false;
+        } else if self.duplicate_merge_file.is_some() {
+            self.duplicate_merge_file = None;
+        } else if self.show_ssh_compare {
+            self.show_ssh_compare = false;
+        } else if self.file_preview.is_some() {
+            self.file_preview = None;
+        } else if self.show_options_table {
+            self.show_options_table = false;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // Creates the host typed into the New Host dialog. With `keep_open` the
+    // dialog stays up, keeping the target file, for entering the next one.
+    fn create_new_host(&mut self, ctx: &egui::Context, keep_open: bool) {
+        if let (Some(config), Some(target_file)) =
+            (&mut self.config, &self.new_host_target_file)
+        {
+            let new_pattern = self.new_host_pattern.trim().to_string();
+            let new_entry = ConfigLine::HostEntry {
+                pattern: new_pattern.clone(),
+                comment: None,
+                tags: Vec::new(),
+                options: Vec::new(),
+                option_comments: Vec::new(),
+                source_file: target_file.clone(),
+            };
+
+            // Add to the end of its file
+            config.lines.push(new_entry);
+            config.normalize_line_order();
+
+            self.is_dirty = true;
+            self.generation += 1;
+            self.status.set(format!(
+                "Created new host '{}' in {}",
+                new_pattern,
+                target_file.display()
+            ));
+
+            // Select the newly created host
+            self.selected_host = config
+                .lines
+                .iter()
+                .rposition(|line| line.source_file() == target_file.as_path());
+
+            self.new_host_pattern.clear();
+            if keep_open {
+                ctx.memory_mut(|m| m.request_focus(egui::Id::new("new_host_pattern_field")));
+            } else {
+                self.new_host_target_file = None;
+                self.show_new_host_dialog = false;
+            }
+        }
+    }
+
+    fn show_pasted_host_dialog(&mut self, ctx: &egui::Context) {
+        let Some(ConfigLine::HostEntry { pattern, options, source_file, .. }) = &mut self.pasted_host else {
+            return;
+        };
+        let mut create = false;
+        let mut dismiss = false;
+
+        egui::Window::new("🔗 Create Host from URL")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_min_width(350.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("Host Pattern:");
+                    if ui.text_edit_singleline(pattern).changed() {
+                        sanitize_field(pattern, &mut self.status);
+                    }
                 });
+                for (key, value) in options.iter() {
+                    ui.label(egui::RichText::new(format!("    {} {}", key, value)).monospace());
+                }
                 ui.label(
-                    egui::RichText::new("  (to selected host)")
-                        .color(egui::Color32::GRAY)
-                        .italics(),
+                    egui::RichText::new(format!("Added to {}", source_file.display()))
+                        .small()
+                        .color(egui::Color32::GRAY),
                 );
 
+                let already_defined = self
+                    .config
+                    .as_ref()
+                    .is_some_and(|config| config.find_host_index(pattern).is_some());
+                if already_defined {
+                    ui.label(
+                        egui::RichText::new("⚠ A host with this pattern already exists")
+                            .color(egui::Color32::YELLOW),
+                    );
+                }
+
                 ui.add_space(10.0);
-                ui.heading("Legacy SSH Options");
-                ui.separator();
-                ui.label(egui::RichText::new("Adds these options:").color(egui::Color32::GRAY));
-                ui.label(egui::RichText::new("  • HostKeyAlgorithms +ssh-rsa,ssh-rsa-cert-v01@openssh.com").monospace().small());
-                ui.label(egui::RichText::new("  • PubkeyAcceptedAlgorithms +ssh-rsa,ssh-rsa-cert-v01@openssh.com").monospace().small());
-                ui.label(egui::RichText::new("  • Ciphers +aes256-cbc,aes128-cbc").monospace().small());
-                ui.label(egui::RichText::new("  • MACs +aes256-cbc,hmac-sha1").monospace().small());
-                ui.label(egui::RichText::new("  • KexAlgorithms +diffie-hellman-group1-sha1").monospace().small());
-                ui.add_space(15.0);
                 ui.separator();
-                if ui.button("Close").clicked() {
-                    self.show_shortcuts = false;
-                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(pattern::check_pattern_list(pattern).is_ok(), egui::Button::new("Create"))
+                        .clicked()
+                    {
+                        create = true;
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        dismiss = true;
+                    }
+                });
+            });
+
+        if create
+            && let Some(config) = &mut self.config
+            && let Some(ConfigLine::HostEntry { pattern, tags, options, source_file, .. }) = self.pasted_host.take()
+        {
+            let pattern = pattern.trim().to_string();
+            config.lines.push(ConfigLine::HostEntry {
+                pattern: pattern.clone(),
+                comment: None,
+                tags,
+                options,
+                option_comments: Vec::new(),
+                source_file: source_file.clone(),
             });
+            config.normalize_line_order();
+            self.selected_host = config.lines.iter().rposition(|line| line.source_file() == source_file);
+            self.is_dirty = true;
+            self.generation += 1;
+            self.status.set(format!("Created host '{}' from URL", pattern));
+        }
+        if dismiss {
+            self.pasted_host = None;
+        }
     }
 
-    fn show_quit_dialog(&mut self, ctx: &egui::Context) {
-        egui::Window::new("⚠ Unsaved Changes")
+    fn add_include(&mut self) {
+        let (Some(config), Some(target_file)) = (&mut self.config, &self.new_include_target_file) else {
+            return;
+        };
+        let path = self.new_include_path.trim().to_string();
+
+        match config.add_include(&path, target_file) {
+            Ok((index, added)) => {
+                let new_files = match config.lines.get(index) {
+                    Some(ConfigLine::Include { resolved, .. }) => resolved.iter().filter(|file| !file.exists()).count(),
+                    _ => 0,
+                };
+                self.sync_selection();
+                self.is_dirty = true;
+                self.generation += 1;
+                self.status.set(if new_files > 0 {
+                    format!("Added Include {}; the file will be created when saving", path)
+                } else if added > 0 {
+                    format!("Added Include {} ({} file(s) included)", path, added)
+                } else {
+                    format!("Added Include {}, but it doesn't match any new files", path)
+                });
+                self.new_include_path.clear();
+                self.new_include_target_file = None;
+                self.show_include_dialog = false;
+            }
+            Err(e) => {
+                self.status.set(format!("Error adding include: {}", e));
+            }
+        }
+    }
+
+    fn show_include_dialog(&mut self, ctx: &egui::Context) {
+        egui::Window::new("📁 Add Include")
             .collapsible(false)
             .resizable(false)
             .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
             .show(ctx, |ui| {
-                ui.set_min_width(300.0);
+                ui.set_min_width(400.0);
 
-                ui.label("You have unsaved changes. Do you want to save before quitting?");
-                ui.add_space(10.0);
+                ui.label("Path or glob pattern, relative paths resolve against ~/.ssh:");
+                ui.add_space(5.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    if ui
+                        .add(egui::TextEdit::singleline(&mut self.new_include_path).hint_text("config.d/*"))
+                        .changed()
+                    {
+                        sanitize_field(&mut self.new_include_path, &mut self.status);
+                    }
+                    if ui.button("File…").clicked()
+                        && let Some(path) = rfd::FileDialog::new().pick_file()
+                        && let Some(config) = &self.config
+                    {
+                        self.new_include_path = config.include_path_for(&path);
+                    }
+                    if ui.button("Folder…").clicked()
+                        && let Some(path) = rfd::FileDialog::new().pick_folder()
+                        && let Some(config) = &self.config
+                    {
+                        self.new_include_path = config.include_path_for(&path.join("*"));
+                    }
+                });
+
+                ui.add_space(5.0);
 
                 ui.horizontal(|ui| {
-                    if ui.button("Save and Quit").clicked() {
-                        self.save_config();
-                        ctx.send_viewport_cmd(ViewportCommand::Close);
-                        self.show_quit_dialog = false;
+                    ui.label("Add to:");
+
+                    let all_files = self.managed_files();
+                    if self.new_include_target_file.is_none() {
+                        self.new_include_target_file = all_files.first().cloned();
                     }
 
-                    if ui.button("Quit Without Saving").clicked() {
-                        ctx.send_viewport_cmd(ViewportCommand::Close);
-                        self.show_quit_dialog = false;
+                    egui::ComboBox::from_id_salt("include_target_file_combo")
+                        .selected_text(
+                            self.new_include_target_file
+                                .as_ref()
+                                .map(|p| p.display().to_string())
+                                .unwrap_or_else(|| "Select file...".to_string()),
+                        )
+                        .show_ui(ui, |ui| {
+                            for file in &all_files {
+                                let is_selected = self.new_include_target_file.as_ref() == Some(file);
+                                if ui.selectable_label(is_selected, file.display().to_string()).clicked() {
+                                    self.new_include_target_file = Some(file.clone());
+                                }
+                            }
+                        });
+                });
+
+                ui.add_space(15.0);
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let can_add = !self.new_include_path.trim().is_empty()
+                        && self.new_include_target_file.is_some();
+
+                    if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                        self.add_include();
                     }
 
                     if ui.button("Cancel").clicked() {
-                        self.show_quit_dialog = false;
+                        self.new_include_path.clear();
+                        self.new_include_target_file = None;
+                        self.show_include_dialog = false;
                     }
                 });
             });
@@ -248,42 +2705,50 @@ impl SshConfigApp {
 
                 ui.horizontal(|ui| {
                     ui.label("Host Pattern:");
-                    let pattern_response = ui.text_edit_singleline(&mut self.new_host_pattern);
+                    let pattern_response = ui.add(
+                        egui::TextEdit::singleline(&mut self.new_host_pattern)
+                            .id(egui::Id::new("new_host_pattern_field")),
+                    );
+                    if pattern_response.changed() {
+                        sanitize_field(&mut self.new_host_pattern, &mut self.status);
+                    }
 
                     // Enter on host pattern creates the entry (if valid)
                     if pattern_response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                        let can_create = !self.new_host_pattern.is_empty()
+                        let can_create = pattern::check_pattern_list(&self.new_host_pattern).is_ok()
                             && self.new_host_target_file.is_some();
 
                         if can_create {
-                            if let (Some(config), Some(target_file)) =
-                                (&mut self.config, &self.new_host_target_file)
-                            {
-                                // Create new host entry
-                                let new_entry = ConfigLine::HostEntry {
-                                    pattern: self.new_host_pattern.clone(),
-                                    options: Vec::new(),
-                                    source_file: target_file.clone(),
-                                };
-
-                                config.lines.push(new_entry);
-
-                                self.is_dirty = true;
-                                self.status_message = format!(
-                                    "Created new host '{}' in {}",
-                                    self.new_host_pattern,
-                                    target_file.display()
-                                );
-
-                                self.selected_host = Some(config.lines.len() - 1);
+                            self.create_new_host(ctx, false);
+                        }
+                    }
+                });
 
-                                self.new_host_pattern.clear();
-                                self.new_host_target_file = None;
-                                self.show_new_host_dialog = false;
+                // Nothing to complain about until something was typed
+                let check = pattern::check_pattern_list(&self.new_host_pattern);
+                if !self.new_host_pattern.is_empty() {
+                    match &check {
+                        Err(e) => {
+                            ui.label(egui::RichText::new(format!("✖ {}", e)).color(egui::Color32::RED));
+                        }
+                        Ok(warnings) => {
+                            for warning in warnings {
+                                ui.label(egui::RichText::new(format!("⚠ {}", warning)).color(egui::Color32::YELLOW));
                             }
                         }
                     }
-                });
+                }
+
+                let already_defined = self
+                    .config
+                    .as_ref()
+                    .is_some_and(|config| config.find_host_index(&self.new_host_pattern).is_some());
+                if already_defined && check.is_ok() {
+                    ui.label(
+                        egui::RichText::new("⚠ A host with this pattern already exists")
+                            .color(egui::Color32::YELLOW),
+                    );
+                }
 
                 ui.add_space(5.0);
 
@@ -291,15 +2756,9 @@ impl SshConfigApp {
                 ui.horizontal(|ui| {
                     ui.label("Target File:");
 
-                    if let Some(config) = &self.config {
+                    if self.config.is_some() {
                         // Build list of all files (main + included)
-                        let mut all_files = vec![];
-                        if let Some(main_path) = &self.config_path {
-                            all_files.push(main_path.clone());
-                        }
-                        for (include_path, _) in &config.included_files {
-                            all_files.push(include_path.clone());
-                        }
+                        let all_files = self.managed_files();
 
                         if !all_files.is_empty() {
                             // Set default if not set
@@ -330,38 +2789,14 @@ impl SshConfigApp {
                 ui.separator();
 
                 ui.horizontal(|ui| {
-                    let can_create = !self.new_host_pattern.is_empty()
-                        && self.new_host_target_file.is_some();
+                    let can_create = check.is_ok() && self.new_host_target_file.is_some();
 
                     if ui.add_enabled(can_create, egui::Button::new("Create")).clicked() {
-                        if let (Some(config), Some(target_file)) =
-                            (&mut self.config, &self.new_host_target_file)
-                        {
-                            // Create new host entry
-                            let new_entry = ConfigLine::HostEntry {
-                                pattern: self.new_host_pattern.clone(),
-                                options: Vec::new(),
-                                source_file: target_file.clone(),
-                            };
-
-                            // Add to the end
-                            config.lines.push(new_entry);
-
-                            self.is_dirty = true;
-                            self.status_message = format!(
-                                "Created new host '{}' in {}",
-                                self.new_host_pattern,
-                                target_file.display()
-                            );
-
-                            // Select the newly created host
-                            self.selected_host = Some(config.lines.len() - 1);
+                        self.create_new_host(ctx, false);
+                    }
 
-                            // Clear and close
-                            self.new_host_pattern.clear();
-                            self.new_host_target_file = None;
-                            self.show_new_host_dialog = false;
-                        }
+                    if ui.add_enabled(can_create, egui::Button::new("Create & Add Another")).clicked() {
+                        self.create_new_host(ctx, true);
                     }
 
                     if ui.button("Cancel").clicked() {
@@ -375,50 +2810,115 @@ impl SshConfigApp {
 }
 
 impl eframe::App for SshConfigApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.settings.store(storage);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Reduce frame rate when idle to save power (2 FPS = 500ms)
-        // UI still feels instant but uses much less GPU when idle
-        ctx.request_repaint_after(std::time::Duration::from_millis(500));
+        // Reduce frame rate when idle to save power. UI still feels instant
+        // but uses much less GPU when idle. When the window is unfocused we
+        // don't schedule anything and rely on input events to wake us up.
+        if ctx.input(|i| i.focused) {
+            ctx.request_repaint_after(Duration::from_millis(self.settings.idle_repaint_ms));
+        }
 
-        // Load default config on first frame
+        // Load the startup file on first frame
         if !self.initialized {
-            self.load_default_config();
+            match std::mem::take(&mut self.startup_file) {
+                StartupFile::Default => self.load_default_config(ctx),
+                StartupFile::Path(path) => self.start_load(ctx, path, LoadKind::Open),
+                StartupFile::None => {}
+            }
+            self.load_system_config();
             self.initialized = true;
         }
 
+        // Closing the window with the title bar button goes through the same
+        // unsaved changes check as Ctrl+Q
+        if ctx.input(|i| i.viewport().close_requested()) && self.any_dirty() && !self.quit_confirmed {
+            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+            self.guard_unsaved(ctx, PendingAction::Quit);
+        }
+
+        self.poll_load();
+        self.poll_post_save();
+        self.sync_selection();
+        if let Some(config) = &mut self.config {
+            config.indent_override = self.settings.reindent.then_some(self.settings.indent_width);
+        }
+
         // Handle Ctrl+F for search
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::F)) {
+        if ctx.input(|i| shortcuts::pressed(i, Command::FocusSearch)) {
             self.search_focused = true;
         }
 
-        // Handle Escape to clear search
-        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
-            self.search_query.clear();
-            self.search_focused = false;
+        // Make sure a pending focus request is applied on the next frame
+        // instead of waiting for the idle repaint
+        if self.search_focused {
+            ctx.request_repaint();
+        }
+
+        // Handle Escape: a focused text field just loses focus (egui does
+        // that itself), otherwise the topmost dialog closes, and only with no
+        // dialog open does it clear the search
+        if ctx.input(|i| shortcuts::pressed(i, Command::Escape))
+            && !ctx.wants_keyboard_input()
+            && !self.close_topmost_dialog()
+            && !self.search_query.is_empty()
+        {
+            self.search_query.clear();
+            self.search_focused = false;
+        }
+
+        // Handle Ctrl+W to close the topmost dialog, even while typing in
+        // it, or the tab when no dialog is open
+        if ctx.input(|i| shortcuts::pressed(i, Command::CloseDialog))
+            && !self.close_topmost_dialog()
+            && (self.tab_count() > 1 || self.config.is_some())
+        {
+            self.guard_unsaved(ctx, PendingAction::CloseTab);
+        }
+
+        // Handle Ctrl+Tab / Ctrl+Shift+Tab to cycle through tabs
+        let count = self.tab_count();
+        if ctx.input(|i| shortcuts::pressed(i, Command::NextTab)) {
+            self.switch_tab((self.active_tab + 1) % count);
+        }
+        if ctx.input(|i| shortcuts::pressed(i, Command::PreviousTab)) {
+            self.switch_tab((self.active_tab + count - 1) % count);
         }
 
-        // Handle Ctrl+Shift+L to add legacy SSH options
-        let add_legacy = ctx.input(|i| i.modifiers.ctrl && i.modifiers.shift && i.key_pressed(egui::Key::L));
+        // Handle Ctrl+Shift+L to add legacy SSH options. Without a selected
+        // host, ask where to add them instead of ignoring the key.
+        let add_legacy = ctx.input(|i| shortcuts::pressed_once(i, Command::AddLegacy));
+        if add_legacy && self.config.is_some() && self.selected_host.is_none() {
+            self.status.set("Select a host first to add legacy SSH options");
+            self.show_legacy_prompt = true;
+        }
 
         // Handle Ctrl+S to save
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::S)) {
-            self.save_config();
+        if ctx.input(|i| shortcuts::pressed(i, Command::Save)) {
+            self.save_config(ctx);
+        }
+
+        // Handle Ctrl+Shift+S to save every tab
+        if ctx.input(|i| shortcuts::pressed(i, Command::SaveAll)) {
+            self.save_all_documents(ctx);
         }
 
         // Handle Ctrl+O to open
-        let open_file = ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::O));
+        let open_file = ctx.input(|i| shortcuts::pressed(i, Command::Open));
+
+        // Handle Ctrl+T to open in a new tab
+        let open_in_tab = ctx.input(|i| shortcuts::pressed(i, Command::OpenInTab));
 
         // Handle Ctrl+Q to quit
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Q)) {
-            if self.is_dirty {
-                self.show_quit_dialog = true;
-            } else {
-                ctx.send_viewport_cmd(ViewportCommand::Close);
-            }
+        if ctx.input(|i| shortcuts::pressed(i, Command::Quit)) {
+            self.guard_unsaved(ctx, PendingAction::Quit);
         }
 
         // Handle Ctrl+N to create new host
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::N)) {
+        if ctx.input(|i| shortcuts::pressed(i, Command::NewHost)) {
             // Pre-fill target file based on currently selected host
             if let Some(config) = &self.config {
                 if let Some(selected_idx) = self.selected_host {
@@ -434,20 +2934,35 @@ impl eframe::App for SshConfigApp {
             self.show_new_host_dialog = true;
         }
 
+        // Handle Ctrl+Z to undo the last option removal. Text fields handle
+        // their own undo while focused.
+        if ctx.input(|i| shortcuts::pressed(i, Command::UndoRemove)) && !ctx.wants_keyboard_input() {
+            self.undo_remove_option();
+        }
+
+        // Pasting an ssh:// URL outside of text fields offers to create a host
+        if !ctx.wants_keyboard_input()
+            && let Some(config) = &self.config
+            && let Some(target_file) = self
+                .selected_host
+                .and_then(|idx| config.lines.get(idx))
+                .map(|line| line.source_file().to_path_buf())
+                .or_else(|| self.config_path.clone())
+        {
+            let pasted = ctx.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Paste(text) => ssh_config::host_from_ssh_url(text, &target_file),
+                    _ => None,
+                })
+            });
+            if pasted.is_some() {
+                self.pasted_host = pasted;
+            }
+        }
+
         // Handle Ctrl+A to toggle always on top
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::A)) {
-            self.always_on_top = !self.always_on_top;
-            let level = if self.always_on_top {
-                WindowLevel::AlwaysOnTop
-            } else {
-                WindowLevel::Normal
-            };
-            ctx.send_viewport_cmd(ViewportCommand::WindowLevel(level));
-            self.status_message = if self.always_on_top {
-                "Always on top: enabled".to_string()
-            } else {
-                "Always on top: disabled".to_string()
-            };
+        if ctx.input(|i| shortcuts::pressed(i, Command::AlwaysOnTop)) {
+            self.toggle_always_on_top(ctx);
         }
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -458,68 +2973,90 @@ impl eframe::App for SshConfigApp {
                             .add_filter("SSH Config", &["config", "*"])
                             .pick_file()
                         {
-                            match SshConfig::parse_file(&path) {
-                                Ok(config) => {
-                                    let included_count = config.included_files.len();
-                                    self.config = Some(config);
-                                    self.config_path = Some(path.clone());
-                                    self.is_dirty = false;
-                                    self.status_message = if included_count > 0 {
-                                        format!(
-                                            "Loaded: {} ({} included files)",
-                                            path.display(),
-                                            included_count
-                                        )
-                                    } else {
-                                        format!("Loaded: {}", path.display())
-                                    };
-                                }
-                                Err(e) => {
-                                    self.status_message = format!("Error loading file: {}", e);
-                                }
+                            self.guard_unsaved(ctx, PendingAction::Open(path));
+                        }
+                        ui.close();
+                    }
+
+                    if ui.button("Open in New Tab…  (Ctrl+T)").clicked() || open_in_tab {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("SSH Config", &["config", "*"])
+                            .pick_file()
+                        {
+                            // An empty tab is reused instead of opening another
+                            if self.config.is_some() && self.loading.is_none() {
+                                self.new_tab();
                             }
+                            self.start_load(ctx, path, LoadKind::Open);
                         }
                         ui.close();
                     }
 
+                    if ui.add_enabled(self.config.is_some(), egui::Button::new("Export Option Matrix…")).clicked() {
+                        self.show_matrix_export = true;
+                        ui.close();
+                    }
+
+                    if ui.button("Merge from…").clicked() {
+                        self.start_merge();
+                        ui.close();
+                    }
+
+                    if ui.button("Import Hosts from List…").clicked() {
+                        self.start_inventory_import();
+                        ui.close();
+                    }
+
+                    if ui
+                        .button("Import Hosts from JSON…")
+                        .on_hover_text("Create hosts, or set options of existing ones, from {\"hosts\": [...]}")
+                        .clicked()
+                    {
+                        self.import_json();
+                        ui.close();
+                    }
+
+                    ui.menu_button("Export Flattened Config", |ui| {
+                        if ui.button("With Comments…").clicked() {
+                            self.export_flattened(true);
+                            ui.close();
+                        }
+                        if ui
+                            .button("Directives Only…")
+                            .on_hover_text("Leave out comments and blank lines")
+                            .clicked()
+                        {
+                            self.export_flattened(false);
+                            ui.close();
+                        }
+                    });
+
                     if ui.button("Save  (Ctrl+S)").clicked() {
-                        self.save_config();
+                        self.save_config(ctx);
+                        ui.close();
+                    }
+
+                    if ui.add_enabled(self.tab_count() > 1, egui::Button::new("Save All Tabs  (Ctrl+Shift+S)")).clicked() {
+                        self.save_all_documents(ctx);
                         ui.close();
                     }
 
                     if ui.button("Reload").clicked() {
-                        if let Some(path) = &self.config_path.clone() {
-                            match SshConfig::parse_file(path) {
-                                Ok(config) => {
-                                    let included_count = config.included_files.len();
-                                    self.config = Some(config);
-                                    self.is_dirty = false;
-                                    self.status_message = if included_count > 0 {
-                                        format!(
-                                            "Reloaded: {} ({} included files)",
-                                            path.display(),
-                                            included_count
-                                        )
-                                    } else {
-                                        format!("Reloaded: {}", path.display())
-                                    };
-                                }
-                                Err(e) => {
-                                    self.status_message = format!("Error reloading: {}", e);
-                                }
-                            }
-                        }
+                        self.guard_unsaved(ctx, PendingAction::Reload);
+                        ui.close();
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Settings…").clicked() {
+                        self.show_settings = true;
                         ui.close();
                     }
 
                     ui.separator();
 
                     if ui.button("Quit  (Ctrl+Q)").clicked() {
-                        if self.is_dirty {
-                            self.show_quit_dialog = true;
-                        } else {
-                            ctx.send_viewport_cmd(ViewportCommand::Close);
-                        }
+                        self.guard_unsaved(ctx, PendingAction::Quit);
                         ui.close();
                     }
                 });
@@ -541,28 +3078,111 @@ impl eframe::App for SshConfigApp {
                         self.show_new_host_dialog = true;
                         ui.close();
                     }
+
+                    if ui.add_enabled(self.config.is_some(), egui::Button::new("Add Include…")).clicked() {
+                        self.show_include_dialog = true;
+                        ui.close();
+                    }
+
+                    if ui
+                        .add_enabled(self.config.is_some(), egui::Button::new("Normalize Yes/No Values"))
+                        .on_hover_text("Rewrite True, 1, NO, ... of yes/no options as lowercase yes or no")
+                        .clicked()
+                    {
+                        if let Some(config) = &mut self.config {
+                            let report = lint::normalize_booleans(config);
+                            if report.changed > 0 {
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            }
+                            let mut message = format!("Normalized {} yes/no value(s)", report.changed);
+                            if !report.unrecognized.is_empty() {
+                                message.push_str(&format!("; not a yes/no value: {}", report.unrecognized.join(", ")));
+                            }
+                            self.status.set(message);
+                        }
+                        ui.close();
+                    }
+
+                    if ui
+                        .add_enabled(self.config.is_some(), egui::Button::new("Move Matching Hosts to File…"))
+                        .clicked()
+                    {
+                        // Start from the selected host
+                        let glob = self
+                            .selected_host
+                            .and_then(|idx| self.config.as_ref()?.lines.get(idx))
+                            .map(|line| match line {
+                                ConfigLine::HostEntry { pattern, .. } => pattern.clone(),
+                                _ => String::new(),
+                            })
+                            .unwrap_or_default();
+                        self.host_move = Some(HostMove { glob, target_file: None });
+                        ui.close();
+                    }
+
+                    let duplicate_files = self.config.as_ref().map(SshConfig::files_with_duplicate_hosts).unwrap_or_default();
+                    if ui
+                        .add_enabled(!duplicate_files.is_empty(), egui::Button::new("Merge Duplicate Hosts…"))
+                        .on_disabled_hover_text("No file has two Host blocks with the same pattern")
+                        .clicked()
+                    {
+                        self.duplicate_merge_file = duplicate_files.into_iter().next();
+                        ui.close();
+                    }
                 });
 
                 ui.menu_button("View", |ui| {
-                    let always_on_top_label = if self.always_on_top {
+                    let always_on_top_label = if self.settings.always_on_top {
                         "✓ Always on Top  (Ctrl+A)"
                     } else {
                         "Always on Top  (Ctrl+A)"
                     };
 
                     if ui.button(always_on_top_label).clicked() {
-                        self.always_on_top = !self.always_on_top;
-                        let level = if self.always_on_top {
-                            WindowLevel::AlwaysOnTop
-                        } else {
-                            WindowLevel::Normal
-                        };
-                        ctx.send_viewport_cmd(ViewportCommand::WindowLevel(level));
-                        self.status_message = if self.always_on_top {
-                            "Always on top: enabled".to_string()
-                        } else {
-                            "Always on top: disabled".to_string()
-                        };
+                        self.toggle_always_on_top(ctx);
+                        ui.close();
+                    }
+
+                    if ui.checkbox(&mut self.show_history, "History").clicked() {
+                        ui.close();
+                    }
+
+                    if ui.checkbox(&mut self.show_options_table, "All Options Table").clicked() {
+                        ui.close();
+                    }
+
+                    if ui.checkbox(&mut self.show_audit, "Security Audit").clicked() {
+                        ui.close();
+                    }
+
+                    if ui
+                        .add_enabled(
+                            self.system_config.is_some(),
+                            egui::Checkbox::new(&mut self.show_system_config, "System Config (read-only)"),
+                        )
+                        .on_disabled_hover_text("Enable the overlay under Settings → System config")
+                        .clicked()
+                    {
+                        ui.close();
+                    }
+
+                    if ui.button("Preview Main File").clicked() {
+                        self.file_preview = self.config_path.clone();
+                        ui.close();
+                    }
+
+                    if ui.checkbox(&mut self.show_ssh_compare, "Compare with ssh -G").clicked() {
+                        // Start from the selected host's first literal alias
+                        if let Some(config) = &self.config
+                            && let Some(ConfigLine::HostEntry { pattern, .. }) =
+                                self.selected_host.and_then(|idx| config.lines.get(idx))
+                            && let Some(alias) = pattern::split_patterns(pattern)
+                                .find(|p| !p.starts_with('!') && !p.contains(['*', '?']))
+                        {
+                            self.compare_alias = alias.to_string();
+                            self.compare_result = None;
+                        }
                         ui.close();
                     }
                 });
@@ -572,20 +3192,115 @@ impl eframe::App for SshConfigApp {
                         self.show_shortcuts = true;
                         ui.close();
                     }
+
+                    if ui.button("Copy Diagnostics").clicked() {
+                        match &self.config {
+                            Some(config) => {
+                                ctx.copy_text(diagnostics::redacted_summary(config));
+                                self.status.set("Copied redacted diagnostics to clipboard");
+                            }
+                            None => self.status.set("No file loaded"),
+                        }
+                        ui.close();
+                    }
+                });
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let pinned = self.settings.always_on_top;
+                    let hover = if pinned {
+                        "Always on top (Ctrl+A to unpin)"
+                    } else {
+                        "Not on top (Ctrl+A to pin)"
+                    };
+                    let icon = egui::RichText::new("📌").color(if pinned {
+                        ui.visuals().strong_text_color()
+                    } else {
+                        ui.visuals().weak_text_color()
+                    });
+                    if ui.selectable_label(pinned, icon).on_hover_text(hover).clicked() {
+                        self.toggle_always_on_top(ctx);
+                    }
                 });
             });
+            if self.tab_count() > 1 {
+                ui.separator();
+                self.show_tab_bar(ctx, ui);
+            }
         });
 
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label(&self.status_message);
+                if let Some(pending) = &self.loading {
+                    ui.spinner();
+                    ui.label(format!("Loading {}…", pending.path.display()));
+                    ui.separator();
+                }
+
+                if ui
+                    .add(egui::Label::new(self.status.message()).sense(egui::Sense::click()))
+                    .on_hover_text("Click to show history")
+                    .clicked()
+                {
+                    self.show_history = !self.show_history;
+                }
+
+                let warning_count = self
+                    .config
+                    .as_ref()
+                    .map_or(0, |c| c.warnings.len() + lint::conflicting_globals(c).len());
+                if warning_count > 0 {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let text = egui::RichText::new(format!("⚠ {} warning(s)", warning_count))
+                            .color(egui::Color32::YELLOW);
+                        if ui.link(text).clicked() {
+                            self.show_warnings = !self.show_warnings;
+                        }
+                    });
+                }
             });
         });
 
+        if self.show_history {
+            egui::TopBottomPanel::bottom("history_panel")
+                .resizable(true)
+                .default_height(150.0)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong("History (UTC)");
+                        if ui.small_button("Clear").clicked() {
+                            self.status.clear_history();
+                        }
+                        if ui.small_button("✖").clicked() {
+                            self.show_history = false;
+                        }
+                    });
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .auto_shrink([false, false])
+                        .show(ui, |ui| {
+                            for (time, message) in self.status.history().rev() {
+                                ui.horizontal(|ui| {
+                                    ui.label(
+                                        egui::RichText::new(status::format_time(*time))
+                                            .monospace()
+                                            .color(egui::Color32::GRAY),
+                                    );
+                                    ui.label(message);
+                                });
+                            }
+                        });
+                });
+        }
+
         if let Some(config) = &mut self.config {
-            egui::SidePanel::left("hosts_panel")
+            // Keep a remembered width usable even if the window got smaller
+            let max_sidebar_width = (ctx.screen_rect().width() - 200.0).max(MIN_SIDEBAR_WIDTH);
+            let sidebar_width = self.settings.sidebar_width.clamp(MIN_SIDEBAR_WIDTH, max_sidebar_width);
+
+            let sidebar = egui::SidePanel::left("hosts_panel")
                 .resizable(true)
-                .default_width(250.0)
+                .default_width(sidebar_width)
+                .width_range(MIN_SIDEBAR_WIDTH..=max_sidebar_width)
                 .show(ctx, |ui| {
                     ui.heading("SSH Hosts");
                     ui.separator();
@@ -604,85 +3319,379 @@ impl eframe::App for SshConfigApp {
                             self.search_query.clear();
                         }
                     });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.settings.search_options, "Search in options");
+                        ui.checkbox(&mut self.settings.search_fuzzy, "Fuzzy");
+                    });
+
+                    ui.horizontal_wrapped(|ui| {
+                        ui.toggle_value(&mut self.show_changed_only, "● Changed only")
+                            .on_hover_text("Only hosts added or edited since the last save");
+                        for filter in QuickFilter::ALL {
+                            let active = self.settings.quick_filters.contains(&filter);
+                            if ui.selectable_label(active, filter.label()).clicked() {
+                                if active {
+                                    self.settings.quick_filters.retain(|f| *f != filter);
+                                } else {
+                                    self.settings.quick_filters.push(filter);
+                                }
+                            }
+                        }
+                    });
+
+                    let mut all_tags: Vec<&String> = config.hosts().flat_map(|host| host.tags).collect();
+                    all_tags.sort();
+                    all_tags.dedup();
+                    if self.tag_filter.as_ref().is_some_and(|tag| !all_tags.contains(&tag)) {
+                        self.tag_filter = None;
+                    }
+                    if !all_tags.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Tag:");
+                            egui::ComboBox::from_id_salt("tag_filter")
+                                .selected_text(self.tag_filter.as_deref().unwrap_or("All"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.tag_filter, None, "All");
+                                    for tag in &all_tags {
+                                        let text = egui::RichText::new(tag.as_str()).color(tag_color(tag));
+                                        ui.selectable_value(&mut self.tag_filter, Some(tag.to_string()), text);
+                                    }
+                                });
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Sort:");
+                        egui::ComboBox::from_id_salt("host_sort")
+                            .selected_text(self.settings.host_sort.label())
+                            .show_ui(ui, |ui| {
+                                for sort in HostSort::ALL {
+                                    ui.selectable_value(&mut self.settings.host_sort, sort, sort.label());
+                                }
+                            });
+                        if self.settings.host_sort != HostSort::Pattern && !config.included_files.is_empty() {
+                            if ui.small_button("⊞").on_hover_text("Expand all files").clicked() {
+                                self.settings.collapsed_files.clear();
+                            }
+                            if ui.small_button("⊟").on_hover_text("Collapse all files").clicked() {
+                                for line in &config.lines {
+                                    let file = line.source_file();
+                                    if !self.settings.collapsed_files.iter().any(|f| f == file) {
+                                        self.settings.collapsed_files.push(file.to_path_buf());
+                                    }
+                                }
+                            }
+                        }
+                    });
                     ui.separator();
 
                     let search_lower = self.search_query.to_lowercase();
                     let is_searching = !search_lower.is_empty();
+                    let fuzzy = is_searching && self.settings.search_fuzzy;
 
-                    egui::ScrollArea::vertical().show(ui, |ui| {
-                        for (idx, line) in config.lines.iter().enumerate() {
-                            match line {
-                                ConfigLine::HostEntry {
-                                    pattern,
-                                    source_file,
-                                    ..
-                                } => {
-                                    // Filter by search query
-                                    if is_searching && !pattern.to_lowercase().contains(&search_lower) {
-                                        continue;
-                                    }
+                    let key = SidebarKey {
+                        query: search_lower.clone(),
+                        fuzzy,
+                        search_options: self.settings.search_options,
+                        sort: self.settings.host_sort,
+                        changed_only: self.show_changed_only,
+                        generation: self.generation,
+                    };
+                    if self.sidebar_cache.as_ref().is_none_or(|cache| cache.key != key) {
+                        self.sidebar_cache = Some(SidebarCache::build(config, &self.saved_lines, key));
+                    }
+                    let Some(cache) = &self.sidebar_cache else {
+                        return;
+                    };
+                    let best_score = cache.listed.first().map_or(0, |(_, score, _)| *score);
+
+                    // Group headers only make sense while the list is grouped by file
+                    let show_file_headers = !fuzzy && self.settings.host_sort != HostSort::Pattern && !config.included_files.is_empty();
+                    // Search results are never folded away
+                    let can_collapse = show_file_headers && !is_searching;
+
+                    // Work out the visible rows up front so only the ones
+                    // scrolled into view are laid out, which keeps configs
+                    // with thousands of hosts responsive
+                    let mut rows: Vec<SidebarRow> = Vec::new();
+                    let mut last_file: Option<&std::path::Path> = None;
+                    for (idx, score, matching_options) in &cache.listed {
+                        let (idx, score) = (*idx, *score);
+                        match &config.lines[idx] {
+                            ConfigLine::HostEntry { tags, options, source_file, .. } => {
+                                if let Some(tag) = &self.tag_filter
+                                    && !tags.contains(tag)
+                                {
+                                    continue;
+                                }
+                                if !self.settings.quick_filters.iter().all(|filter| filter.matches(options)) {
+                                    continue;
+                                }
+                                if self.show_changed_only && !cache.changed_hosts.contains(&idx) {
+                                    continue;
+                                }
+
+                                if show_file_headers && last_file != Some(source_file.as_path()) {
+                                    last_file = Some(source_file);
+                                    rows.push(SidebarRow::FileHeader(idx));
+                                }
+                                if can_collapse && self.settings.collapsed_files.contains(source_file) {
+                                    continue;
+                                }
+                                rows.push(SidebarRow::Host { idx, score });
+                                rows.extend(matching_options.iter().cloned().map(SidebarRow::OptionMatch));
+                            }
+                            ConfigLine::Include { resolved, .. } if !is_searching && !self.show_changed_only => {
+                                rows.push(SidebarRow::Include(idx));
+                                rows.extend((0..resolved.len()).map(|file| SidebarRow::IncludedFile(idx, file)));
+                            }
+                            _ => {}
+                        }
+                    }
 
-                                    let is_selected = self.selected_host == Some(idx);
+                    self.first_listed_host = rows.iter().find_map(|row| match row {
+                        SidebarRow::Host { idx, .. } => Some(*idx),
+                        _ => None,
+                    });
 
-                                    // Show indicator if from included file
-                                    let display_text = if let Some(main_path) = &self.config_path {
-                                        if source_file != main_path {
-                                            format!("  {}", pattern)
+                    let row_height = ui.spacing().interact_size.y;
+                    egui::ScrollArea::vertical().show_rows(ui, row_height, rows.len(), |ui, visible| {
+                        for row in &rows[visible] {
+                            ui.horizontal(|ui| {
+                                ui.set_height(row_height);
+                                match row {
+                                    SidebarRow::FileHeader(idx) => {
+                                        let file = config.lines[*idx].source_file();
+                                        let name = file
+                                            .file_name()
+                                            .map_or_else(|| file.display().to_string(), |n| n.to_string_lossy().into_owned());
+                                        let system = config.is_system_file(file);
+                                        let icon = if system { "🔒" } else { "📄" };
+                                        let header = if cache.changed_files.contains(file) {
+                                            egui::RichText::new(format!("{} {} ●", icon, name)).color(egui::Color32::YELLOW)
+                                        } else if system {
+                                            egui::RichText::new(format!("{} {}", icon, name)).color(SYSTEM_FILE_COLOR)
                                         } else {
-                                            pattern.clone()
+                                            egui::RichText::new(format!("{} {}", icon, name)).color(egui::Color32::GRAY)
+                                        };
+                                        let mut hover = format!("{}\nClick to preview what will be saved", file.display());
+                                        if system {
+                                            hover.push_str("\nMachine-wide file, saving asks for confirmation");
+                                        }
+                                        if can_collapse {
+                                            let collapsed = self.settings.collapsed_files.iter().any(|f| f == file);
+                                            let arrow = if collapsed { "⏵" } else { "⏷" };
+                                            if ui
+                                                .add(egui::Button::new(egui::RichText::new(arrow).small()).frame(false))
+                                                .on_hover_text(if collapsed { "Show this file's hosts" } else { "Hide this file's hosts" })
+                                                .clicked()
+                                            {
+                                                if collapsed {
+                                                    self.settings.collapsed_files.retain(|f| f != file);
+                                                } else {
+                                                    self.settings.collapsed_files.push(file.to_path_buf());
+                                                }
+                                            }
+                                        }
+                                        let response = ui
+                                            .add(egui::Label::new(header.small()).sense(egui::Sense::click()))
+                                            .on_hover_text(hover);
+                                        if response.clicked() {
+                                            self.file_preview = Some(file.to_path_buf());
+                                        }
+                                    }
+                                    SidebarRow::Host { idx, score } => {
+                                        let ConfigLine::HostEntry { pattern, tags, source_file, .. } = &config.lines[*idx] else {
+                                            return;
+                                        };
+                                        let is_selected = self.selected_host == Some(*idx);
+
+                                        // Show indicator if from included file
+                                        let prefix = match &self.config_path {
+                                            Some(main_path) if source_file != main_path => "  ",
+                                            _ => "",
+                                        };
+                                        let highlight_query = if fuzzy { "" } else { search_lower.as_str() };
+                                        let mut display_text = highlighted_text(ui, prefix, pattern, highlight_query);
+
+                                        // Dim weak fuzzy matches
+                                        if fuzzy && *score < best_score / 2 {
+                                            for section in &mut display_text.sections {
+                                                section.format.color = egui::Color32::GRAY;
+                                            }
+                                        }
+
+                                        for tag in tags {
+                                            display_text.append(
+                                                tag,
+                                                6.0,
+                                                egui::TextFormat {
+                                                    font_id: egui::TextStyle::Small.resolve(ui.style()),
+                                                    color: tag_color(tag),
+                                                    ..Default::default()
+                                                },
+                                            );
+                                        }
+
+                                        if cache.insecure_hosts.contains(idx) {
+                                            display_text.append(
+                                                "🔓",
+                                                6.0,
+                                                egui::TextFormat {
+                                                    color: egui::Color32::from_rgb(231, 76, 60),
+                                                    ..Default::default()
+                                                },
+                                            );
                                         }
-                                    } else {
-                                        pattern.clone()
-                                    };
 
-                                    if ui.selectable_label(is_selected, &display_text).clicked() {
-                                        self.selected_host = Some(idx);
+                                        let response = ui.selectable_label(is_selected, display_text);
+                                        let response = if cache.insecure_hosts.contains(idx) {
+                                            response.on_hover_text("Has risky settings, see View → Security Audit")
+                                        } else {
+                                            response
+                                        };
+                                        if response.clicked() {
+                                            self.selected_host = Some(*idx);
+                                        }
+                                        let mut open_in_editor =
+                                            response.middle_clicked() || (response.clicked() && ui.input(|i| i.modifiers.ctrl));
+                                        response.context_menu(|ui| {
+                                            if ui.button("Open in Editor").clicked() {
+                                                open_in_editor = true;
+                                                ui.close();
+                                            }
+                                            match config.host_line(*idx) {
+                                                Some(line) => {
+                                                    let location = format!("{}:{}", source_file.display(), line);
+                                                    if ui.button(format!("Copy location ({})", location)).clicked() {
+                                                        ctx.copy_text(location);
+                                                        ui.close();
+                                                    }
+                                                }
+                                                None => {
+                                                    ui.label("Not in the file yet, save and reload first");
+                                                }
+                                            }
+                                        });
+                                        if open_in_editor {
+                                            let line = config.host_line(*idx);
+                                            match external_editor::open(&self.settings.external_editor, source_file, line) {
+                                                // Saving here afterwards would overwrite whatever was
+                                                // changed in the editor
+                                                Ok(()) if self.is_dirty => self.status.set(format!(
+                                                    "Opened {} externally. ⚠ This window has unsaved changes; saving them will overwrite external edits",
+                                                    source_file.display()
+                                                )),
+                                                Ok(()) => self.status.set(format!(
+                                                    "Opened {} externally, reload when done",
+                                                    source_file.display()
+                                                )),
+                                                Err(e) => self.status.set(format!("Could not open editor: {}", e)),
+                                            }
+                                        }
                                     }
-                                }
-                                ConfigLine::Include { path, .. } => {
-                                    if !is_searching {
-                                        ui.label(
-                                            egui::RichText::new(format!("📁 Include: {}", path))
-                                                .color(egui::Color32::DARK_GRAY),
-                                        );
+                                    SidebarRow::OptionMatch(option_text) => {
+                                        let job = highlighted_text(ui, "      ", option_text, &search_lower);
+                                        ui.label(job);
+                                    }
+                                    SidebarRow::Include(idx) => {
+                                        if let ConfigLine::Include { path, resolved, .. } = &config.lines[*idx] {
+                                            let text = match resolved.len() {
+                                                0 => egui::RichText::new(format!("📁 Include: {} (no matches)", path))
+                                                    .color(egui::Color32::from_rgb(231, 76, 60)),
+                                                n => egui::RichText::new(format!("📁 Include: {} ({})", path, n))
+                                                    .color(egui::Color32::DARK_GRAY),
+                                            };
+                                            ui.label(text);
+                                        }
+                                    }
+                                    SidebarRow::IncludedFile(idx, file) => {
+                                        let Some(ConfigLine::Include { resolved, .. }) = config.lines.get(*idx) else {
+                                            return;
+                                        };
+                                        let path = &resolved[*file];
+                                        let name = path
+                                            .file_name()
+                                            .map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+                                        ui.add_space(16.0);
+                                        let text = if config.is_system_file(path) {
+                                            egui::RichText::new(format!("🔒 {}", name)).color(SYSTEM_FILE_COLOR)
+                                        } else {
+                                            egui::RichText::new(format!("📄 {}", name))
+                                        };
+                                        let response = ui
+                                            .add(egui::Label::new(text.small()).sense(egui::Sense::click()))
+                                            .on_hover_text(path.display().to_string());
+                                        if response.clicked() {
+                                            match config.hosts().find(|host| host.source_file == path) {
+                                                Some(host) => self.selected_host = Some(host.index),
+                                                None => self.status.set(format!("No hosts in {}", path.display())),
+                                            }
+                                        }
                                     }
                                 }
-                                _ => {}
-                            }
+                            });
                         }
                     });
                 });
+            self.settings.sidebar_width = sidebar.response.rect.width();
 
             egui::CentralPanel::default().show(ctx, |ui| {
+                if self.show_options_table {
+                    ui.horizontal(|ui| {
+                        ui.heading("All Options");
+                        if ui.button("Back to Details").clicked() {
+                            self.show_options_table = false;
+                        }
+                    });
+                    ui.separator();
+
+                    let (select, changed) = self.options_table.show(ui, config, &mut self.status);
+                    if changed {
+                        self.is_dirty = true;
+                        self.generation += 1;
+                    }
+                    if select.is_some() {
+                        self.selected_host = select;
+                        self.show_options_table = false;
+                    }
+                    return;
+                }
+
                 ui.heading("Configuration Details");
                 ui.separator();
 
                 if let Some(selected_idx) = self.selected_host {
+                    let mut move_request = None;
+                    let mut copy_as_text = false;
+                    let mut convert_to_match = false;
+                    let mut jump_to = None;
+                    let alias_links = alias_links(config, selected_idx);
+                    let removed_occurrence = config.host_handle(selected_idx).map_or(0, |h| h.occurrence);
+                    let saved_version = config.saved_host(selected_idx, &self.saved_lines);
+                    let is_new_host = saved_version.is_none();
+                    let is_modified = saved_version != config.lines.get(selected_idx);
+                    let home = config.home().map(std::path::Path::to_path_buf);
+                    let mut revert_request = false;
+                    let mut delete_request = false;
+
                     if let Some(ConfigLine::HostEntry {
                         pattern,
+                        comment,
+                        tags,
                         options,
+                        option_comments,
                         source_file,
                     }) = config.lines.get_mut(selected_idx)
                     {
                         // Add legacy SSH options if Ctrl+Shift+L was pressed
                         if add_legacy {
-                            let legacy_options = vec![
-                                ("HostKeyAlgorithms", "+ssh-rsa,ssh-rsa-cert-v01@openssh.com,ssh-dss"),
-                                ("PubkeyAcceptedAlgorithms", "+ssh-rsa,ssh-rsa-cert-v01@openssh.com"),
-                                ("Ciphers", "+aes256-cbc,aes128-cbc,3des-cbc"),
-                                ("MACs", "+hmac-sha1,hmac-md5"),
-                                ("KexAlgorithms", "+diffie-hellman-group14-sha1,diffie-hellman-group1-sha1"),
-                            ];
-
-                            for (key, value) in legacy_options {
-                                // Check if this option already exists
-                                if !options.iter().any(|(k, _)| k == key) {
-                                    options.push((key.to_string(), value.to_string()));
-                                }
+                            if add_legacy_options(options) {
+                                self.status.set(format!("Added legacy SSH options to {}", pattern));
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            } else {
+                                self.status.set(format!("{} already sets the legacy SSH options", pattern));
                             }
-
-                            self.status_message = format!("Added legacy SSH options to {}", pattern);
-                            self.is_dirty = true;
                         }
 
                         // Show source file info
@@ -692,14 +3701,89 @@ impl eframe::App for SshConfigApp {
                                 egui::RichText::new(source_file.display().to_string())
                                     .color(egui::Color32::GRAY),
                             );
+                            if ui.button("📋 Copy as text").clicked() {
+                                copy_as_text = true;
+                            }
+                            if ui.button("⇄ To Match block").on_hover_text("Turn this host into a `Match host` block").clicked() {
+                                convert_to_match = true;
+                            }
+                            if is_new_host {
+                                if ui.button("🗑 Delete new host").on_hover_text("This host hasn't been saved yet").clicked() {
+                                    delete_request = true;
+                                }
+                            } else if ui
+                                .add_enabled(is_modified, egui::Button::new("↺ Revert host"))
+                                .on_hover_text("Discard changes to this host since the last load or save")
+                                .clicked()
+                            {
+                                revert_request = true;
+                            }
+                        });
+
+                        // Better to know before editing than to find out when saving
+                        if let Some(reason) = unwritable_reason(&mut self.unwritable_files, source_file) {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "⚠ This host is in a read-only file ({}); changes won't be saved",
+                                    reason
+                                ))
+                                .color(egui::Color32::YELLOW),
+                            );
+                        }
+
+                        ui.separator();
+
+                        ui.horizontal(|ui| {
+                            ui.label("Host Pattern:");
+                            if ui.text_edit_singleline(pattern).changed() {
+                                sanitize_field(pattern, &mut self.status);
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            }
+                            if ui.button("▲").on_hover_text("Move host up within its file").clicked() {
+                                move_request = Some(MoveDirection::Up);
+                            }
+                            if ui.button("▼").on_hover_text("Move host down within its file").clicked() {
+                                move_request = Some(MoveDirection::Down);
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Comment:");
+                            let mut comment_text = comment.clone().unwrap_or_default();
+                            if ui.text_edit_singleline(&mut comment_text).changed() {
+                                sanitize_field(&mut comment_text, &mut self.status);
+                                *comment = if comment_text.trim().is_empty() {
+                                    None
+                                } else {
+                                    Some(comment_text)
+                                };
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            }
                         });
 
-                        ui.separator();
-
                         ui.horizontal(|ui| {
-                            ui.label("Host Pattern:");
-                            if ui.text_edit_singleline(pattern).changed() {
+                            ui.label("Tags:");
+                            // The raw text is kept while editing so separators
+                            // being typed aren't normalized away
+                            let edit_id = egui::Id::new(("host_tags", selected_idx));
+                            let mut tags_text = ui
+                                .data_mut(|d| d.get_temp::<String>(edit_id))
+                                .unwrap_or_else(|| tags.join(", "));
+                            let response = ui.add(
+                                egui::TextEdit::singleline(&mut tags_text).hint_text("prod, eu"),
+                            );
+                            if response.changed() {
+                                sanitize_field(&mut tags_text, &mut self.status);
+                                *tags = tags::parse_tag_list(&tags_text);
                                 self.is_dirty = true;
+                                self.generation += 1;
+                            }
+                            if response.has_focus() {
+                                ui.data_mut(|d| d.insert_temp(edit_id, tags_text));
+                            } else {
+                                ui.data_mut(|d| d.remove::<String>(edit_id));
                             }
                         });
 
@@ -708,22 +3792,332 @@ impl eframe::App for SshConfigApp {
 
                         egui::ScrollArea::vertical().show(ui, |ui| {
                             let mut to_remove = None;
+                            let mut to_repeat = None;
+                            let mut hostname_split = None;
+                            let mut list_merge = None;
+                            let mut help_url = None;
+                            let list_merges = lint::mergeable_lists(options);
+                            // `HostName me@host:2222` packs User and Port into the
+                            // host; the split is only offered when it wouldn't
+                            // overwrite a different User/Port
+                            let hostname_splits: Vec<(usize, lint::HostNameSplit, Vec<String>)> = options
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, (key, _))| key.eq_ignore_ascii_case("hostname"))
+                                .filter_map(|(idx, (_, value))| {
+                                    let split = lint::split_hostname(value)?;
+                                    let conflicts = lint::hostname_split_conflicts(options, &split);
+                                    Some((idx, split, conflicts))
+                                })
+                                .collect();
 
-                            for (idx, (key, value)) in options.iter_mut().enumerate() {
-                                ui.horizontal(|ui| {
-                                    ui.label(format!("{}:", key));
-                                    if ui.text_edit_singleline(value).changed() {
-                                        self.is_dirty = true;
+                            egui::Grid::new(("options_grid", selected_idx)).num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
+                                for (idx, (key, value)) in options.iter_mut().enumerate() {
+                                    // The comment written above the option in the file
+                                    if let Some(option_comment) = option_comments.get(idx).filter(|c| !c.is_empty()) {
+                                        for comment_line in option_comment.lines().filter(|l| !l.trim().is_empty()) {
+                                            let note = comment_line.trim().trim_start_matches(['#', ';']).trim();
+                                            ui.label("");
+                                            ui.label(egui::RichText::new(note).small().italics().color(egui::Color32::GRAY));
+                                            ui.end_row();
+                                        }
                                     }
-                                    if ui.button("🗑").clicked() {
-                                        to_remove = Some(idx);
+                                    // Options that only restate ssh's default are dimmed
+                                    let is_default = keywords::is_default_value(key, value);
+                                    let mut key_text = egui::RichText::new(format!("{}:", key));
+                                    if is_default {
+                                        key_text = key_text.weak();
                                     }
-                                });
+                                    // The key acts as the row's focus target: Delete or
+                                    // Backspace removes the option only while the row
+                                    // itself is focused, not while editing the value
+                                    let row_response = ui
+                                        .horizontal(|ui| {
+                                            let response = ui
+                                                .add(egui::Label::new(key_text).sense(egui::Sense::click()))
+                                                .on_hover_text(if is_default {
+                                                    "Same as ssh's default. Click, then press Delete to remove"
+                                                } else {
+                                                    "Click, then press Delete to remove"
+                                                });
+                                            if let Some(url) = keywords::manual_url(key)
+                                                && ui
+                                                    .small_button("?")
+                                                    .on_hover_text(format!("Open {} in the ssh_config manual", key))
+                                                    .clicked()
+                                            {
+                                                help_url = Some(url);
+                                            }
+                                            response
+                                        })
+                                        .inner;
+                                    if row_response.clicked() {
+                                        row_response.request_focus();
+                                    }
+                                    if row_response.has_focus() {
+                                        ui.painter().rect_stroke(
+                                            row_response.rect.expand(2.0),
+                                            2.0,
+                                            ui.visuals().selection.stroke,
+                                            egui::StrokeKind::Outside,
+                                        );
+                                        if ui.input(|i| shortcuts::pressed(i, Command::RemoveOption)) {
+                                            to_remove = Some(idx);
+                                        }
+                                    }
+                                    ui.horizontal(|ui| {
+                                        // The value takes whatever width the buttons leave,
+                                        // so the buttons line up too
+                                        let weak_color = is_default.then(|| ui.visuals().weak_text_color());
+                                        let value_width = (ui.available_width() - OPTION_BUTTONS_WIDTH).max(150.0);
+                                        if ui
+                                            .add(egui::TextEdit::singleline(value).text_color_opt(weak_color).desired_width(value_width))
+                                            .changed()
+                                        {
+                                            sanitize_field(value, &mut self.status);
+                                            self.is_dirty = true;
+                                            self.generation += 1;
+                                        }
+                                        if ui.button("🗑").clicked() {
+                                            to_remove = Some(idx);
+                                        }
+                                        if keywords::is_repeatable(key)
+                                            && ui
+                                                .button("➕")
+                                                .on_hover_text(format!("Add another {} below", key))
+                                                .clicked()
+                                        {
+                                            to_repeat = Some(idx);
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    // ssh refuses private keys other users can read
+                                    if key.eq_ignore_ascii_case("identityfile")
+                                        && let Some(key_path) = ssh_config::identity_file_path(value, home.as_deref())
+                                        && let Some(mode) = ssh_config::open_key_permissions(&key_path)
+                                    {
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            ui.label(
+                                                egui::RichText::new(format!(
+                                                    "⚠ Key permissions are {:o}; ssh requires 600",
+                                                    mode
+                                                ))
+                                                .color(egui::Color32::YELLOW),
+                                            );
+                                            if ui.button("Fix permissions").clicked() {
+                                                match ssh_config::restrict_key_permissions(&key_path) {
+                                                    Ok(()) => self.status.set(format!(
+                                                        "Set permissions of {} to 600",
+                                                        key_path.display()
+                                                    )),
+                                                    Err(e) => self.status.set(format!("Error fixing permissions: {}", e)),
+                                                }
+                                            }
+                                        });
+                                        ui.end_row();
+                                    }
+
+                                    if let Some((_, split, conflicts)) = hostname_splits.iter().find(|(i, _, _)| *i == idx) {
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            let hint = if conflicts.is_empty() {
+                                                format!("⚠ ssh reads this as one host name; did you mean {}?", split.describe())
+                                            } else {
+                                                format!(
+                                                    "⚠ ssh reads this as one host name; {} conflicts with {}",
+                                                    split.describe(),
+                                                    conflicts.join(", ")
+                                                )
+                                            };
+                                            ui.label(egui::RichText::new(hint).color(egui::Color32::YELLOW));
+                                            if conflicts.is_empty() && ui.button("Split").clicked() {
+                                                hostname_split = Some((idx, split.clone()));
+                                            }
+                                        });
+                                        ui.end_row();
+                                    }
+
+                                    // `Ciphers x` followed by `Ciphers +y`: only x counts
+                                    if let Some(merge) = list_merges.iter().find(|m| m.indices[0] == idx) {
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            ui.label(
+                                                egui::RichText::new(format!(
+                                                    "⚠ ssh only uses this {}; the later ones are ignored",
+                                                    key
+                                                ))
+                                                .color(egui::Color32::YELLOW),
+                                            )
+                                            .on_hover_text(format!(
+                                                "+, - and ^ change ssh's default list, not an earlier {} line. \
+                                                 Combined, they would be:\n{} {}",
+                                                key, key, merge.combined
+                                            ));
+                                            if ui.button("Combine").clicked() {
+                                                list_merge = Some(merge.clone());
+                                            }
+                                        });
+                                        ui.end_row();
+                                    }
+
+                                    // One row per variable, joined back into the value
+                                    if key.eq_ignore_ascii_case("setenv") {
+                                        let mut pairs = env_vars::split_set_env(value);
+                                        let mut edited = false;
+                                        let mut remove = None;
+                                        for (pair_idx, (name, var_value)) in pairs.iter_mut().enumerate() {
+                                            ui.label("");
+                                            ui.horizontal(|ui| {
+                                                edited |= ui.add(egui::TextEdit::singleline(name).desired_width(120.0)).changed();
+                                                ui.label("=");
+                                                edited |= ui.add(egui::TextEdit::singleline(var_value).desired_width(160.0)).changed();
+                                                if ui.small_button("🗑").clicked() {
+                                                    remove = Some(pair_idx);
+                                                }
+                                                if !env_vars::is_valid_env_name(name) {
+                                                    ui.label(egui::RichText::new("⚠ Invalid name").color(egui::Color32::YELLOW));
+                                                }
+                                            });
+                                            ui.end_row();
+                                        }
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            if ui.small_button("➕ Variable").clicked() {
+                                                pairs.push(("NAME".to_string(), String::new()));
+                                                edited = true;
+                                            }
+                                        });
+                                        ui.end_row();
+                                        if let Some(pair_idx) = remove {
+                                            pairs.remove(pair_idx);
+                                            edited = true;
+                                        }
+                                        if edited {
+                                            for (name, var_value) in &mut pairs {
+                                                sanitize_field(name, &mut self.status);
+                                                sanitize_field(var_value, &mut self.status);
+                                            }
+                                            *value = env_vars::join_set_env(&pairs);
+                                            self.is_dirty = true;
+                                            self.generation += 1;
+                                        }
+                                    } else if key.eq_ignore_ascii_case("sendenv") {
+                                        let mut names = env_vars::split_send_env(value);
+                                        let mut edited = false;
+                                        let mut remove = None;
+                                        for (name_idx, name) in names.iter_mut().enumerate() {
+                                            ui.label("");
+                                            ui.horizontal(|ui| {
+                                                edited |= ui.add(egui::TextEdit::singleline(name).desired_width(160.0)).changed();
+                                                if ui.small_button("🗑").clicked() {
+                                                    remove = Some(name_idx);
+                                                }
+                                                if !env_vars::is_valid_send_env_name(name) {
+                                                    ui.label(egui::RichText::new("⚠ Invalid name").color(egui::Color32::YELLOW));
+                                                }
+                                            });
+                                            ui.end_row();
+                                        }
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            if ui.small_button("➕ Variable").clicked() {
+                                                names.push("NAME".to_string());
+                                                edited = true;
+                                            }
+                                        });
+                                        ui.end_row();
+                                        if let Some(name_idx) = remove {
+                                            names.remove(name_idx);
+                                            edited = true;
+                                        }
+                                        if edited {
+                                            for name in &mut names {
+                                                sanitize_field(name, &mut self.status);
+                                            }
+                                            *value = env_vars::join_send_env(&names);
+                                            self.is_dirty = true;
+                                            self.generation += 1;
+                                        }
+                                    }
+
+                                    // Links to the hosts this option refers to
+                                    for (alias, target) in alias_links.iter().filter(|l| l.0 == idx).map(|l| (&l.1, l.2)) {
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            match target {
+                                                Some(target_idx) => {
+                                                    if ui.link(format!("→ {}", alias)).clicked() {
+                                                        jump_to = Some(target_idx);
+                                                    }
+                                                }
+                                                None => {
+                                                    ui.label(
+                                                        egui::RichText::new(format!(
+                                                            "⚠ '{}' is not defined in this config",
+                                                            alias
+                                                        ))
+                                                        .color(egui::Color32::YELLOW),
+                                                    );
+                                                }
+                                            }
+                                        });
+                                        ui.end_row();
+                                    }
+                                }
+                            });
+
+                            if let Some(url) = help_url
+                                && let Err(e) = external_editor::open_url(&url)
+                            {
+                                self.status.set(format!("Error opening {}: {}", url, e));
+                            }
+
+                            if let Some(merge) = list_merge {
+                                lint::apply_list_merge(options, option_comments, &merge);
+                                self.status.set(format!("Combined {} into {} {}", merge.key, merge.key, merge.combined));
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            }
+
+                            if let Some((idx, split)) = hostname_split
+                                && let Some(inserted) = lint::apply_hostname_split(options, idx, &split)
+                            {
+                                for position in inserted {
+                                    ssh_config::insert_option_comment(option_comments, position, String::new());
+                                }
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            }
+
+                            // The copy goes right after the original so repeated
+                            // entries stay together, in the order ssh tries them
+                            if let Some(idx) = to_repeat {
+                                let repeated = options[idx].clone();
+                                options.insert(idx + 1, repeated);
+                                ssh_config::insert_option_comment(option_comments, idx + 1, String::new());
+                                self.is_dirty = true;
+                                self.generation += 1;
                             }
 
                             if let Some(idx) = to_remove {
-                                options.remove(idx);
+                                let (key, value) = options.remove(idx);
+                                let option_comment = ssh_config::remove_option_comment(option_comments, idx);
+                                self.removed_option = Some(RemovedOption {
+                                    host: HostHandle {
+                                        source_file: source_file.clone(),
+                                        pattern: pattern.clone(),
+                                        occurrence: removed_occurrence,
+                                    },
+                                    option_idx: idx,
+                                    key,
+                                    value,
+                                    option_comment,
+                                    removed_at: Instant::now(),
+                                });
                                 self.is_dirty = true;
+                                self.generation += 1;
                             }
 
                             ui.separator();
@@ -737,6 +4131,9 @@ impl eframe::App for SshConfigApp {
                                     egui::TextEdit::singleline(&mut self.new_option_key)
                                         .id(egui::Id::new("new_option_key_field"))
                                 );
+                                if key_response.changed() {
+                                    sanitize_field(&mut self.new_option_key, &mut self.status);
+                                }
 
                                 // Show error if key contains spaces
                                 if self.new_option_key.contains(' ') {
@@ -757,7 +4154,11 @@ impl eframe::App for SshConfigApp {
                                 let value_response = ui.add(
                                     egui::TextEdit::singleline(&mut self.new_option_value)
                                         .id(egui::Id::new("new_option_value_field"))
+                                        .hint_text("use \"\" for an empty value")
                                 );
+                                if value_response.changed() {
+                                    sanitize_field(&mut self.new_option_value, &mut self.status);
+                                }
 
                                 // Enter on value field adds the option
                                 if value_response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
@@ -771,16 +4172,6 @@ impl eframe::App for SshConfigApp {
                                 }
                             });
 
-                            if add_option {
-                                options.push((
-                                    self.new_option_key.clone(),
-                                    self.new_option_value.clone(),
-                                ));
-                                self.new_option_key.clear();
-                                self.new_option_value.clear();
-                                self.is_dirty = true;
-                            }
-
                             ui.horizontal(|ui| {
                                 let can_add = !self.new_option_key.is_empty()
                                     && !self.new_option_key.contains(' ')
@@ -790,57 +4181,205 @@ impl eframe::App for SshConfigApp {
                                     .add_enabled(can_add, egui::Button::new("➕ Add Option"))
                                     .clicked()
                                 {
-                                    options.push((
-                                        self.new_option_key.clone(),
-                                        self.new_option_value.clone(),
-                                    ));
-                                    self.new_option_key.clear();
-                                    self.new_option_value.clear();
-                                    self.is_dirty = true;
+                                    add_option = true;
                                 }
+                                ui.checkbox(&mut self.settings.new_options_at_top, "Add at top");
                             });
+
+                            if add_option {
+                                let option = (
+                                    self.new_option_key.clone(),
+                                    ssh_config::trim_value(&self.new_option_value).to_string(),
+                                );
+                                if self.settings.new_options_at_top {
+                                    options.insert(0, option);
+                                    ssh_config::insert_option_comment(option_comments, 0, String::new());
+                                } else {
+                                    options.push(option);
+                                }
+                                self.new_option_key.clear();
+                                self.new_option_value.clear();
+                                self.is_dirty = true;
+                                self.generation += 1;
+                            }
                         });
                     }
+
+                    if let Some(target_idx) = jump_to {
+                        self.selected_host = Some(target_idx);
+                    }
+
+                    if revert_request && config.revert_host(selected_idx, &self.saved_lines) {
+                        self.is_dirty = config.lines != self.saved_lines;
+                        self.generation += 1;
+                        self.status.set("Reverted host to its last saved state");
+                    }
+
+                    if delete_request {
+                        config.lines.remove(selected_idx);
+                        self.selected_host = None;
+                        self.is_dirty = config.lines != self.saved_lines;
+                        self.generation += 1;
+                        self.status.set("Deleted unsaved host");
+                    }
+
+                    if convert_to_match {
+                        self.match_conversion = config.host_handle(selected_idx);
+                    }
+
+                    if copy_as_text && let Some(line) = config.lines.get(selected_idx) {
+                        ctx.copy_text(config.line_to_string(line));
+                        self.status.set("Copied host to clipboard");
+                    }
+
+                    if let Some(direction) = move_request {
+                        if let Some(new_idx) = config.move_host(selected_idx, direction) {
+                            self.selected_host = Some(new_idx);
+                            self.is_dirty = true;
+                            self.generation += 1;
+                        } else {
+                            self.status.set("Host is already at the edge of its file");
+                        }
+                    }
                 } else {
                     ui.label("Select a host from the left panel to edit");
 
                     ui.separator();
-                    ui.heading("All Configuration Lines");
+                    ui.horizontal(|ui| {
+                        ui.heading("All Configuration Lines");
+                        if ui.button("➕ Add Include…").clicked() {
+                            self.show_include_dialog = true;
+                        }
+                    });
 
+                    // Indentation each file will be written with
+                    let indents: HashMap<PathBuf, String> = config
+                        .lines
+                        .iter()
+                        .map(|line| line.source_file())
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .map(|file| (file.to_path_buf(), config.format_for(file).indent.replace('\t', "    ")))
+                        .collect();
+
+                    let mut convert_to_host = None;
                     egui::ScrollArea::vertical().show(ui, |ui| {
-                        for line in &config.lines {
+                        // Whether the previous line was a host or one of its
+                        // commented-out options
+                        let mut in_host_block = false;
+                        for (idx, line) in config.lines.iter_mut().enumerate() {
+                            let follows_host = std::mem::replace(&mut in_host_block, false);
                             match line {
-                                ConfigLine::Comment { text, .. } => {
-                                    ui.label(egui::RichText::new(text).color(egui::Color32::GRAY));
-                                }
+                                ConfigLine::Comment { text, .. } => match comments::classify_comment(text, follows_host) {
+                                    CommentKind::DisabledOption { .. } => {
+                                        in_host_block = true;
+                                        ui.label(egui::RichText::new(text.as_str()).color(egui::Color32::GRAY).italics())
+                                            .on_hover_text("Commented-out option");
+                                    }
+                                    CommentKind::DisabledHost(_) => {
+                                        ui.label(egui::RichText::new(text.as_str()).color(egui::Color32::GRAY).italics())
+                                            .on_hover_text("Commented-out host");
+                                    }
+                                    CommentKind::Tags(_) | CommentKind::Text => {
+                                        ui.label(egui::RichText::new(text.as_str()).color(egui::Color32::GRAY));
+                                    }
+                                },
                                 ConfigLine::Empty { .. } => {
                                     ui.label("");
                                 }
-                                ConfigLine::Include { path, .. } => {
+                                ConfigLine::Include { path, comment, .. } => {
                                     ui.label(
-                                        egui::RichText::new(format!("Include {}", path))
+                                        egui::RichText::new(format!("Include {}{}", path, trailing_comment_text(comment)))
                                             .color(egui::Color32::LIGHT_BLUE),
                                     );
                                 }
                                 ConfigLine::GlobalOption { key, value, .. } => {
-                                    ui.label(format!("{} {}", key, value));
+                                    // Global options are edited in place
+                                    ui.horizontal(|ui| {
+                                        ui.label(key.as_str());
+                                        let response = ui.add(
+                                            egui::TextEdit::singleline(value)
+                                                .id(egui::Id::new(("global_option_value", idx))),
+                                        );
+                                        if response.changed() {
+                                            sanitize_field(value, &mut self.status);
+                                            self.is_dirty = true;
+                                            self.generation += 1;
+                                        }
+                                    });
                                 }
                                 ConfigLine::HostEntry {
                                     pattern,
+                                    comment,
+                                    tags: _,
                                     options,
-                                    source_file: _,
+                                    option_comments,
+                                    source_file,
                                 } => {
                                     ui.label(
-                                        egui::RichText::new(format!("Host {}", pattern))
+                                        egui::RichText::new(format!("Host {}{}", pattern, trailing_comment_text(comment)))
                                             .strong(),
                                     );
-                                    for (key, value) in options {
-                                        ui.label(format!("    {} {}", key, value));
+                                    in_host_block = true;
+                                    let indent = indents.get(source_file).map_or("", String::as_str);
+                                    for (idx, (key, value)) in options.iter().enumerate() {
+                                        if let Some(option_comment) = option_comments.get(idx).filter(|c| !c.is_empty()) {
+                                            ui.label(
+                                                egui::RichText::new(option_comment.trim_end_matches('\n'))
+                                                    .monospace()
+                                                    .color(egui::Color32::GRAY),
+                                            );
+                                        }
+                                        ui.label(egui::RichText::new(format!("{}{} {}", indent, key, value)).monospace());
+                                    }
+                                }
+                                ConfigLine::MatchEntry {
+                                    criteria,
+                                    comment,
+                                    options,
+                                    option_comments,
+                                    source_file,
+                                    ..
+                                } => {
+                                    ui.horizontal(|ui| {
+                                        ui.label(
+                                            egui::RichText::new(format!("Match {}{}", criteria, trailing_comment_text(comment)))
+                                                .strong(),
+                                        );
+                                        if ssh_config::match_host_patterns(criteria).is_some()
+                                            && ui
+                                                .small_button("⇄ To Host block")
+                                                .on_hover_text("Turn this block into a Host block for the same patterns")
+                                                .clicked()
+                                        {
+                                            convert_to_host = Some(idx);
+                                        }
+                                    });
+                                    in_host_block = true;
+                                    let indent = indents.get(source_file).map_or("", String::as_str);
+                                    for (idx, (key, value)) in options.iter().enumerate() {
+                                        if let Some(option_comment) = option_comments.get(idx).filter(|c| !c.is_empty()) {
+                                            ui.label(
+                                                egui::RichText::new(option_comment.trim_end_matches('\n'))
+                                                    .monospace()
+                                                    .color(egui::Color32::GRAY),
+                                            );
+                                        }
+                                        ui.label(egui::RichText::new(format!("{}{} {}", indent, key, value)).monospace());
                                     }
                                 }
                             }
                         }
                     });
+
+                    if let Some(idx) = convert_to_host
+                        && config.convert_match_to_host(idx)
+                    {
+                        self.selected_host = Some(idx);
+                        self.is_dirty = true;
+                        self.generation += 1;
+                        self.status.set("Converted the Match block to a Host block");
+                    }
                 }
             });
         } else {
@@ -849,7 +4388,30 @@ impl eframe::App for SshConfigApp {
                     ui.add_space(200.0);
                     ui.heading("SSH Config Editor");
                     ui.add_space(20.0);
+                    if let Some(pending) = &self.loading {
+                        ui.spinner();
+                        ui.label(format!("Loading {}…", pending.path.display()));
+                        return;
+                    }
                     ui.label("Click File → Open SSH Config to get started");
+
+                    if let Some(home) = self.home.clone() {
+                        let default_path = home.join(".ssh").join("config");
+                        if !default_path.exists() {
+                            ui.add_space(10.0);
+                            if ui.button(format!("Create {}", default_path.display())).clicked() {
+                                self.config = Some(SshConfig::with_home(Some(home.clone())));
+                                self.saved_lines.clear();
+                                self.config_path = Some(default_path.clone());
+                                self.is_dirty = true;
+                                self.generation += 1;
+                                self.status.set(format!(
+                                    "New config: {} (not saved yet)",
+                                    default_path.display()
+                                ));
+                            }
+                        }
+                    }
                 });
             });
         }
@@ -859,12 +4421,519 @@ impl eframe::App for SshConfigApp {
             self.show_shortcuts_popup(ctx);
         }
 
-        if self.show_quit_dialog {
-            self.show_quit_dialog(ctx);
+        if self.unsaved_prompt.is_some() {
+            self.show_unsaved_dialog(ctx);
+        }
+
+        if self.duplicate_warning.is_some() {
+            self.show_duplicate_dialog(ctx);
+        }
+
+        if self.system_save_warning.is_some() {
+            self.show_system_save_dialog(ctx);
+        }
+
+        if self.lossy_save_warning.is_some() {
+            self.show_lossy_save_dialog(ctx);
+        }
+
+        if self.match_conversion.is_some() {
+            self.show_match_conversion_dialog(ctx);
         }
 
         if self.show_new_host_dialog {
             self.show_new_host_dialog(ctx);
         }
+
+        if self.show_include_dialog {
+            self.show_include_dialog(ctx);
+        }
+
+        if self.pasted_host.is_some() {
+            self.show_pasted_host_dialog(ctx);
+        }
+
+        if self.merge_source.is_some() {
+            self.show_merge_dialog(ctx);
+        }
+
+        if self.inventory_import.is_some() {
+            self.show_inventory_import_dialog(ctx);
+        }
+
+        if self.host_move.is_some() {
+            self.show_host_move_dialog(ctx);
+        }
+
+        if self.show_legacy_prompt {
+            self.show_legacy_prompt_dialog(ctx);
+        }
+
+        if self.show_warnings {
+            self.show_warnings_window(ctx);
+        }
+
+        if self.show_system_config {
+            self.show_system_config_window(ctx);
+        }
+
+        if self.pending_create_dir.is_some() {
+            self.show_create_dir_dialog(ctx);
+        }
+
+        if self.removed_option.is_some() {
+            self.show_undo_toast(ctx);
+        }
+
+        if self.show_settings {
+            self.show_settings_window(ctx);
+        }
+
+        if self.show_ssh_compare {
+            self.show_ssh_compare_window(ctx);
+        }
+
+        if self.file_preview.is_some() {
+            self.show_file_preview(ctx);
+        }
+
+        if self.show_audit {
+            self.show_audit_window(ctx);
+        }
+
+        if self.show_matrix_export {
+            self.show_matrix_export_window(ctx);
+        }
+
+        if self.duplicate_merge_file.is_some() {
+            self.show_duplicate_merge_window(ctx);
+        }
+
+        // Remember which host is selected so it can be found again if lines
+        // move before the next frame
+        self.selected_handle = match (&self.config, self.selected_host) {
+            (Some(config), Some(idx)) => config.host_handle(idx),
+            _ => None,
+        };
+    }
+}
+
+fn trailing_comment_text(comment: &Option<String>) -> String {
+    match comment {
+        Some(comment) => format!(" # {}", comment),
+        None => String::new(),
+    }
+}
+
+// Lays out `prefix` followed by `text` with every case-insensitive match of
+// `query_lower` highlighted
+fn highlighted_text(ui: &egui::Ui, prefix: &str, text: &str, query_lower: &str) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let normal = egui::TextFormat {
+        font_id: font_id.clone(),
+        color: ui.visuals().text_color(),
+        ..Default::default()
+    };
+    let highlight = egui::TextFormat {
+        font_id,
+        color: ui.visuals().strong_text_color(),
+        background: ui.visuals().selection.bg_fill,
+        ..Default::default()
+    };
+
+    let mut job = egui::text::LayoutJob::default();
+    job.append(prefix, 0.0, normal.clone());
+
+    let mut last_end = 0;
+    for range in search::find_matches(text, query_lower) {
+        job.append(&text[last_end..range.start], 0.0, normal.clone());
+        job.append(&text[range.clone()], 0.0, highlight.clone());
+        last_end = range.end;
+    }
+    job.append(&text[last_end..], 0.0, normal);
+
+    job
+}
+
+// Saves every file of a document and updates its saved lines. Files that
+// were skipped keep their last saved lines, so they still show as changed.
+// Edited files with invalid UTF-8 are only written with `write_lossy`. Also
+// returns how many lossily decoded files were written.
+fn write_document(
+    config: &mut SshConfig,
+    path: &std::path::Path,
+    saved_lines: &mut Vec<ConfigLine>,
+    write_lossy: bool,
+) -> (SaveReport, usize) {
+    let lossy_before = config.lossy_files.len();
+    let report = config.save_all_with(path, write_lossy);
+    let skipped: HashSet<&std::path::Path> = report.skipped.iter().map(|(file, _)| file.as_path()).collect();
+
+    let kept: Vec<ConfigLine> = saved_lines
+        .iter()
+        .filter(|line| skipped.contains(line.source_file()))
+        .cloned()
+        .collect();
+    *saved_lines = config
+        .lines
+        .iter()
+        .filter(|line| !skipped.contains(line.source_file()))
+        .cloned()
+        .chain(kept)
+        .collect();
+
+    let lossy_written = lossy_before - config.lossy_files.len();
+    (report, lossy_written)
+}
+
+// What saving every open document did
+#[derive(Debug, Default, PartialEq)]
+struct SaveAllSummary {
+    // Documents saved completely
+    documents: usize,
+    files: usize,
+    // Documents with files that couldn't be written: title and how many
+    failed: Vec<(String, usize)>,
+    // Main files that were written, for the post-save command
+    written_mains: Vec<PathBuf>,
+}
+
+impl SaveAllSummary {
+    fn message(&self) -> String {
+        let mut message = format!("Saved {} document(s), {} file(s)", self.documents, self.files);
+        if !self.failed.is_empty() {
+            let failed: Vec<String> = self
+                .failed
+                .iter()
+                .map(|(title, skipped)| format!("{} ({} file(s) not written)", title, skipped))
+                .collect();
+            message.push_str(&format!("; failed: {}", failed.join(", ")));
+        }
+        message
+    }
+}
+
+// Saves every document with unsaved changes. A document with files that
+// couldn't be written stays dirty and doesn't stop the others from saving.
+fn save_documents(documents: &mut [Document], write_lossy: bool) -> SaveAllSummary {
+    let mut summary = SaveAllSummary::default();
+    for document in documents {
+        let (true, Some(config), Some(path)) = (document.is_dirty, &mut document.config, &document.config_path) else {
+            continue;
+        };
+        let (report, _) = write_document(config, path, &mut document.saved_lines, write_lossy);
+        document.is_dirty = !report.skipped.is_empty();
+        summary.files += report.written.len();
+        if report.skipped.is_empty() {
+            summary.documents += 1;
+        } else {
+            summary.failed.push((document.title(), report.skipped.len()));
+        }
+        if report.written.contains(path) {
+            summary.written_mains.push(path.clone());
+        }
+    }
+    summary
+}
+
+// Why `file` can't be written, checked the first time it's asked about and
+// then taken from `cache` until the cache is cleared
+fn unwritable_reason<'a>(cache: &'a mut HashMap<PathBuf, Option<String>>, file: &std::path::Path) -> Option<&'a str> {
+    cache
+        .entry(file.to_path_buf())
+        .or_insert_with(|| ssh_config::check_writable(file).err())
+        .as_deref()
+}
+
+// Tab label: the file name, with a dot while there are unsaved changes
+fn tab_title(config: Option<&SshConfig>, path: Option<&PathBuf>, is_dirty: bool) -> String {
+    let mut name = path.map_or_else(
+        || "Untitled".to_string(),
+        |path| path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned()),
+    );
+    if let (Some(config), Some(path)) = (config, path)
+        && config.is_system_file(path)
+    {
+        name = format!("🔒 {}", name);
+    }
+    if is_dirty { format!("● {}", name) } else { name }
+}
+
+// Flattens tabs and line breaks that were pasted into a single-line field
+// and notes it in the status bar
+fn sanitize_field(text: &mut String, status: &mut StatusLog) {
+    if let Some(clean) = ssh_config::sanitize_single_line(text) {
+        *text = clean;
+        status.set("Removed line breaks and tabs from the entered text");
+    }
+}
+
+// Lays out config file text in monospace with comments dimmed and Host
+// lines and keywords emphasized
+fn config_text_job(ui: &egui::Ui, text: &str) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let format = |color: egui::Color32| egui::TextFormat {
+        font_id: font_id.clone(),
+        color,
+        ..Default::default()
+    };
+    let normal = format(ui.visuals().text_color());
+    let comment = format(egui::Color32::GRAY);
+    let keyword = format(egui::Color32::LIGHT_BLUE);
+    let host = format(ui.visuals().strong_text_color());
+
+    let mut job = egui::text::LayoutJob::default();
+    for line in text.lines() {
+        let content = line.trim_start();
+        let indent = &line[..line.len() - content.len()];
+        job.append(indent, 0.0, normal.clone());
+        if content.starts_with('#') {
+            job.append(content, 0.0, comment.clone());
+        } else {
+            let key_len = content.find(char::is_whitespace).unwrap_or(content.len());
+            let (key, rest) = content.split_at(key_len);
+            if key.eq_ignore_ascii_case("host") || key.eq_ignore_ascii_case("match") {
+                job.append(content, 0.0, host.clone());
+            } else {
+                job.append(key, 0.0, keyword.clone());
+                job.append(rest, 0.0, normal.clone());
+            }
+        }
+        job.append("\n", 0.0, normal.clone());
+    }
+    job
+}
+
+fn severity_color(severity: Severity) -> egui::Color32 {
+    match severity {
+        Severity::Info => egui::Color32::GRAY,
+        Severity::Warning => egui::Color32::YELLOW,
+        Severity::High => egui::Color32::from_rgb(231, 76, 60),
+    }
+}
+
+// Stable color for a tag so it looks the same everywhere it's shown
+fn tag_color(tag: &str) -> egui::Color32 {
+    const PALETTE: [egui::Color32; 6] = [
+        egui::Color32::from_rgb(231, 76, 60),
+        egui::Color32::from_rgb(46, 204, 113),
+        egui::Color32::from_rgb(52, 152, 219),
+        egui::Color32::from_rgb(241, 196, 15),
+        egui::Color32::from_rgb(155, 89, 182),
+        egui::Color32::from_rgb(26, 188, 156),
+    ];
+    let hash = tag.bytes().fold(0usize, |acc, b| acc.wrapping_mul(31).wrapping_add(b as usize));
+    PALETTE[hash % PALETTE.len()]
+}
+
+// Adds the `LEGACY_OPTIONS` the host doesn't set yet. Returns whether any
+// was added.
+fn add_legacy_options(options: &mut Vec<(String, String)>) -> bool {
+    let before = options.len();
+    for (key, value) in LEGACY_OPTIONS {
+        if !options.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            options.push((key.to_string(), value.to_string()));
+        }
+    }
+    options.len() > before
+}
+
+// Host aliases referenced by the options of the host at `host_idx`, as
+// (option index, alias, defining host index). ProxyJump hops are always
+// listed so undefined ones can be flagged; a HostName is only linked when it
+// names another host in this config.
+fn alias_links(config: &SshConfig, host_idx: usize) -> Vec<(usize, String, Option<usize>)> {
+    let mut links = Vec::new();
+    let Some(ConfigLine::HostEntry { options, .. }) = config.lines.get(host_idx) else {
+        return links;
+    };
+
+    for (option_idx, (key, value)) in options.iter().enumerate() {
+        if key.eq_ignore_ascii_case("proxyjump") {
+            for alias in pattern::proxy_jump_hosts(value) {
+                let target = config.find_host_for_alias(alias);
+                links.push((option_idx, alias.to_string(), target));
+            }
+        } else if key.eq_ignore_ascii_case("hostname")
+            && !keywords::is_none_value(value)
+            && let Some(target) = config.find_host_for_alias(value)
+            && target != host_idx
+            && matches!(
+                config.lines.get(target),
+                Some(ConfigLine::HostEntry { pattern: p, .. }) if pattern::defines_alias(p, value)
+            )
+        {
+            links.push((option_idx, value.clone(), Some(target)));
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn document(path: &Path, text: &str, is_dirty: bool) -> Document {
+        std::fs::write(path, text).unwrap();
+        let config = SshConfig::parse_file(path).unwrap();
+        Document {
+            saved_lines: config.lines.clone(),
+            config: Some(config),
+            config_path: Some(path.to_path_buf()),
+            is_dirty,
+            ..Document::default()
+        }
+    }
+
+    fn add_option(document: &mut Document, pattern: &str) {
+        if let Some(ConfigLine::HostEntry { options, .. }) = document.config.as_mut().unwrap().host_mut_by_pattern(pattern) {
+            options.push(("User".to_string(), "me".to_string()));
+        }
+    }
+
+    #[test]
+    fn save_all_documents_continues_past_a_partial_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second, clean) = (dir.path().join("first"), dir.path().join("second"), dir.path().join("clean"));
+        let missing = dir.path().join("missing").join("work");
+
+        let mut documents = vec![
+            document(&first, "Host a\n", true),
+            document(&second, "Host b\n", true),
+            document(&clean, "Host c\n", false),
+        ];
+        add_option(&mut documents[0], "a");
+        add_option(&mut documents[1], "b");
+        documents[1].config.as_mut().unwrap().lines.push(ConfigLine::GlobalOption {
+            key: "User".to_string(),
+            value: "me".to_string(),
+            source_file: missing,
+        });
+        add_option(&mut documents[2], "c");
+
+        let summary = save_documents(&mut documents, false);
+        assert_eq!((summary.documents, summary.files), (1, 2));
+        assert_eq!(summary.written_mains, [first.clone(), second.clone()]);
+        // The title of the still dirty tab, which counts as a system file
+        // when the temporary directory is outside the home directory
+        let failed = [(documents[1].title(), 1)];
+        assert_eq!(summary.failed, failed);
+        assert!(summary.message().starts_with("Saved 1 document(s), 2 file(s); failed: ● "));
+
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "Host a\n    User me\n");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "Host b\n    User me\n");
+        // Clean documents aren't written, even with (unflagged) changes
+        assert_eq!(std::fs::read_to_string(&clean).unwrap(), "Host c\n");
+        assert_eq!(documents.iter().map(|d| d.is_dirty).collect::<Vec<_>>(), [false, true, false]);
+    }
+
+    #[test]
+    fn save_all_documents_with_nothing_to_save() {
+        let mut documents = vec![Document::default()];
+        let summary = save_documents(&mut documents, false);
+        assert_eq!(summary, SaveAllSummary::default());
+        assert_eq!(summary.message(), "Saved 0 document(s), 0 file(s)");
+    }
+
+    #[test]
+    fn none_values_link_to_no_host() {
+        // Even with a host called `none`, the values mean "no host"
+        let config = SshConfig::parse_str(
+            "Host none\n\nHost web\n    HostName none\n    ProxyJump NONE\n    IdentityFile none\n",
+            "config",
+        )
+        .unwrap();
+        let web = config.find_host_index("web").unwrap();
+        assert_eq!(alias_links(&config, web), []);
+        assert_eq!(lint::structural_warnings(&config), []);
+    }
+
+    #[test]
+    fn sidebar_cache_filters_and_ranks_hosts() {
+        let config = SshConfig::parse_str("Host web-prod\n    Port 2222\n\nHost db\n\nHost prodweb\n", "config").unwrap();
+        let key = |query: &str, fuzzy| SidebarKey {
+            query: query.to_string(),
+            fuzzy,
+            search_options: true,
+            sort: HostSort::Pattern,
+            changed_only: false,
+            generation: 0,
+        };
+        let listed = |key| -> Vec<(String, Vec<String>)> {
+            SidebarCache::build(&config, &config.lines, key)
+                .listed
+                .into_iter()
+                .map(|(idx, _, matching)| match &config.lines[idx] {
+                    ConfigLine::HostEntry { pattern, .. } => (pattern.clone(), matching),
+                    line => panic!("listed a non-host line {:?}", line),
+                })
+                .collect()
+        };
+        let pattern = |name: &str| (name.to_string(), Vec::new());
+
+        assert_eq!(listed(key("", false)), [pattern("db"), pattern("prodweb"), pattern("web-prod")]);
+        assert_eq!(listed(key("2222", false)), [("web-prod".to_string(), vec!["Port 2222".to_string()])]);
+        assert_eq!(listed(key("prodweb", true))[0], pattern("prodweb"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hosts_of_read_only_includes_are_flagged_once_checked() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system");
+        std::fs::write(&system, "Host sys\n    Port 22\n").unwrap();
+        std::fs::set_permissions(&system, std::fs::Permissions::from_mode(0o444)).unwrap();
+        let main = dir.path().join("config");
+        std::fs::write(&main, format!("Include {}\n\nHost web\n", system.display())).unwrap();
+        let config = SshConfig::parse_file(&main).unwrap();
+
+        let mut cache = HashMap::new();
+        let source = |pattern| config.lines[config.find_host_index(pattern).unwrap()].source_file().to_path_buf();
+        assert_eq!(unwritable_reason(&mut cache, &source("sys")), Some("file is read-only"));
+        assert_eq!(unwritable_reason(&mut cache, &source("web")), None);
+
+        // Checked lazily: the answer stays until the cache is cleared
+        std::fs::set_permissions(&system, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(unwritable_reason(&mut cache, &system), Some("file is read-only"));
+        cache.clear();
+        assert_eq!(unwritable_reason(&mut cache, &system), None);
+    }
+
+    #[test]
+    fn edited_hosts_show_as_changed_until_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        std::fs::write(&path, "Host web\n    User me\n\nHost db\n").unwrap();
+        let mut config = SshConfig::parse_file(&path).unwrap();
+        let mut saved_lines = config.lines.clone();
+        let key = SidebarKey {
+            query: String::new(),
+            fuzzy: false,
+            search_options: false,
+            sort: HostSort::FileOrder,
+            changed_only: true,
+            generation: 0,
+        };
+
+        let cache = SidebarCache::build(&config, &saved_lines, key.clone());
+        assert!(cache.changed_hosts.is_empty());
+        assert!(cache.changed_files.is_empty());
+
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("web") {
+            options.push(("Port".to_string(), "2222".to_string()));
+        }
+        let cache = SidebarCache::build(&config, &saved_lines, key.clone());
+        assert_eq!(cache.changed_hosts, HashSet::from([config.find_host_index("web").unwrap()]));
+        assert_eq!(cache.changed_files, HashSet::from([path.clone()]));
+
+        let (report, _) = write_document(&mut config, &path, &mut saved_lines, false);
+        assert!(report.skipped.is_empty());
+        let cache = SidebarCache::build(&config, &saved_lines, key);
+        assert!(cache.changed_hosts.is_empty());
+        assert!(cache.changed_files.is_empty());
     }
 }
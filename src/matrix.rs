@@ -0,0 +1,110 @@
+use crate::SshConfig;
+
+// Table of hosts against chosen keywords, for checking which hosts set what.
+// The first row is the header ("Host" and the keywords); every following row
+// is one host's pattern and its values, blank where it doesn't set the key.
+// Several values of a repeated keyword are joined with "; ".
+pub fn option_matrix(config: &SshConfig, keys: &[String]) -> Vec<Vec<String>> {
+    let mut rows = vec![std::iter::once("Host".to_string()).chain(keys.iter().cloned()).collect()];
+    for host in config.hosts() {
+        let mut row = vec![host.pattern.to_string()];
+        for key in keys {
+            let values: Vec<&str> = host
+                .options
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.as_str())
+                .collect();
+            row.push(values.join("; "));
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+// Keywords set by any host, in first-seen spelling, sorted case-insensitively
+pub fn keys_in_use(config: &SshConfig) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for host in config.hosts() {
+        for (key, _) in host.options {
+            if !keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+                keys.push(key.clone());
+            }
+        }
+    }
+    keys.sort_by_key(|key| key.to_lowercase());
+    keys
+}
+
+// RFC 4180 CSV: fields with commas, quotes or line breaks are quoted
+pub fn to_csv(rows: &[Vec<String>]) -> String {
+    let mut csv = String::new();
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|field| {
+                if field.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.clone()
+                }
+            })
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+// Markdown table with the first row as header
+pub fn to_markdown(rows: &[Vec<String>]) -> String {
+    let Some((header, body)) = rows.split_first() else {
+        return String::new();
+    };
+    let format_row = |row: &Vec<String>| {
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    let mut markdown = format_row(header);
+    markdown.push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+    for row in body {
+        markdown.push_str(&format_row(row));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SshConfig {
+        SshConfig::parse_str(
+            "Host web\n    User deploy\n    LocalForward 8080 localhost:80\n    LocalForward 9090 localhost:90\n\n\
+             Host db\n    user \"ops, admin\"\n    Port 5432\n",
+            "config",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn matrix_has_a_row_per_host_and_a_column_per_key() {
+        let config = config();
+        let keys = ["User".to_string(), "LocalForward".to_string(), "Port".to_string()];
+        assert_eq!(
+            option_matrix(&config, &keys),
+            [
+                vec!["Host", "User", "LocalForward", "Port"],
+                vec!["web", "deploy", "8080 localhost:80; 9090 localhost:90", ""],
+                vec!["db", "\"ops, admin\"", "", "5432"],
+            ]
+        );
+        assert_eq!(keys_in_use(&config), ["LocalForward", "Port", "User"]);
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let config = config();
+        let rows = option_matrix(&config, &["User".to_string(), "Port".to_string()]);
+        assert_eq!(to_csv(&rows), "Host,User,Port\r\nweb,deploy,\r\ndb,\"\"\"ops, admin\"\"\",5432\r\n");
+    }
+}
@@ -0,0 +1,126 @@
+use eframe::egui;
+use egui_ssh_config::{ConfigLine, SshConfig};
+
+use crate::sanitize_field;
+use crate::status::StatusLog;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Host,
+    Key,
+    Value,
+}
+
+// Spreadsheet-like view listing every (host, key, value) of the config, with
+// values editable in place
+pub struct OptionsTable {
+    sort_column: Column,
+    ascending: bool,
+    key_filter: String,
+}
+
+impl Default for OptionsTable {
+    fn default() -> Self {
+        Self {
+            sort_column: Column::Host,
+            ascending: true,
+            key_filter: String::new(),
+        }
+    }
+}
+
+impl OptionsTable {
+    // Returns the host index to select if a host name was clicked, and whether
+    // any value was edited
+    pub fn show(&mut self, ui: &mut egui::Ui, config: &mut SshConfig, status: &mut StatusLog) -> (Option<usize>, bool) {
+        let mut select = None;
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            ui.label("Filter by key:");
+            ui.add(egui::TextEdit::singleline(&mut self.key_filter).hint_text("e.g. Port"));
+            if !self.key_filter.is_empty() && ui.button("✖").clicked() {
+                self.key_filter.clear();
+            }
+        });
+        ui.separator();
+
+        // (host index, option index) of every row to show
+        let filter = self.key_filter.to_lowercase();
+        let mut rows: Vec<(usize, usize)> = Vec::new();
+        for (host_idx, line) in config.lines.iter().enumerate() {
+            if let ConfigLine::HostEntry { options, .. } = line {
+                for (option_idx, (key, _)) in options.iter().enumerate() {
+                    if key.to_lowercase().contains(&filter) {
+                        rows.push((host_idx, option_idx));
+                    }
+                }
+            }
+        }
+
+        let cell = |(host_idx, option_idx): (usize, usize), column: Column| -> String {
+            match &config.lines[host_idx] {
+                ConfigLine::HostEntry { pattern, options, .. } => match column {
+                    Column::Host => pattern.to_lowercase(),
+                    Column::Key => options[option_idx].0.to_lowercase(),
+                    Column::Value => options[option_idx].1.to_lowercase(),
+                },
+                _ => String::new(),
+            }
+        };
+        // Stable sort, so rows with equal cells keep config order
+        rows.sort_by_cached_key(|&row| cell(row, self.sort_column));
+        if !self.ascending {
+            rows.reverse();
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("options_table")
+                .num_columns(3)
+                .striped(true)
+                .spacing([20.0, 4.0])
+                .show(ui, |ui| {
+                    for (column, title) in [(Column::Host, "Host"), (Column::Key, "Key"), (Column::Value, "Value")] {
+                        let arrow = match (self.sort_column == column, self.ascending) {
+                            (true, true) => " ⏶",
+                            (true, false) => " ⏷",
+                            (false, _) => "",
+                        };
+                        let header = egui::RichText::new(format!("{}{}", title, arrow)).strong();
+                        if ui.add(egui::Button::new(header).frame(false)).clicked() {
+                            if self.sort_column == column {
+                                self.ascending = !self.ascending;
+                            } else {
+                                self.sort_column = column;
+                                self.ascending = true;
+                            }
+                        }
+                    }
+                    ui.end_row();
+
+                    for (host_idx, option_idx) in rows {
+                        let Some(ConfigLine::HostEntry { pattern, options, .. }) = config.lines.get_mut(host_idx) else {
+                            continue;
+                        };
+                        if ui.link(pattern.as_str()).clicked() {
+                            select = Some(host_idx);
+                        }
+                        let (key, value) = &mut options[option_idx];
+                        ui.label(key.as_str());
+                        let response = ui.add(
+                            egui::TextEdit::singleline(value)
+                                .id(egui::Id::new(("options_table_value", host_idx, option_idx)))
+                                .desired_width(300.0),
+                        );
+                        if response.changed() {
+                            sanitize_field(value, status);
+                            changed = true;
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+        (select, changed)
+    }
+}
@@ -0,0 +1,192 @@
+use crate::keywords;
+
+// Host pattern matching following ssh_config(5): a pattern list is split on
+// whitespace and commas, `*` and `?` are wildcards and a leading `!` negates
+// a pattern. A negated match always wins over positive matches. Like in ssh,
+// matching ignores case; patterns keep their casing for display and saving.
+pub fn matches_pattern_list(pattern_list: &str, host: &str) -> bool {
+    let mut matched = false;
+    let host = host.to_lowercase();
+    let host = host.as_str();
+
+    for pattern in split_patterns(pattern_list) {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, host) {
+                return false;
+            }
+        } else if wildcard_match(pattern, host) {
+            matched = true;
+        }
+    }
+
+    matched
+}
+
+// Whether the criteria of a `Match` line hold for `host`, as far as the
+// editor can tell: `all`, and `host`/`originalhost` pattern lists, each
+// possibly negated with `!`. ssh tests `host` after applying HostName; here
+// both are tested against the alias. Any other criterion (`exec`, `user`,
+// ...) can't be evaluated, so the block is taken not to apply.
+pub fn match_criteria_hold(criteria: &str, host: &str) -> bool {
+    let mut words = criteria.split_whitespace();
+    let mut any = false;
+    while let Some(word) = words.next() {
+        let (negated, name) = match word.strip_prefix('!') {
+            Some(name) => (true, name),
+            None => (false, word),
+        };
+        let holds = match name.to_lowercase().as_str() {
+            "all" => true,
+            "host" | "originalhost" => match words.next() {
+                Some(list) => matches_pattern_list(list.trim_matches('"'), host),
+                None => return false,
+            },
+            _ => return false,
+        };
+        if holds == negated {
+            return false;
+        }
+        any = true;
+    }
+    any
+}
+
+// True when `alias` appears literally (no wildcards involved) in the list
+pub fn defines_alias(pattern_list: &str, alias: &str) -> bool {
+    split_patterns(pattern_list).any(|pattern| pattern.eq_ignore_ascii_case(alias))
+}
+
+// True when two Host lines name the same hosts, ignoring case and how the
+// patterns are separated (`Web db` and `web,db` are the same)
+pub fn same_pattern_list(a: &str, b: &str) -> bool {
+    let mut a = split_patterns(a);
+    let mut b = split_patterns(b);
+    loop {
+        match (a.next(), b.next()) {
+            (Some(x), Some(y)) if x.to_lowercase() == y.to_lowercase() => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+// Checks a Host pattern list typed by the user. Err if ssh can't use it at
+// all, otherwise the likely mistakes it would still accept.
+pub fn check_pattern_list(pattern_list: &str) -> Result<Vec<String>, String> {
+    let trimmed = pattern_list.trim();
+    if trimmed.is_empty() {
+        return Err("Pattern is empty".to_string());
+    }
+    if split_patterns(trimmed).any(|p| p == "!") {
+        return Err("'!' must be followed by a pattern".to_string());
+    }
+
+    let mut warnings = Vec::new();
+    if trimmed.starts_with(',') || trimmed.ends_with(',') {
+        warnings.push("Leading or trailing comma".to_string());
+    }
+    if trimmed.trim_matches(',').split(',').any(|part| part.trim().is_empty()) {
+        warnings.push("Empty entry between commas".to_string());
+    }
+    if split_patterns(trimmed).all(|p| p.starts_with('!')) {
+        warnings.push("Only negated patterns, so this never matches any host".to_string());
+    }
+    Ok(warnings)
+}
+
+pub fn split_patterns(pattern_list: &str) -> impl Iterator<Item = &str> {
+    pattern_list
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|p| !p.is_empty())
+}
+
+// `text` must already be lowercase
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Extracts the host names from a ProxyJump value such as
+// `user@bastion:2222,ssh://jump2`. `ProxyJump none` names no hosts.
+pub fn proxy_jump_hosts(value: &str) -> Vec<&str> {
+    if keywords::is_none_value(value) {
+        return Vec::new();
+    }
+    value
+        .split(',')
+        .map(|hop| {
+            let hop = hop.trim();
+            let hop = hop.strip_prefix("ssh://").unwrap_or(hop);
+            let hop = hop.rsplit_once('@').map_or(hop, |(_, host)| host);
+            hop.split(':').next().unwrap_or(hop)
+        })
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_compare_case_insensitively() {
+        assert!(same_pattern_list("Web", "web"));
+        assert!(same_pattern_list("Web db", "web,DB"));
+        assert!(!same_pattern_list("web", "web db"));
+        assert!(matches_pattern_list("Web-*", "WEB-1"));
+        assert!(defines_alias("Web db", "web"));
+    }
+
+    #[test]
+    fn proxy_jump_none_names_no_hosts() {
+        assert_eq!(proxy_jump_hosts("none"), Vec::<&str>::new());
+        assert_eq!(proxy_jump_hosts("\"NONE\""), Vec::<&str>::new());
+        assert_eq!(proxy_jump_hosts("me@bastion:2222,ssh://jump"), ["bastion", "jump"]);
+    }
+
+    #[test]
+    fn pattern_lists_are_checked_before_creating_a_host() {
+        assert_eq!(check_pattern_list("web db.example.com"), Ok(Vec::new()));
+        assert_eq!(check_pattern_list("  \t"), Err("Pattern is empty".to_string()));
+        assert_eq!(check_pattern_list("web ! db"), Err("'!' must be followed by a pattern".to_string()));
+        assert_eq!(check_pattern_list(",web"), Ok(vec!["Leading or trailing comma".to_string()]));
+        assert_eq!(check_pattern_list("web,,db"), Ok(vec!["Empty entry between commas".to_string()]));
+        assert_eq!(
+            check_pattern_list("!web"),
+            Ok(vec!["Only negated patterns, so this never matches any host".to_string()])
+        );
+    }
+
+    #[test]
+    fn match_criteria_hold_only_where_the_editor_can_tell() {
+        assert!(match_criteria_hold("all", "web"));
+        assert!(match_criteria_hold("host web*,!web-test", "web-1"));
+        assert!(!match_criteria_hold("host web*,!web-test", "web-test"));
+        assert!(match_criteria_hold("originalhost \"db,web\" !host db", "web"));
+        assert!(!match_criteria_hold("!host web", "web"));
+        assert!(!match_criteria_hold("host web exec \"true\"", "web"));
+        assert!(!match_criteria_hold("host", "web"));
+        assert!(!match_criteria_hold("", "web"));
+    }
+}
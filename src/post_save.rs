@@ -0,0 +1,73 @@
+use eframe::egui;
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc;
+
+// Placeholder replaced with the path of the saved main config file
+pub const FILE_PLACEHOLDER: &str = "{file}";
+
+// The command line to run, with every `{file}` replaced by `path` quoted
+// for the shell it runs in
+pub fn expand_command(template: &str, path: &Path) -> String {
+    template.replace(FILE_PLACEHOLDER, &shell_quote(&path.display().to_string()))
+}
+
+#[cfg(not(windows))]
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(windows)]
+pub fn shell_quote(text: &str) -> String {
+    format!("\"{}\"", text)
+}
+
+// Runs `command` through the system shell on a worker thread, so a slow
+// hook can't block the UI. The receiver gets a status message once it exits.
+pub fn spawn(ctx: &egui::Context, command: String) -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let output = if cfg!(windows) {
+            Command::new("cmd").arg("/C").arg(&command).output()
+        } else {
+            Command::new("sh").arg("-c").arg(&command).output()
+        };
+        let message = match output {
+            Ok(output) if output.status.success() => "Post-save command finished".to_string(),
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                match stderr.lines().next() {
+                    Some(line) => format!("Post-save command failed ({}): {}", output.status, line),
+                    None => format!("Post-save command failed ({})", output.status),
+                }
+            }
+            Err(e) => format!("Could not run post-save command: {}", e),
+        };
+        let _ = sender.send(message);
+        ctx.request_repaint();
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_file_placeholder_is_replaced_by_the_quoted_path() {
+        let path = Path::new("/home/me/.ssh/config");
+        assert_eq!(expand_command("ssh -O exit web", path), "ssh -O exit web");
+        assert_eq!(
+            expand_command("cp {file} {file}.bak", path),
+            format!("cp {0} {0}.bak", shell_quote("/home/me/.ssh/config"))
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn paths_are_quoted_for_sh() {
+        let path = Path::new("/home/me/my ssh/it's config");
+        assert_eq!(expand_command("git add {file}", path), "git add '/home/me/my ssh/it'\\''s config'");
+    }
+}
@@ -0,0 +1,200 @@
+use std::ops::Range;
+
+// Returns the byte ranges of every non-overlapping, case-insensitive
+// occurrence of `query_lower` (already lowercased) in `text`.
+pub fn find_matches(text: &str, query_lower: &str) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    if query_lower.is_empty() {
+        return matches;
+    }
+
+    let mut next_start = 0;
+    for (start, _) in text.char_indices() {
+        if start < next_start {
+            continue;
+        }
+        if let Some(len) = match_len_at(&text[start..], query_lower) {
+            matches.push(start..start + len);
+            next_start = start + len;
+        }
+    }
+
+    matches
+}
+
+// Whether a host shows up in a plain (non-fuzzy) search for `query_lower`:
+// None if neither its pattern nor, with `search_options`, any of its options
+// contain the query. Otherwise the `key value` text of the matching options.
+pub fn host_search_matches(
+    pattern: &str,
+    options: &[(String, String)],
+    query_lower: &str,
+    search_options: bool,
+) -> Option<Vec<String>> {
+    let matching_options: Vec<String> = options
+        .iter()
+        .filter(|_| search_options)
+        .map(|(key, value)| format!("{} {}", key, value))
+        .filter(|text| text.to_lowercase().contains(query_lower))
+        .collect();
+    if pattern.to_lowercase().contains(query_lower) || !matching_options.is_empty() {
+        Some(matching_options)
+    } else {
+        None
+    }
+}
+
+// Length in bytes of the prefix of `text` that lowercases to `query_lower`
+fn match_len_at(text: &str, query_lower: &str) -> Option<usize> {
+    let mut query = query_lower.chars().peekable();
+    let mut len = 0;
+
+    for c in text.chars() {
+        if query.peek().is_none() {
+            break;
+        }
+        for lower in c.to_lowercase() {
+            if query.next() != Some(lower) {
+                return None;
+            }
+        }
+        len += c.len_utf8();
+    }
+
+    if query.peek().is_none() { Some(len) } else { None }
+}
+
+// Scores how well `query` fuzzily matches `text`, higher is better. Matches
+// the query as an in-order subsequence (rewarding consecutive characters and
+// word starts), and falls back to character-bigram overlap so reordered names
+// (`prodweb` vs `web-prod`) still match. Returns None for no match.
+pub fn fuzzy_score(text: &str, query: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let query: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let subsequence = subsequence_score(&text, &query);
+    let similarity = bigram_similarity(&text, &query);
+    let overlap = if similarity >= 0.5 {
+        Some((similarity * 100.0) as i32)
+    } else {
+        None
+    };
+
+    match (subsequence, overlap) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn subsequence_score(text: &[char], query: &[char]) -> Option<i32> {
+    let mut score = 100;
+    let mut query_idx = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (idx, &c) in text.iter().enumerate() {
+        if query_idx == query.len() {
+            break;
+        }
+        if c != query[query_idx] {
+            continue;
+        }
+
+        let at_word_start = idx == 0 || !text[idx - 1].is_alphanumeric();
+        if at_word_start {
+            score += 10;
+        }
+        match last_match {
+            Some(last) if last + 1 == idx => score += 15,
+            Some(last) => score -= ((idx - last - 1) as i32).min(10),
+            None => score -= (idx as i32).min(10),
+        }
+
+        last_match = Some(idx);
+        query_idx += 1;
+    }
+
+    if query_idx == query.len() { Some(score) } else { None }
+}
+
+// Dice coefficient over the bigrams of the alphanumeric characters
+fn bigram_similarity(text: &[char], query: &[char]) -> f32 {
+    let bigrams = |chars: &[char]| -> Vec<(char, char)> {
+        let alnum: Vec<char> = chars.iter().copied().filter(|c| c.is_alphanumeric()).collect();
+        alnum.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+
+    let text_bigrams = bigrams(text);
+    let mut query_bigrams = bigrams(query);
+    if text_bigrams.is_empty() || query_bigrams.is_empty() {
+        return 0.0;
+    }
+
+    let total = text_bigrams.len() + query_bigrams.len();
+    let mut shared = 0;
+    for bigram in &text_bigrams {
+        if let Some(pos) = query_bigrams.iter().position(|b| b == bigram) {
+            query_bigrams.swap_remove(pos);
+            shared += 1;
+        }
+    }
+
+    (2 * shared) as f32 / total as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SshConfig;
+
+    // ~5000 hosts, as in a large generated inventory
+    fn large_config() -> SshConfig {
+        let mut text = String::new();
+        for n in 0..5000 {
+            text.push_str(&format!("Host web-{n}\n    HostName 10.0.{}.{}\n    Port {}\n\n", n / 256, n % 256, 2000 + n));
+        }
+        SshConfig::parse_str(&text, "config").unwrap()
+    }
+
+    #[test]
+    fn large_config_parses_and_filters() {
+        let config = large_config();
+        assert_eq!(config.hosts().count(), 5000);
+
+        let search = |query: &str, search_options| {
+            config
+                .hosts()
+                .filter(|host| host_search_matches(host.pattern, host.options, query, search_options).is_some())
+                .count()
+        };
+        // web-42, web-420..web-429 and web-4200..web-4299
+        assert_eq!(search("web-42", false), 111);
+        assert_eq!(search("port 4999", false), 0);
+        assert_eq!(search("port 4999", true), 1);
+    }
+
+    #[test]
+    fn host_search_lists_matching_options() {
+        let options = vec![("HostName".to_string(), "db.example.com".to_string()), ("Port".to_string(), "22".to_string())];
+        assert_eq!(host_search_matches("db", &options, "example", false), None);
+        assert_eq!(host_search_matches("db", &options, "example", true), Some(vec!["HostName db.example.com".to_string()]));
+        assert_eq!(host_search_matches("db", &options, "db", false), Some(Vec::new()));
+    }
+
+    #[test]
+    fn fuzzy_score_ranks_closer_matches_first() {
+        let score = |text| fuzzy_score(text, "web").unwrap();
+        // Consecutive characters beat scattered ones, early matches late ones
+        assert!(score("webserver") > score("w-e-b"));
+        assert!(score("webserver") > score("my-web"));
+        assert!(fuzzy_score("web-prod", "wp") > fuzzy_score("swamp", "wp"));
+
+        // Reordered names still match by their shared bigrams
+        assert!(fuzzy_score("web-prod", "prodweb").is_some());
+        assert_eq!(fuzzy_score("production", "prodweb"), None);
+        assert_eq!(fuzzy_score("db", "web"), None);
+        assert_eq!(fuzzy_score("anything", " "), Some(0));
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use eframe::egui;
+use egui_ssh_config::filters::QuickFilter;
+use egui_ssh_config::ssh_config::ParseOptions;
+use serde::{Deserialize, Serialize};
+
+// Key of the JSON settings blob in eframe's app storage
+pub const SETTINGS_KEY: &str = "settings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn preference(self) -> egui::ThemePreference {
+        match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Light => egui::ThemePreference::Light,
+            Theme::Dark => egui::ThemePreference::Dark,
+        }
+    }
+}
+
+// Display order of the hosts list. Only the list is sorted, the lines are
+// saved in their original order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HostSort {
+    FileOrder,
+    Pattern,
+    FileThenPattern,
+}
+
+impl HostSort {
+    pub const ALL: [HostSort; 3] = [HostSort::FileOrder, HostSort::Pattern, HostSort::FileThenPattern];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            HostSort::FileOrder => "File order",
+            HostSort::Pattern => "Pattern A–Z",
+            HostSort::FileThenPattern => "File, then pattern",
+        }
+    }
+}
+
+// Everything the app remembers between runs. Fields missing from a stored
+// blob (e.g. written by an older version) fall back to their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub theme: Theme,
+    pub always_on_top: bool,
+    // How often to repaint while focused but idle
+    pub idle_repaint_ms: u64,
+    pub search_options: bool,
+    pub search_fuzzy: bool,
+    // Hosts list filters and order; the search text itself isn't kept
+    pub quick_filters: Vec<QuickFilter>,
+    pub host_sort: HostSort,
+    // Files whose hosts are folded away under their header in the hosts list
+    pub collapsed_files: Vec<PathBuf>,
+    pub sidebar_width: f32,
+    // Insert options added from the form before the existing ones
+    pub new_options_at_top: bool,
+    // Accept `;` comment lines when loading, see `ParseOptions`
+    pub semicolon_comments: bool,
+    // Also read the machine-wide config, read-only, for resolving and
+    // auditing what ssh will actually use
+    pub system_overlay: bool,
+    // Re-indent options with `indent_width` spaces instead of keeping each
+    // file's own indentation
+    pub reindent: bool,
+    pub indent_width: usize,
+    // Shell command run after every successful save, empty for none
+    pub post_save_command: String,
+    // Editor command for "Open in Editor", empty to use $VISUAL/$EDITOR
+    pub external_editor: String,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::System,
+            always_on_top: false,
+            idle_repaint_ms: 500,
+            search_options: false,
+            search_fuzzy: false,
+            quick_filters: Vec::new(),
+            host_sort: HostSort::FileOrder,
+            collapsed_files: Vec::new(),
+            sidebar_width: 250.0,
+            new_options_at_top: false,
+            semicolon_comments: false,
+            system_overlay: false,
+            reindent: false,
+            indent_width: 4,
+            post_save_command: String::new(),
+            external_editor: String::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    // Stored settings, or the defaults if there are none or they can't be read
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| storage.get_string(SETTINGS_KEY))
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            semicolon_comments: self.semicolon_comments,
+        }
+    }
+
+    pub fn store(&self, storage: &mut dyn eframe::Storage) {
+        if let Ok(json) = serde_json::to_string(self) {
+            storage.set_string(SETTINGS_KEY, json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_defaults() {
+        let settings: AppSettings = serde_json::from_str(r#"{"theme": "Dark", "indent_width": 2}"#).unwrap();
+        assert_eq!(
+            settings,
+            AppSettings {
+                theme: Theme::Dark,
+                indent_width: 2,
+                ..AppSettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn settings_round_trip_through_json() {
+        let settings = AppSettings {
+            host_sort: HostSort::Pattern,
+            collapsed_files: vec![PathBuf::from("/home/me/.ssh/conf.d/work")],
+            post_save_command: "make -C ~/.ssh".to_string(),
+            ..AppSettings::default()
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(serde_json::from_str::<AppSettings>(&json).unwrap(), settings);
+    }
+
+    #[test]
+    fn search_settings_round_trip_through_json() {
+        let settings = AppSettings {
+            search_fuzzy: true,
+            search_options: true,
+            quick_filters: vec![QuickFilter::HasProxyJump, QuickFilter::NoHostName],
+            ..AppSettings::default()
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        let restored: AppSettings = serde_json::from_str(&json).unwrap();
+        assert!(restored.search_fuzzy);
+        assert!(restored.search_options);
+        assert_eq!(restored.quick_filters, [QuickFilter::HasProxyJump, QuickFilter::NoHostName]);
+    }
+}
@@ -0,0 +1,142 @@
+use eframe::egui::{self, Key, Modifiers};
+
+// What a keyboard shortcut does. `update` checks these through `pressed`,
+// and the shortcuts popup lists them, so both always agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Open,
+    OpenInTab,
+    NextTab,
+    PreviousTab,
+    NewHost,
+    PasteHost,
+    Save,
+    SaveAll,
+    Quit,
+    FocusSearch,
+    Escape,
+    CloseDialog,
+    AlwaysOnTop,
+    AddLegacy,
+    RemoveOption,
+    UndoRemove,
+}
+
+pub struct Shortcut {
+    pub command: Command,
+    pub category: &'static str,
+    pub modifiers: Modifiers,
+    pub key: Key,
+    pub description: &'static str,
+}
+
+const fn shortcut(command: Command, category: &'static str, modifiers: Modifiers, key: Key, description: &'static str) -> Shortcut {
+    Shortcut { command, category, modifiers, key, description }
+}
+
+const FILE: &str = "File Operations";
+const NAVIGATION: &str = "Search & Navigation";
+const VIEW: &str = "View";
+const ACTIONS: &str = "Quick Actions";
+const CTRL_SHIFT: Modifiers = Modifiers { ctrl: true, shift: true, ..Modifiers::NONE };
+
+// Every shortcut, in the order the popup lists them. A command may have
+// several keys (e.g. Delete and Backspace).
+pub const SHORTCUTS: &[Shortcut] = &[
+    shortcut(Command::Open, FILE, Modifiers::CTRL, Key::O, "Open SSH config file"),
+    shortcut(Command::OpenInTab, FILE, Modifiers::CTRL, Key::T, "Open SSH config file in a new tab"),
+    shortcut(Command::NewHost, FILE, Modifiers::CTRL, Key::N, "New host entry"),
+    // Arrives as a paste event rather than a key press
+    shortcut(Command::PasteHost, FILE, Modifiers::CTRL, Key::V, "New host from a copied ssh:// URL"),
+    shortcut(Command::Save, FILE, Modifiers::CTRL, Key::S, "Save all changes"),
+    shortcut(Command::SaveAll, FILE, CTRL_SHIFT, Key::S, "Save every open tab"),
+    shortcut(Command::Quit, FILE, Modifiers::CTRL, Key::Q, "Quit (prompts to save if dirty)"),
+    shortcut(Command::FocusSearch, NAVIGATION, Modifiers::CTRL, Key::F, "Focus search box"),
+    shortcut(Command::Escape, NAVIGATION, Modifiers::NONE, Key::Escape, "Unfocus field / close dialog / clear search"),
+    shortcut(Command::CloseDialog, NAVIGATION, Modifiers::CTRL, Key::W, "Close dialog, or the tab if none is open"),
+    shortcut(Command::NextTab, NAVIGATION, Modifiers::CTRL, Key::Tab, "Next tab"),
+    shortcut(Command::PreviousTab, NAVIGATION, CTRL_SHIFT, Key::Tab, "Previous tab"),
+    shortcut(Command::AlwaysOnTop, VIEW, Modifiers::CTRL, Key::A, "Toggle always on top"),
+    shortcut(Command::AddLegacy, ACTIONS, CTRL_SHIFT, Key::L, "Add legacy SSH options to the selected host"),
+    shortcut(Command::RemoveOption, ACTIONS, Modifiers::NONE, Key::Delete, "Remove the focused option row"),
+    shortcut(Command::RemoveOption, ACTIONS, Modifiers::NONE, Key::Backspace, "Remove the focused option row"),
+    shortcut(Command::UndoRemove, ACTIONS, Modifiers::CTRL, Key::Z, "Undo the last option removal"),
+];
+
+impl Shortcut {
+    // e.g. "Ctrl+Shift+L"
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.modifiers.ctrl {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.alt {
+            label.push_str("Alt+");
+        }
+        if self.modifiers.shift {
+            label.push_str("Shift+");
+        }
+        label.push_str(self.key.name());
+        label
+    }
+
+    // Modifiers must match exactly, so Ctrl+Tab and Ctrl+Shift+Tab differ
+    fn pressed(&self, input: &egui::InputState) -> bool {
+        input.modifiers.ctrl == self.modifiers.ctrl
+            && input.modifiers.shift == self.modifiers.shift
+            && input.modifiers.alt == self.modifiers.alt
+            && input.key_pressed(self.key)
+    }
+
+    // Like `pressed`, but ignoring the repeats of a held key
+    fn pressed_once(&self, input: &egui::InputState) -> bool {
+        self.pressed(input)
+            && input.events.iter().any(|event| {
+                matches!(event, egui::Event::Key { key, pressed: true, repeat: false, .. } if *key == self.key)
+            })
+    }
+}
+
+// Whether any key bound to `command` was pressed this frame
+pub fn pressed(input: &egui::InputState, command: Command) -> bool {
+    SHORTCUTS.iter().any(|shortcut| shortcut.command == command && shortcut.pressed(input))
+}
+
+// For commands that shouldn't run again while their keys are held down
+pub fn pressed_once(input: &egui::InputState, command: Command) -> bool {
+    SHORTCUTS.iter().any(|shortcut| shortcut.command == command && shortcut.pressed_once(input))
+}
+
+// Shortcuts grouped by category, categories in first-listed order
+pub fn by_category() -> Vec<(&'static str, Vec<&'static Shortcut>)> {
+    let mut groups: Vec<(&'static str, Vec<&'static Shortcut>)> = Vec::new();
+    for shortcut in SHORTCUTS {
+        match groups.iter_mut().find(|(category, _)| *category == shortcut.category) {
+            Some((_, shortcuts)) => shortcuts.push(shortcut),
+            None => groups.push((shortcut.category, vec![shortcut])),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_shortcut_has_a_unique_combo() {
+        for (idx, shortcut) in SHORTCUTS.iter().enumerate() {
+            let clash = SHORTCUTS[..idx]
+                .iter()
+                .find(|other| other.key == shortcut.key && other.modifiers == shortcut.modifiers);
+            assert!(clash.is_none(), "{} is bound twice", shortcut.label());
+        }
+    }
+
+    #[test]
+    fn every_shortcut_is_listed_in_a_category() {
+        let listed: usize = by_category().iter().map(|(_, shortcuts)| shortcuts.len()).sum();
+        assert_eq!(listed, SHORTCUTS.len());
+        assert_eq!(SHORTCUTS.iter().find(|s| s.command == Command::AddLegacy).unwrap().label(), "Ctrl+Shift+L");
+    }
+}
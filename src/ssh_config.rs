@@ -2,7 +2,14 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+use crate::{keywords, lint, pattern, tags};
+
+// Serialized as an object tagged with its variant name, e.g.
+// `{"type": "HostEntry", "pattern": "web", "options": [["Port", "22"]], ...}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum ConfigLine {
     Comment {
         text: String,
@@ -12,12 +19,39 @@ pub enum ConfigLine {
         source_file: PathBuf,
     },
     Include {
+        // Exactly as written (relative, `~/`, glob, quoted) and written back
+        // that way; the files it resolves to are the keys of `included_files`
         path: String,
+        comment: Option<String>,
+        // Files the path matched when it was parsed, empty for a dead include
+        #[serde(default)]
+        resolved: Vec<PathBuf>,
         source_file: PathBuf,
     },
     HostEntry {
         pattern: String,
+        comment: Option<String>,
+        // From a `# @tags:` comment right above the Host line
+        #[serde(default)]
+        tags: Vec<String>,
+        options: Vec<(String, String)>,
+        // Comment and blank lines directly above each option, verbatim and
+        // each ending in a newline. Lined up with `options`; a missing or
+        // empty entry means the option has none.
+        #[serde(default)]
+        option_comments: Vec<String>,
+        source_file: PathBuf,
+    },
+    // A `Match` block. Its options only apply where the criteria hold, which
+    // the editor can tell for `all`, `host` and `originalhost` alone.
+    MatchEntry {
+        criteria: String,
+        comment: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
         options: Vec<(String, String)>,
+        #[serde(default)]
+        option_comments: Vec<String>,
         source_file: PathBuf,
     },
     GlobalOption {
@@ -27,54 +61,360 @@ pub enum ConfigLine {
     },
 }
 
+impl ConfigLine {
+    pub fn source_file(&self) -> &Path {
+        match self {
+            ConfigLine::Comment { source_file, .. } => source_file,
+            ConfigLine::Empty { source_file } => source_file,
+            ConfigLine::Include { source_file, .. } => source_file,
+            ConfigLine::HostEntry { source_file, .. } => source_file,
+            ConfigLine::MatchEntry { source_file, .. } => source_file,
+            ConfigLine::GlobalOption { source_file, .. } => source_file,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+// What to do when a merged host's pattern already exists in this config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub skipped: usize,
+    pub overwritten: usize,
+    pub renamed: usize,
+}
+
+// What `apply_json` does with the options of a host that already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertPolicy {
+    // Set the given options and keep the host's other options
+    Update,
+    // Make the given options the host's only options
+    Replace,
+    // Leave existing hosts as they are
+    KeepExisting,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpsertReport {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+// One host of an `apply_json` document, validated
+struct JsonHost {
+    pattern: String,
+    file: Option<PathBuf>,
+    options: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateMergeReport {
+    // Host blocks folded into an earlier block with the same pattern
+    pub merged: usize,
+    // Options dropped because the earlier block already sets the key
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveReport {
+    pub written: Vec<PathBuf>,
+    // Files that couldn't be written, with the reason; their changes are
+    // still unsaved
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+// A host entry line borrowed from `SshConfig::lines`, see `SshConfig::hosts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostRef<'a> {
+    // Index of the line in `lines`
+    pub index: usize,
+    pub pattern: &'a str,
+    pub comment: Option<&'a str>,
+    pub tags: &'a [String],
+    pub options: &'a [(String, String)],
+    pub source_file: &'a Path,
+}
+
+// Identifies a host entry independently of its index in `lines`, so it can
+// be found again after lines are inserted, removed, reordered or reparsed.
+// `occurrence` tells apart hosts with the same pattern in the same file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostHandle {
+    pub source_file: PathBuf,
+    pub pattern: String,
+    pub occurrence: usize,
+}
+
+// Something the parser had to work around, reported to the user instead of
+// failing the whole load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub file: PathBuf,
+    pub message: String,
+}
+
+// Parser behaviour beyond what OpenSSH itself accepts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    // Treat lines starting with `;` as comments, as some generated configs
+    // use them. ssh rejects such lines, so this is off by default.
+    pub semicolon_comments: bool,
+}
+
+// Whitespace layout used when writing a file back out. Detected from the
+// first indented option and the first keyword/value separator in each file,
+// so tab-indented or tab-separated configs keep their style on save.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineFormat {
+    pub indent: String,
+    pub separator: String,
+}
+
+impl Default for LineFormat {
+    fn default() -> Self {
+        Self {
+            indent: "    ".to_string(),
+            separator: " ".to_string(),
+        }
+    }
+}
+
+// Host or Match block being collected while parsing, flushed into `lines`
+// when the block ends
+struct PendingHost {
+    is_match: bool,
+    // Host patterns, or the criteria of a Match block
+    pattern: String,
+    comment: Option<String>,
+    tags: Vec<String>,
+    options: Vec<(String, String)>,
+    option_comments: Vec<String>,
+    line: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct SshConfig {
+    // Lines of every managed file. Lines of the same file are always kept in
+    // file order; lines of different files may interleave (includes are
+    // parsed inline), which `normalize_line_order` can tidy up. Edits go
+    // here; the per-file lines (`file_lines`) are only the state each file
+    // was last loaded or saved in, kept to tell which files changed.
     pub lines: Vec<ConfigLine>,
     pub included_files: HashMap<PathBuf, IncludedFileData>,
+    pub formats: HashMap<PathBuf, LineFormat>,
+    // Indent options by this many spaces (1-8) in every file instead of
+    // keeping the indentation detected in each
+    pub indent_override: Option<usize>,
+    pub warnings: Vec<ParseWarning>,
+    // Files that contained invalid UTF-8; saving them writes U+FFFD in place
+    // of the original bytes
+    pub lossy_files: HashSet<PathBuf>,
+    options: ParseOptions,
+    // Text of the main file as it was read; included files keep theirs in
+    // `included_files`
+    main_content: String,
+    // Lines of the main file as they were last loaded or saved
+    main_lines: Vec<ConfigLine>,
+    // 1-based line number of each parsed Host line within its file
+    host_lines: Vec<(HostHandle, usize)>,
     visited_files: HashSet<PathBuf>,
+    // Files in the order they were first parsed (main file first)
+    file_order: Vec<PathBuf>,
+    // Home directory `~` expands to, None if it couldn't be determined
+    home: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
 pub struct IncludedFileData {
-    #[allow(dead_code)]
     pub content: String,
-    #[allow(dead_code)]
+    // The file's own lines as they were last loaded or saved. Not updated
+    // by edits, which go to `SshConfig::lines`; compare with `file_modified`.
     pub lines: Vec<ConfigLine>,
 }
 
+impl Default for SshConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SshConfig {
     pub fn new() -> Self {
+        Self::with_home(dirs::home_dir())
+    }
+
+    // An empty config that expands `~` to `home`, or flags `~` paths if None
+    pub fn with_home(home: Option<PathBuf>) -> Self {
         Self {
             lines: Vec::new(),
             included_files: HashMap::new(),
+            formats: HashMap::new(),
+            indent_override: None,
+            warnings: Vec::new(),
+            lossy_files: HashSet::new(),
+            options: ParseOptions::default(),
+            main_content: String::new(),
+            main_lines: Vec::new(),
+            host_lines: Vec::new(),
             visited_files: HashSet::new(),
+            file_order: Vec::new(),
+            home,
         }
     }
 
     pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        let mut config = Self::new();
+        Self::parse_file_with(path, ParseOptions::default())
+    }
+
+    pub fn parse_file_with<P: AsRef<Path>>(path: P, options: ParseOptions) -> Result<Self, String> {
+        Self::parse_file_with_home(path, options, dirs::home_dir())
+    }
+
+    // Like `parse_file_with`, expanding `~` to `home` instead of the user's
+    // home directory
+    pub fn parse_file_with_home<P: AsRef<Path>>(
+        path: P,
+        options: ParseOptions,
+        home: Option<PathBuf>,
+    ) -> Result<Self, String> {
+        let mut config = Self::with_home(home);
+        config.options = options;
+        let content = config.read_file(path.as_ref()).map_err(|e| e.to_string())?;
         let canonical_path = path.as_ref().canonicalize()
             .unwrap_or_else(|_| path.as_ref().to_path_buf());
         config.visited_files.insert(canonical_path.clone());
         config.parse_content(&content, path.as_ref())?;
+        config.main_content = content;
+        config.store_file_lines(&config.file_order.clone());
+        config.warnings.extend(lint::structural_warnings(&config));
+        Ok(config)
+    }
+
+    // Parses config text as if it had been read from `path`. The path becomes
+    // the lines' source file and relative Include directives resolve against
+    // its directory.
+    pub fn parse_str<P: AsRef<Path>>(content: &str, path: P) -> Result<Self, String> {
+        Self::parse_str_with(content, path, ParseOptions::default())
+    }
+
+    pub fn parse_str_with<P: AsRef<Path>>(content: &str, path: P, options: ParseOptions) -> Result<Self, String> {
+        Self::parse_str_with_home(content, path, options, dirs::home_dir())
+    }
+
+    pub fn parse_str_with_home<P: AsRef<Path>>(
+        content: &str,
+        path: P,
+        options: ParseOptions,
+        home: Option<PathBuf>,
+    ) -> Result<Self, String> {
+        let mut config = Self::with_home(home);
+        config.options = options;
+        let canonical_path = path.as_ref().canonicalize()
+            .unwrap_or_else(|_| path.as_ref().to_path_buf());
+        config.visited_files.insert(canonical_path);
+        config.parse_content(content, path.as_ref())?;
+        config.store_file_lines(&config.file_order.clone());
+        config.warnings.extend(lint::structural_warnings(&config));
         Ok(config)
     }
 
+    // Reads a config file, replacing invalid UTF-8 instead of failing so the
+    // valid parts can still be viewed and edited
+    fn read_file(&mut self, path: &Path) -> std::io::Result<String> {
+        let bytes = fs::read(path)?;
+        match String::from_utf8(bytes) {
+            Ok(content) => Ok(content),
+            Err(e) => {
+                self.warnings.push(ParseWarning {
+                    file: path.to_path_buf(),
+                    message: "File contains invalid UTF-8; invalid bytes were replaced with U+FFFD".to_string(),
+                });
+                self.lossy_files.insert(path.to_path_buf());
+                Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+            }
+        }
+    }
+
     fn parse_content(&mut self, content: &str, base_path: &Path) -> Result<(), String> {
-        let mut current_host: Option<(String, Vec<(String, String)>)> = None;
+        // Editors on Windows may prefix the file with a UTF-8 BOM, which would
+        // otherwise end up in the first keyword. It isn't written back.
+        let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+        let mut current_host: Option<PendingHost> = None;
+        let mut indent: Option<String> = None;
+        let mut separator: Option<String> = None;
+        let mut seen_host = false;
+        // A tags comment waiting to see whether a Host or Match line follows
+        // it
+        let mut pending_tags: Option<(String, Vec<String>)> = None;
+        // Comment and blank lines inside a host block, waiting to see
+        // whether an option follows them
+        let mut option_comment: Vec<String> = Vec::new();
+
+        if !self.file_order.iter().any(|p| p == base_path) {
+            self.file_order.push(base_path.to_path_buf());
+        }
 
-        for line in content.lines() {
+        for (line_idx, line) in content.lines().enumerate() {
             let trimmed = line.trim();
 
-            // Handle comments
-            if trimmed.starts_with('#') {
-                if let Some((pattern, options)) = current_host.take() {
-                    self.lines.push(ConfigLine::HostEntry {
-                        pattern,
-                        options,
+            let keyword = split_keyword(trimmed).0;
+            let is_host_line = keyword.eq_ignore_ascii_case("host") || keyword.eq_ignore_ascii_case("match");
+            let host_tags = match pending_tags.take() {
+                Some((_, tags)) if is_host_line => tags,
+                Some((text, _)) => {
+                    self.lines.push(ConfigLine::Comment {
+                        text,
                         source_file: base_path.to_path_buf(),
                     });
+                    Vec::new()
+                }
+                None => Vec::new(),
+            };
+
+            // Comments and blank lines inside a host block belong to the
+            // option below them. If the block ends instead, they stay lines of
+            // their own, so the host keeps its place before them. After a
+            // blank line only an indented option continues the block; an
+            // unindented one is kept as a (flagged) global option.
+            let is_comment = trimmed.starts_with('#') || (trimmed.starts_with(';') && self.options.semicolon_comments);
+            if (is_comment || trimmed.is_empty()) && current_host.is_some() && tags::parse_tags_comment(trimmed).is_none() {
+                option_comment.push(line.to_string());
+                continue;
+            }
+            let is_include_line = split_keyword(trimmed).0.eq_ignore_ascii_case("include");
+            let after_gap = option_comment.iter().any(|text| text.trim().is_empty());
+            let continues_host = !is_comment
+                && !trimmed.is_empty()
+                && !is_host_line
+                && !is_include_line
+                && (!after_gap || line.starts_with(char::is_whitespace));
+            if !option_comment.is_empty() && !continues_host {
+                self.flush_host(current_host.take(), base_path);
+                self.push_loose_lines(option_comment.drain(..), base_path);
+            }
+
+            // Handle comments. Their text is kept verbatim, so a `;` prefix
+            // survives saving.
+            let is_semicolon = trimmed.starts_with(';');
+            if trimmed.starts_with('#') || (is_semicolon && self.options.semicolon_comments) {
+                self.flush_host(current_host.take(), base_path);
+                if let Some(tags) = tags::parse_tags_comment(trimmed) {
+                    pending_tags = Some((line.to_string(), tags));
+                    continue;
                 }
                 self.lines.push(ConfigLine::Comment {
                     text: line.to_string(),
@@ -85,64 +425,107 @@ impl SshConfig {
 
             // Handle empty lines
             if trimmed.is_empty() {
-                if let Some((pattern, options)) = current_host.take() {
-                    self.lines.push(ConfigLine::HostEntry {
-                        pattern,
-                        options,
-                        source_file: base_path.to_path_buf(),
-                    });
-                }
+                self.flush_host(current_host.take(), base_path);
                 self.lines.push(ConfigLine::Empty {
                     source_file: base_path.to_path_buf(),
                 });
                 continue;
             }
 
-            // Parse key-value pairs
-            let parts: Vec<&str> = trimmed.splitn(2, char::is_whitespace).collect();
-            if parts.len() < 2 {
-                continue;
+            if is_semicolon {
+                self.warnings.push(ParseWarning {
+                    file: base_path.to_path_buf(),
+                    message: format!(
+                        "'{}' starts with ';', which ssh doesn't treat as a comment; use '#' instead",
+                        trimmed
+                    ),
+                });
             }
 
-            let key = parts[0].trim();
-            let value = parts[1].trim();
+            // Parse key-value pairs. A keyword without a value is kept (with
+            // an empty value) rather than dropped, and flagged since OpenSSH
+            // requires an argument for every keyword. An explicitly empty
+            // argument is written as `""` and kept as that literal value.
+            // Everything after the keyword is the value, however much it
+            // looks like more options (`ProxyCommand ssh -o User=x host`).
+            let content = line.trim_start();
+            let (key, rest) = split_keyword(content);
+            let value = trim_value(rest);
+
+            if value.is_empty() {
+                self.warnings.push(ParseWarning {
+                    file: base_path.to_path_buf(),
+                    message: format!("'{}' has no value", key),
+                });
+            } else if separator.is_none() {
+                separator = Some(detect_separator(&content[key.len()..content.len() - rest.trim_start().len()]));
+            }
 
             match key.to_lowercase().as_str() {
-                "host" => {
+                keyword @ ("host" | "match") => {
                     // Save previous host entry if exists
-                    if let Some((pattern, options)) = current_host.take() {
-                        self.lines.push(ConfigLine::HostEntry {
-                            pattern,
-                            options,
-                            source_file: base_path.to_path_buf(),
-                        });
-                    }
+                    self.flush_host(current_host.take(), base_path);
                     // Start new host entry
-                    current_host = Some((value.to_string(), Vec::new()));
+                    seen_host = true;
+                    let (pattern, comment) = split_trailing_comment(value);
+                    current_host = Some(PendingHost {
+                        is_match: keyword == "match",
+                        pattern: pattern.to_string(),
+                        comment: comment.map(str::to_string),
+                        tags: host_tags,
+                        options: Vec::new(),
+                        option_comments: Vec::new(),
+                        line: line_idx + 1,
+                    });
                 }
                 "include" => {
                     // Save previous host entry if exists
-                    if let Some((pattern, options)) = current_host.take() {
-                        self.lines.push(ConfigLine::HostEntry {
-                            pattern,
-                            options,
-                            source_file: base_path.to_path_buf(),
-                        });
-                    }
+                    self.flush_host(current_host.take(), base_path);
+                    let (path, comment) = split_trailing_comment(value);
+                    let include_idx = self.lines.len();
                     self.lines.push(ConfigLine::Include {
-                        path: value.to_string(),
+                        path: path.to_string(),
+                        comment: comment.map(str::to_string),
+                        resolved: Vec::new(),
                         source_file: base_path.to_path_buf(),
                     });
 
-                    // Parse included files
-                    self.parse_include(value, base_path)?;
+                    // Parse included files. Quotes allow spaces in the path
+                    // but aren't part of it.
+                    let unquoted = path.trim_matches('"');
+                    if !unquoted.is_empty() {
+                        let files = self.parse_include(unquoted, base_path)?;
+                        if let Some(ConfigLine::Include { resolved, .. }) = self.lines.get_mut(include_idx) {
+                            *resolved = files;
+                        }
+                    }
                 }
                 _ => {
-                    if let Some((_, ref mut options)) = current_host {
+                    if let Some(ref mut host) = current_host {
+                        if indent.is_none() {
+                            let leading = &line[..line.len() - line.trim_start().len()];
+                            if !leading.is_empty() {
+                                indent = Some(leading.to_string());
+                            }
+                        }
                         // Add option to current host
-                        options.push((key.to_string(), value.to_string()));
+                        host.options.push((key.to_string(), value.to_string()));
+                        host.option_comments.push(option_comment.drain(..).map(|text| text + "\n").collect());
                     } else {
-                        // Global option
+                        // Global option. Once a Host block has started, ssh
+                        // applies any further option to the preceding block
+                        // (blank lines and comments don't end it), so it is
+                        // kept in place but flagged.
+                        if seen_host {
+                            self.warnings.push(ParseWarning {
+                                file: base_path.to_path_buf(),
+                                message: format!(
+                                    "'{}' appears after a Host block; ssh applies it to the preceding host, \
+                                     not globally. Move it above the first Host or under \"Host *\"",
+                                    key
+                                ),
+                            });
+                        }
                         self.lines.push(ConfigLine::GlobalOption {
                             key: key.to_string(),
                             value: value.to_string(),
@@ -154,175 +537,3031 @@ impl SshConfig {
         }
 
         // Don't forget the last host entry
-        if let Some((pattern, options)) = current_host {
-            self.lines.push(ConfigLine::HostEntry {
-                pattern,
-                options,
+        self.flush_host(current_host, base_path);
+        self.push_loose_lines(option_comment, base_path);
+        if let Some((text, _)) = pending_tags {
+            self.lines.push(ConfigLine::Comment {
+                text,
                 source_file: base_path.to_path_buf(),
             });
         }
 
+        let default_format = LineFormat::default();
+        self.formats.insert(
+            base_path.to_path_buf(),
+            LineFormat {
+                indent: indent.unwrap_or(default_format.indent),
+                separator: separator.unwrap_or(default_format.separator),
+            },
+        );
+
         Ok(())
     }
 
-    fn parse_include(&mut self, pattern: &str, base_path: &Path) -> Result<(), String> {
-        // Expand ~ to home directory
-        let expanded = if pattern.starts_with("~/") {
-            if let Some(home) = dirs::home_dir() {
-                home.join(&pattern[2..])
+    // Comment and blank lines held back from a host block that ended
+    fn push_loose_lines(&mut self, texts: impl IntoIterator<Item = String>, base_path: &Path) {
+        for text in texts {
+            let source_file = base_path.to_path_buf();
+            self.lines.push(if text.trim().is_empty() {
+                ConfigLine::Empty { source_file }
             } else {
-                PathBuf::from(pattern)
+                ConfigLine::Comment { text, source_file }
+            });
+        }
+    }
+
+    fn flush_host(&mut self, host: Option<PendingHost>, base_path: &Path) {
+        let Some(host) = host else {
+            return;
+        };
+        if host.is_match {
+            self.lines.push(ConfigLine::MatchEntry {
+                criteria: host.pattern,
+                comment: host.comment,
+                tags: host.tags,
+                options: host.options,
+                option_comments: host.option_comments,
+                source_file: base_path.to_path_buf(),
+            });
+            return;
+        }
+        self.lines.push(ConfigLine::HostEntry {
+            pattern: host.pattern,
+            comment: host.comment,
+            tags: host.tags,
+            options: host.options,
+            option_comments: host.option_comments,
+            source_file: base_path.to_path_buf(),
+        });
+        if let Some(handle) = self.host_handle(self.lines.len() - 1) {
+            self.host_lines.push((handle, host.line));
+        }
+    }
+
+    // Resolves an Include pattern the way OpenSSH does: `~/` expands to the
+    // home directory and relative paths are looked up in ~/.ssh (or /etc/ssh
+    // when editing a system config). If nothing matches there, the including
+    // file's own directory is tried so configs kept elsewhere still work.
+    // Returns the files the pattern matched.
+    fn parse_include(&mut self, pattern: &str, base_path: &Path) -> Result<Vec<PathBuf>, String> {
+        // Expand ~ to home directory. Without one there's nothing sensible
+        // to resolve against, so the include is flagged instead.
+        let expanded = match (pattern.strip_prefix("~/"), &self.home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            (Some(_), None) => {
+                self.warnings.push(ParseWarning {
+                    file: base_path.to_path_buf(),
+                    message: format!("Include {}: home directory unknown, can't expand ~", pattern),
+                });
+                return Ok(Vec::new());
             }
-        } else {
-            PathBuf::from(pattern)
+            (None, _) => PathBuf::from(pattern),
         };
 
-        // Make relative paths relative to the config file's directory
-        let include_path = if expanded.is_relative() {
+        let mut candidates = Vec::new();
+        if expanded.is_relative() {
+            if let Some(dir) = self.include_base_dir() {
+                candidates.push(dir.join(&expanded));
+            }
             if let Some(parent) = base_path.parent() {
-                parent.join(expanded)
-            } else {
-                expanded
+                let local = parent.join(&expanded);
+                if !candidates.contains(&local) {
+                    candidates.push(local);
+                }
             }
-        } else {
-            expanded
-        };
+        }
+        if candidates.is_empty() {
+            candidates.push(expanded);
+        }
 
-        // Handle glob patterns
-        let pattern_str = include_path.to_string_lossy().to_string();
-        match glob::glob(&pattern_str) {
-            Ok(paths) => {
-                for entry in paths {
-                    if let Ok(path) = entry {
-                        if path.is_file() {
-                            // Check for circular includes
-                            let canonical_path = path.canonicalize()
-                                .unwrap_or_else(|_| path.clone());
-
-                            if self.visited_files.contains(&canonical_path) {
-                                // Skip already visited files to prevent infinite recursion
-                                continue;
-                            }
+        let mut problems: Vec<String> = Vec::new();
+        for include_path in candidates {
+            let (matched, candidate_problems) = resolve_include_files(&include_path);
+            for problem in candidate_problems {
+                if !problems.contains(&problem) {
+                    self.warnings.push(ParseWarning {
+                        file: base_path.to_path_buf(),
+                        message: format!("Include {}: {}", pattern, problem),
+                    });
+                    problems.push(problem);
+                }
+            }
+            if !matched.is_empty() {
+                for path in &matched {
+                    self.parse_included_file(path)?;
+                }
+                return Ok(matched);
+            }
+        }
+
+        Ok(Vec::new())
+    }
 
-                            self.visited_files.insert(canonical_path.clone());
+    // Directory relative Include paths resolve against: /etc/ssh for the
+    // system config, ~/.ssh otherwise
+    fn include_base_dir(&self) -> Option<PathBuf> {
+        let system_dir = Path::new("/etc/ssh");
+        match self.file_order.first() {
+            Some(main) if main.starts_with(system_dir) => Some(system_dir.to_path_buf()),
+            _ => self.home.as_ref().map(|home| home.join(".ssh")),
+        }
+    }
 
-                            if let Ok(content) = fs::read_to_string(&path) {
-                                // Parse the included file - reuse visited_files to track across includes
-                                self.parse_content(&content, &path)?;
+    fn parse_included_file(&mut self, path: &Path) -> Result<(), String> {
+        // Check for circular includes
+        let canonical_path = path.canonicalize()
+            .unwrap_or_else(|_| path.to_path_buf());
 
-                                // Store for reference
-                                self.included_files.insert(
-                                    path.clone(),
-                                    IncludedFileData {
-                                        content: content.clone(),
-                                        lines: Vec::new(),
-                                    },
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-            Err(_) => {
-                // If glob fails, try as a single file
-                if include_path.is_file() {
-                    // Check for circular includes
-                    let canonical_path = include_path.canonicalize()
-                        .unwrap_or_else(|_| include_path.clone());
+        if self.visited_files.contains(&canonical_path) {
+            // Skip already visited files to prevent infinite recursion
+            return Ok(());
+        }
 
-                    if self.visited_files.contains(&canonical_path) {
-                        // Skip already visited files
-                        return Ok(());
-                    }
+        self.visited_files.insert(canonical_path);
 
-                    self.visited_files.insert(canonical_path.clone());
+        if let Ok(content) = self.read_file(path) {
+            // Parse the included file - reuse visited_files to track across includes
+            self.parse_content(&content, path)?;
 
-                    if let Ok(content) = fs::read_to_string(&include_path) {
-                        self.parse_content(&content, &include_path)?;
+            // Store for reference
+            self.included_files.insert(
+                path.to_path_buf(),
+                IncludedFileData {
+                    content,
+                    lines: Vec::new(),
+                },
+            );
+        }
 
-                        self.included_files.insert(
-                            include_path.clone(),
-                            IncludedFileData {
-                                content,
-                                lines: Vec::new(),
-                            },
-                        );
-                    }
+        Ok(())
+    }
+
+    // Files whose text on disk differs from what was parsed (including files
+    // that can no longer be read)
+    pub fn changed_on_disk(&self) -> Vec<PathBuf> {
+        let main_path = self.file_order.first();
+        self.file_order
+            .iter()
+            .filter(|path| {
+                let parsed = match self.included_files.get(*path) {
+                    Some(data) => &data.content,
+                    None if Some(*path) == main_path => &self.main_content,
+                    None => return false,
+                };
+                // A file added but never saved isn't on disk yet
+                fs::read(path).map_or(!parsed.is_empty(), |bytes| String::from_utf8_lossy(&bytes) != parsed.as_str())
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Re-reads only the files that changed on disk, replacing their lines and
+    // keeping everything else (including edits to other files). Changes to
+    // the main file or to any file with Include lines can move whole files
+    // around, so those fall back to parsing everything again. Returns the
+    // files that were re-read.
+    pub fn reload_changed(&mut self) -> Result<Vec<PathBuf>, String> {
+        let changed = self.changed_on_disk();
+        let Some(main_path) = self.file_order.first().cloned() else {
+            return Ok(changed);
+        };
+
+        let mut reparsed = Vec::new();
+        for file in &changed {
+            match self.reparse_alone(file, &main_path)? {
+                Some((content, fresh)) => reparsed.push((file, content, fresh)),
+                None => {
+                    *self = Self::parse_file_with_home(&main_path, self.options, self.home.clone())?;
+                    return Ok(changed);
                 }
             }
         }
 
-        Ok(())
+        for (file, content, fresh) in reparsed {
+            // The file's new lines take the place of its old ones
+            let position = self.lines.iter().position(|line| line.source_file() == file);
+            self.lines.retain(|line| line.source_file() != file);
+            let position = position.unwrap_or(self.lines.len()).min(self.lines.len());
+            self.lines.splice(position..position, fresh.lines);
+
+            self.warnings.retain(|warning| warning.file != *file);
+            self.warnings.extend(fresh.warnings);
+            self.host_lines.retain(|(handle, _)| handle.source_file != *file);
+            self.host_lines.extend(fresh.host_lines);
+            self.formats.extend(fresh.formats);
+            self.lossy_files.remove(file);
+            self.lossy_files.extend(fresh.lossy_files);
+            if let Some(data) = self.included_files.get_mut(file) {
+                data.content = content;
+            }
+        }
+        self.store_file_lines(&changed);
+        Ok(changed)
+    }
+
+    // Parses `file` on its own for `reload_changed`: its new text and lines,
+    // or None if it can't be handled without parsing everything again
+    fn reparse_alone(&self, file: &Path, main_path: &Path) -> Result<Option<(String, SshConfig)>, String> {
+        let has_includes = |lines: &[ConfigLine]| {
+            lines
+                .iter()
+                .any(|line| matches!(line, ConfigLine::Include { source_file, .. } if source_file == file))
+        };
+        if file == main_path || has_includes(&self.lines) {
+            return Ok(None);
+        }
+
+        let mut fresh = Self::with_home(self.home.clone());
+        fresh.options = self.options;
+        let Ok(content) = fresh.read_file(file) else {
+            return Ok(None);
+        };
+        fresh.file_order.push(file.to_path_buf());
+        fresh.parse_content(&content, file)?;
+        if has_includes(&fresh.lines) {
+            return Ok(None);
+        }
+        fresh.warnings.extend(lint::structural_warnings(&fresh));
+        Ok(Some((content, fresh)))
     }
 
     pub fn to_string(&self, file_path: &Path) -> String {
+        self.to_string_with(file_path, true)
+    }
+
+    // Like `to_string`; without `include_comments` only the directives are
+    // written, leaving out comment and blank lines, the comments above
+    // options and after Host/Include values, and `# @tags:` lines
+    pub fn to_string_with(&self, file_path: &Path, include_comments: bool) -> String {
         let mut result = String::new();
+        let format = self.format_for(file_path);
 
         for line in &self.lines {
-            // Get source_file from each line type and skip if not from this file
-            let line_source = match line {
-                ConfigLine::Comment { source_file, .. } => source_file,
-                ConfigLine::Empty { source_file } => source_file,
-                ConfigLine::Include { source_file, .. } => source_file,
-                ConfigLine::HostEntry { source_file, .. } => source_file,
-                ConfigLine::GlobalOption { source_file, .. } => source_file,
-            };
+            // Skip lines that don't belong to this file
+            if line.source_file() != file_path {
+                continue;
+            }
+
+            write_line(&mut result, line, &format, include_comments);
+        }
+
+        result
+    }
 
-            if line_source != file_path {
+    // `main_path` as a single file: every Include line is replaced by the
+    // lines of the files it resolved to, recursively, which is exactly what
+    // ssh reads in its place. Everything is written in the main file's
+    // format. A file included again from within itself is skipped.
+    pub fn to_flattened_string(&self, main_path: &Path, include_comments: bool) -> String {
+        let mut result = String::new();
+        let format = self.format_for(main_path);
+        let mut stack = vec![main_path.to_path_buf()];
+        self.write_flattened(&mut result, main_path, &format, include_comments, &mut stack);
+        result
+    }
+
+    fn write_flattened(
+        &self,
+        result: &mut String,
+        file_path: &Path,
+        format: &LineFormat,
+        include_comments: bool,
+        stack: &mut Vec<PathBuf>,
+    ) {
+        for line in self.lines.iter().filter(|line| line.source_file() == file_path) {
+            let ConfigLine::Include { resolved, .. } = line else {
+                write_line(result, line, format, include_comments);
                 continue;
+            };
+            for file in resolved {
+                if stack.contains(file) {
+                    continue;
+                }
+                stack.push(file.clone());
+                self.write_flattened(result, file, format, include_comments, stack);
+                stack.pop();
+            }
+        }
+    }
+
+    // Serializes a single line (e.g. one host block) using the formatting of
+    // the file it came from
+    pub fn line_to_string(&self, line: &ConfigLine) -> String {
+        let mut result = String::new();
+        let format = self.format_for(line.source_file());
+        write_line(&mut result, line, &format, true);
+        result
+    }
+
+    // Layout `file_path` is written with
+    pub fn format_for(&self, file_path: &Path) -> LineFormat {
+        let mut format = self.formats.get(file_path).cloned().unwrap_or_default();
+        if let Some(width) = self.indent_override {
+            format.indent = " ".repeat(width.clamp(1, 8));
+        }
+        format
+    }
+
+    // Groups lines by source file in include order while keeping the order of
+    // lines within each file. Files that weren't parsed (e.g. a new target
+    // file) go last in order of first appearance.
+    pub fn normalize_line_order(&mut self) {
+        let mut order = self.file_order.clone();
+        for line in &self.lines {
+            if !order.iter().any(|p| p == line.source_file()) {
+                order.push(line.source_file().to_path_buf());
             }
+        }
 
-            // Now write the line
-            match line {
-                ConfigLine::Comment { text, .. } => {
-                    result.push_str(text);
-                    result.push('\n');
+        self.lines.sort_by_key(|line| {
+            order.iter().position(|p| p == line.source_file()).unwrap_or(usize::MAX)
+        });
+    }
+
+    // Copies every host entry of `other` into `target_file`. Hosts whose
+    // pattern already exists are handled according to `policy`.
+    pub fn merge_from(&mut self, other: &SshConfig, target_file: &Path, policy: MergePolicy) -> MergeReport {
+        let mut report = MergeReport::default();
+
+        for line in &other.lines {
+            let ConfigLine::HostEntry {
+                pattern,
+                comment,
+                tags,
+                options,
+                option_comments,
+                ..
+            } = line
+            else {
+                continue;
+            };
+
+            let existing = self.find_host_index(pattern);
+
+            let new_pattern = match (existing, policy) {
+                (None, _) => pattern.clone(),
+                (Some(_), MergePolicy::Skip) => {
+                    report.skipped += 1;
+                    continue;
                 }
-                ConfigLine::Empty { .. } => {
-                    result.push('\n');
+                (Some(idx), MergePolicy::Overwrite) => {
+                    if let Some(ConfigLine::HostEntry {
+                        comment: existing_comment,
+                        tags: existing_tags,
+                        options: existing_options,
+                        option_comments: existing_option_comments,
+                        ..
+                    }) = self.lines.get_mut(idx)
+                    {
+                        *existing_comment = comment.clone();
+                        *existing_tags = tags.clone();
+                        *existing_options = options.clone();
+                        *existing_option_comments = option_comments.clone();
+                    }
+                    report.overwritten += 1;
+                    continue;
                 }
-                ConfigLine::Include { path, .. } => {
-                    result.push_str("Include ");
-                    result.push_str(path);
-                    result.push('\n');
+                (Some(_), MergePolicy::Rename) => {
+                    report.renamed += 1;
+                    self.unique_pattern(pattern)
                 }
-                ConfigLine::HostEntry {
-                    pattern, options, ..
-                } => {
-                    result.push_str("Host ");
-                    result.push_str(pattern);
-                    result.push('\n');
-                    for (key, value) in options {
-                        result.push_str("    ");
-                        result.push_str(key);
-                        result.push(' ');
-                        result.push_str(value);
-                        result.push('\n');
+            };
+
+            self.lines.push(ConfigLine::HostEntry {
+                pattern: new_pattern,
+                comment: comment.clone(),
+                tags: tags.clone(),
+                options: options.clone(),
+                option_comments: option_comments.clone(),
+                source_file: target_file.to_path_buf(),
+            });
+            report.added += 1;
+        }
+
+        self.normalize_line_order();
+        report
+    }
+
+    // Creates or updates hosts from a JSON document like
+    //
+    //   {"hosts": [{"pattern": "web", "file": "/home/me/.ssh/conf.d/work",
+    //               "options": {"HostName": "web.example.com", "Port": "22"}}]}
+    //
+    // `options` may also be a list of `[key, value]` pairs, which keeps their
+    // order and allows repeated keys; `file` defaults to `default_file` and
+    // only matters for new hosts. It must name the main file or a file that
+    // is already included, since every file a line belongs to is written on
+    // save. Existing hosts are found by pattern (see `find_host_index`) and
+    // changed according to `policy`. The whole document is checked first, so
+    // malformed input changes nothing.
+    pub fn apply_json(
+        &mut self,
+        value: &serde_json::Value,
+        default_file: &Path,
+        policy: UpsertPolicy,
+    ) -> Result<UpsertReport, String> {
+        let managed: Vec<&Path> = std::iter::once(default_file)
+            .chain(self.file_order.first().map(PathBuf::as_path))
+            .chain(self.included_files.keys().map(PathBuf::as_path))
+            .collect();
+        let hosts = parse_json_hosts(value, &managed)?;
+        let mut report = UpsertReport::default();
+
+        for host in hosts {
+            let Some(idx) = self.find_host_index(&host.pattern) else {
+                self.lines.push(ConfigLine::HostEntry {
+                    pattern: host.pattern,
+                    comment: None,
+                    tags: Vec::new(),
+                    options: host.options,
+                    option_comments: Vec::new(),
+                    source_file: host.file.unwrap_or_else(|| default_file.to_path_buf()),
+                });
+                report.created += 1;
+                continue;
+            };
+            let Some(ConfigLine::HostEntry { options, option_comments, .. }) = self.lines.get_mut(idx) else {
+                continue;
+            };
+
+            let before = options.clone();
+            match policy {
+                UpsertPolicy::KeepExisting => {}
+                UpsertPolicy::Replace => {
+                    if *options != host.options {
+                        *options = host.options;
+                        option_comments.clear();
                     }
                 }
-                ConfigLine::GlobalOption { key, value, .. } => {
-                    result.push_str(key);
-                    result.push(' ');
-                    result.push_str(value);
-                    result.push('\n');
+                UpsertPolicy::Update => {
+                    for (key, value) in host.options {
+                        if options.iter().any(|(k, v)| k.eq_ignore_ascii_case(&key) && *v == value) {
+                            continue;
+                        }
+                        match options.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(&key)) {
+                            Some((_, existing)) if !keywords::is_repeatable(&key) => *existing = value,
+                            _ => options.push((key, value)),
+                        }
+                    }
                 }
             }
+            if *options == before {
+                report.unchanged += 1;
+            } else {
+                report.updated += 1;
+            }
         }
 
-        result
+        self.normalize_line_order();
+        Ok(report)
+    }
+
+    // Files with more than one Host block for the same pattern list
+    pub fn files_with_duplicate_hosts(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for (idx, line) in self.lines.iter().enumerate() {
+            let ConfigLine::HostEntry { pattern, source_file, .. } = line else {
+                continue;
+            };
+            let repeated = self.lines[..idx].iter().any(|earlier| {
+                matches!(earlier, ConfigLine::HostEntry { pattern: p, source_file: f, .. }
+                    if f == source_file && pattern::same_pattern_list(p, pattern))
+            });
+            if repeated && !files.contains(source_file) {
+                files.push(source_file.clone());
+            }
+        }
+        files
     }
 
-    pub fn save_all(&self, main_path: &Path) -> Result<(), String> {
-        // Save main config file
-        let main_content = self.to_string(main_path);
-        fs::write(main_path, main_content).map_err(|e| e.to_string())?;
+    // Folds every Host block of `file` into the first block with the same
+    // pattern list, the way ssh reads them: for a key both set, the first
+    // block's value wins and the later one is dropped and reported.
+    // Repeatable keywords are all kept, except exact repeats.
+    pub fn merge_duplicate_hosts(&mut self, file: &Path) -> DuplicateMergeReport {
+        let mut report = DuplicateMergeReport::default();
+        let mut idx = 0;
+        while idx < self.lines.len() {
+            let Some(first) = self.lines[..idx].iter().position(|earlier| {
+                matches!((earlier, &self.lines[idx]),
+                    (ConfigLine::HostEntry { pattern: a, source_file: fa, .. },
+                     ConfigLine::HostEntry { pattern: b, source_file: fb, .. })
+                    if fa == file && fb == file && pattern::same_pattern_list(a, b))
+            }) else {
+                idx += 1;
+                continue;
+            };
+
+            let ConfigLine::HostEntry { comment, tags, options, option_comments, .. } = self.lines.remove(idx) else {
+                unreachable!();
+            };
+            if let Some(ConfigLine::HostEntry {
+                pattern: first_pattern,
+                comment: first_comment,
+                tags: first_tags,
+                options: first_options,
+                option_comments: first_option_comments,
+                ..
+            }) = self.lines.get_mut(first)
+            {
+                if first_comment.is_none() {
+                    *first_comment = comment;
+                }
+                for tag in tags {
+                    if !first_tags.contains(&tag) {
+                        first_tags.push(tag);
+                    }
+                }
+                for (option_idx, (key, value)) in options.into_iter().enumerate() {
+                    let existing = first_options.iter().find(|(k, _)| k.eq_ignore_ascii_case(&key));
+                    let keep = if keywords::is_repeatable(&key) {
+                        !first_options.iter().any(|(k, v)| k.eq_ignore_ascii_case(&key) && *v == value)
+                    } else {
+                        if let Some((existing_key, existing_value)) = existing
+                            && *existing_value != value
+                        {
+                            report.conflicts.push(format!(
+                                "Host {}: dropped '{} {}', keeping '{} {}'",
+                                first_pattern, key, value, existing_key, existing_value
+                            ));
+                        }
+                        existing.is_none()
+                    };
+                    if keep {
+                        let position = first_options.len();
+                        first_options.push((key, value));
+                        let option_comment = option_comments.get(option_idx).cloned().unwrap_or_default();
+                        insert_option_comment(first_option_comments, position, option_comment);
+                    }
+                }
+            }
+            report.merged += 1;
 
-        // Save all included files
-        for (include_path, _) in &self.included_files {
-            let include_content = self.to_string(include_path);
-            fs::write(include_path, include_content).map_err(|e| e.to_string())?;
+            // Don't leave two blank lines where the block was
+            let is_blank = |line: Option<&ConfigLine>| matches!(line, Some(ConfigLine::Empty { source_file }) if source_file == file);
+            let at_end = self.lines.get(idx).is_none_or(|line| line.source_file() != file);
+            if idx > 0 && is_blank(self.lines.get(idx - 1)) && (at_end || is_blank(self.lines.get(idx))) {
+                self.lines.remove(idx - 1);
+                idx -= 1;
+            }
         }
+        report
+    }
 
-        Ok(())
+    // First of `pattern-2`, `pattern-3`, ... that isn't used by any host
+    fn unique_pattern(&self, pattern: &str) -> String {
+        (2..)
+            .map(|n| format!("{}-{}", pattern, n))
+            .find(|candidate| self.find_host_index(candidate).is_none())
+            .unwrap_or_else(|| pattern.to_string())
+    }
+
+    pub fn host_handle(&self, index: usize) -> Option<HostHandle> {
+        let ConfigLine::HostEntry { pattern, source_file, .. } = self.lines.get(index)? else {
+            return None;
+        };
+        let occurrence = self.lines[..index]
+            .iter()
+            .filter(|line| {
+                matches!(line, ConfigLine::HostEntry { pattern: p, source_file: f, .. }
+                    if p == pattern && f == source_file)
+            })
+            .count();
+
+        Some(HostHandle {
+            source_file: source_file.clone(),
+            pattern: pattern.clone(),
+            occurrence,
+        })
+    }
+
+    // Line of the host's `Host` line in its file as it was parsed, or None for
+    // hosts added or renamed since
+    pub fn host_line(&self, index: usize) -> Option<usize> {
+        let handle = self.host_handle(index)?;
+        self.host_lines.iter().find(|(h, _)| *h == handle).map(|(_, line)| *line)
+    }
+
+    // Current index of the host `handle` refers to, if it still exists
+    pub fn resolve_host(&self, handle: &HostHandle) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                matches!(line, ConfigLine::HostEntry { pattern, source_file, .. }
+                    if *pattern == handle.pattern && *source_file == handle.source_file)
+            })
+            .nth(handle.occurrence)
+            .map(|(idx, _)| idx)
+    }
+
+    // The version of the host at `index` in `saved` (the lines as they were
+    // last loaded or saved). Hosts are matched by pattern first and, if the
+    // pattern was edited, by their position among the hosts of their file.
+    // None means the host was added since.
+    pub fn saved_host<'a>(&self, index: usize, saved: &'a [ConfigLine]) -> Option<&'a ConfigLine> {
+        let handle = self.host_handle(index)?;
+        let saved_hosts_of_file = || {
+            saved.iter().filter(|line| {
+                matches!(line, ConfigLine::HostEntry { source_file, .. } if *source_file == handle.source_file)
+            })
+        };
+
+        let by_pattern = saved_hosts_of_file()
+            .filter(|line| matches!(line, ConfigLine::HostEntry { pattern, .. } if *pattern == handle.pattern))
+            .nth(handle.occurrence);
+        by_pattern.or_else(|| {
+            let position = self.lines[..index]
+                .iter()
+                .filter(|line| {
+                    matches!(line, ConfigLine::HostEntry { source_file, .. } if *source_file == handle.source_file)
+                })
+                .count();
+            saved_hosts_of_file().nth(position)
+        })
+    }
+
+    // Files whose lines differ from `saved` (the lines as they were last
+    // loaded or saved), i.e. the files with unsaved changes
+    pub fn changed_files(&self, saved: &[ConfigLine]) -> HashSet<PathBuf> {
+        let current = lines_by_file(&self.lines);
+        let saved = lines_by_file(saved);
+
+        current
+            .keys()
+            .chain(saved.keys())
+            .filter(|file| current.get(*file) != saved.get(*file))
+            .map(|file| file.to_path_buf())
+            .collect()
+    }
+
+    // Files with unsaved changes that look machine-wide, see `is_system_path`
+    pub fn changed_system_files(&self, saved: &[ConfigLine]) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .changed_files(saved)
+            .into_iter()
+            .filter(|file| self.is_system_file(file))
+            .collect();
+        files.sort();
+        files
+    }
+
+    // Home directory `~` expands to in this config
+    pub fn home(&self) -> Option<&Path> {
+        self.home.as_deref()
+    }
+
+    pub fn is_system_file(&self, path: &Path) -> bool {
+        is_system_path(path, self.home.as_deref())
+    }
+
+    // Indices of the hosts added or edited since `saved`. Only hosts of
+    // files with changes are compared.
+    pub fn changed_hosts(&self, saved: &[ConfigLine]) -> HashSet<usize> {
+        let changed_files = self.changed_files(saved);
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(idx, line)| {
+                matches!(line, ConfigLine::HostEntry { source_file, .. } if changed_files.contains(source_file))
+                    && self.saved_host(*idx, saved) != Some(*line)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    // Restores the host at `index` to its version in `saved`. Returns false
+    // if the host didn't exist there.
+    pub fn revert_host(&mut self, index: usize, saved: &[ConfigLine]) -> bool {
+        let Some(saved_line) = self.saved_host(index, saved).cloned() else {
+            return false;
+        };
+        self.lines[index] = saved_line;
+        true
+    }
+
+    // Every host entry, in line order
+    pub fn hosts(&self) -> impl Iterator<Item = HostRef<'_>> {
+        self.lines.iter().enumerate().filter_map(|(index, line)| match line {
+            ConfigLine::HostEntry {
+                pattern,
+                comment,
+                tags,
+                options,
+                source_file,
+                ..
+            } => Some(HostRef {
+                index,
+                pattern,
+                comment: comment.as_deref(),
+                tags,
+                options,
+                source_file,
+            }),
+            _ => None,
+        })
+    }
+
+    // `(key, value, file)` of every option outside a host block
+    pub fn globals(&self) -> impl Iterator<Item = (&str, &str, &Path)> {
+        self.lines.iter().filter_map(|line| match line {
+            ConfigLine::GlobalOption { key, value, source_file } => Some((key.as_str(), value.as_str(), source_file.as_path())),
+            _ => None,
+        })
+    }
+
+    // `(path as written, files it resolved to, file it's in)` of every
+    // Include line
+    pub fn includes(&self) -> impl Iterator<Item = (&str, &[PathBuf], &Path)> {
+        self.lines.iter().filter_map(|line| match line {
+            ConfigLine::Include { path, resolved, source_file, .. } => {
+                Some((path.as_str(), resolved.as_slice(), source_file.as_path()))
+            }
+            _ => None,
+        })
+    }
+
+    // Index of the first host entry with the same pattern list as `pattern`,
+    // ignoring case and separators (see `pattern::same_pattern_list`)
+    pub fn find_host_index(&self, pattern: &str) -> Option<usize> {
+        self.hosts()
+            .find(|host| pattern::same_pattern_list(host.pattern, pattern))
+            .map(|host| host.index)
+    }
+
+    // The host entry `find_host_index` finds, always a `ConfigLine::HostEntry`
+    pub fn host_mut_by_pattern(&mut self, pattern: &str) -> Option<&mut ConfigLine> {
+        let index = self.find_host_index(pattern)?;
+        self.lines.get_mut(index)
+    }
+
+    // Index of the host entry that defines `alias`. A host naming the alias
+    // literally is preferred over one that only matches it via wildcards.
+    pub fn find_host_for_alias(&self, alias: &str) -> Option<usize> {
+        self.hosts()
+            .find(|host| pattern::defines_alias(host.pattern, alias))
+            .or_else(|| self.hosts().find(|host| pattern::matches_pattern_list(host.pattern, alias)))
+            .map(|host| host.index)
+    }
+
+    // Options ssh would use when connecting to `alias`, in the order it reads
+    // them: the first value of a keyword wins, except for repeatable keywords
+    // whose values add up. Options following a Host block belong to it, like
+    // in ssh, so they only count when that block matches. Match blocks count
+    // where `pattern::match_criteria_hold` says they do.
+    pub fn effective_options(&self, alias: &str) -> Vec<(String, String)> {
+        self.effective_options_with_system(alias, None)
+    }
+
+    // Like `effective_options`, followed by the machine-wide config the way
+    // ssh reads it after the user's: a keyword set here wins over the same
+    // keyword in `system`, wherever it is set in either.
+    pub fn effective_options_with_system(&self, alias: &str, system: Option<&SshConfig>) -> Vec<(String, String)> {
+        let mut effective: Vec<(String, String)> = Vec::new();
+        let mut add = |key: &str, value: &str| {
+            if keywords::is_repeatable(key) || !effective.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                effective.push((key.to_string(), value.to_string()));
+            }
+        };
+
+        for config in std::iter::once(self).chain(system) {
+            let main = config
+                .file_order
+                .first()
+                .map(PathBuf::as_path)
+                .or_else(|| config.lines.first().map(ConfigLine::source_file));
+            // Every config starts outside of any Host block
+            if let Some(main) = main {
+                config.add_effective(main, alias, true, &mut add, &mut vec![main.to_path_buf()]);
+            }
+        }
+        effective
+    }
+
+    // Passes the options of `file` that apply to `alias` to `add` in the
+    // order ssh reads them, following Include lines into the files they
+    // resolved to. `active` says whether the lines before are in a matching
+    // Host block (or outside of any). Like OpenSSH's readconf, an included
+    // file starts in the state of its Include line, and that state is
+    // restored once the file is read.
+    fn add_effective(
+        &self,
+        file: &Path,
+        alias: &str,
+        mut active: bool,
+        add: &mut dyn FnMut(&str, &str),
+        stack: &mut Vec<PathBuf>,
+    ) {
+        for line in self.lines.iter().filter(|line| line.source_file() == file) {
+            match line {
+                ConfigLine::HostEntry { pattern, options, .. } => {
+                    active = pattern::matches_pattern_list(pattern, alias);
+                    if active {
+                        for (key, value) in options {
+                            add(key, value);
+                        }
+                    }
+                }
+                ConfigLine::MatchEntry { criteria, options, .. } => {
+                    active = pattern::match_criteria_hold(criteria, alias);
+                    if active {
+                        for (key, value) in options {
+                            add(key, value);
+                        }
+                    }
+                }
+                ConfigLine::GlobalOption { key, value, .. } if active => add(key, value),
+                ConfigLine::Include { resolved, .. } => {
+                    for included in resolved {
+                        if stack.contains(included) {
+                            continue;
+                        }
+                        stack.push(included.clone());
+                        self.add_effective(included, alias, active, add, stack);
+                        stack.pop();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Spells `path` the way an Include directive would: relative to ~/.ssh
+    // when it's inside it, `~/`-prefixed when it's elsewhere in the home
+    // directory, and absolute otherwise
+    pub fn include_path_for(&self, path: &Path) -> String {
+        if let Some(home) = &self.home {
+            if let Ok(rest) = path.strip_prefix(home.join(".ssh")) {
+                return rest.display().to_string();
+            }
+            if let Ok(rest) = path.strip_prefix(home) {
+                return format!("~/{}", rest.display());
+            }
+        }
+        path.display().to_string()
+    }
+
+    // Adds an `Include path` directive to `target_file` and parses whatever
+    // it matches right away. The directive goes before the file's first Host
+    // or Match line, since an Include inside a block only applies to it.
+    // Returns the index of the new line and how many files it pulled in; a
+    // pattern matching nothing is still added, with a warning.
+    pub fn add_include(&mut self, path: &str, target_file: &Path) -> Result<(usize, usize), String> {
+        let first_host = self.lines.iter().position(|line| {
+            matches!(line, ConfigLine::HostEntry { source_file, .. } | ConfigLine::MatchEntry { source_file, .. }
+                if source_file == target_file)
+        });
+        let index = first_host.unwrap_or_else(|| {
+            self.lines
+                .iter()
+                .rposition(|line| line.source_file() == target_file)
+                .map_or(self.lines.len(), |idx| idx + 1)
+        });
+
+        self.lines.insert(
+            index,
+            ConfigLine::Include {
+                path: path.to_string(),
+                comment: None,
+                resolved: Vec::new(),
+                source_file: target_file.to_path_buf(),
+            },
+        );
+
+        let files_before: HashSet<PathBuf> = self.included_files.keys().cloned().collect();
+        let mut files = self.parse_include(path.trim_matches('"'), target_file)?;
+        // A plain path to a file that doesn't exist yet becomes an empty
+        // managed file, so hosts can be added to it and saving creates it
+        if files.is_empty()
+            && let Some(new_file) = self.new_include_file(path.trim_matches('"'), target_file)
+        {
+            self.included_files.insert(
+                new_file.clone(),
+                IncludedFileData {
+                    content: String::new(),
+                    lines: Vec::new(),
+                },
+            );
+            files.push(new_file);
+        }
+        if let Some(ConfigLine::Include { resolved, .. }) = self.lines.get_mut(index) {
+            *resolved = files;
+        }
+        let new_files: Vec<PathBuf> = self
+            .included_files
+            .keys()
+            .filter(|file| !files_before.contains(*file))
+            .cloned()
+            .collect();
+        self.store_file_lines(&new_files);
+        let added = new_files.len();
+
+        if added == 0 {
+            self.warnings.push(ParseWarning {
+                file: target_file.to_path_buf(),
+                message: format!("Include '{}' doesn't match any new files", path),
+            });
+        }
+
+        Ok((index, added))
+    }
+
+    // Where a new file for the Include path `pattern` would go, or None for a
+    // glob or a path that's already taken
+    fn new_include_file(&self, pattern: &str, base_path: &Path) -> Option<PathBuf> {
+        if pattern.is_empty() || pattern.contains(['*', '?', '[']) {
+            return None;
+        }
+        let expanded = match pattern.strip_prefix("~/") {
+            Some(rest) => self.home.as_ref()?.join(rest),
+            None => PathBuf::from(pattern),
+        };
+        let path = if expanded.is_relative() {
+            self.include_base_dir().or_else(|| base_path.parent().map(Path::to_path_buf))?.join(expanded)
+        } else {
+            expanded
+        };
+        (!path.exists() && !self.included_files.contains_key(&path)).then_some(path)
+    }
+
+    // Swaps the host at `index` with the nearest Host or Match block in the
+    // same file in the given direction. Lines from other files keep their
+    // positions. Returns the new index of the moved host.
+    pub fn move_host(&mut self, index: usize, direction: MoveDirection) -> Option<usize> {
+        let source = match self.lines.get(index) {
+            Some(ConfigLine::HostEntry { source_file, .. }) => source_file.clone(),
+            _ => return None,
+        };
+
+        let is_sibling = |line: &ConfigLine| {
+            matches!(line, ConfigLine::HostEntry { source_file, .. } | ConfigLine::MatchEntry { source_file, .. }
+                if *source_file == source)
+        };
+
+        let target = match direction {
+            MoveDirection::Up => self.lines[..index].iter().rposition(is_sibling),
+            MoveDirection::Down => self.lines[index + 1..]
+                .iter()
+                .position(is_sibling)
+                .map(|offset| index + 1 + offset),
+        }?;
+
+        self.lines.swap(index, target);
+        Some(target)
+    }
+
+    // Turns the host at `index` into the equivalent Match block (see
+    // `host_to_match`), or adds that block after it, following a blank line,
+    // with `keep_host`. Returns the index of the Match block.
+    pub fn convert_host_to_match(&mut self, index: usize, keep_host: bool) -> Option<usize> {
+        let block = host_to_match(self.lines.get(index)?)?;
+        if !keep_host {
+            self.lines[index] = block;
+            return Some(index);
+        }
+        let source_file = block.source_file().to_path_buf();
+        self.lines.insert(index + 1, ConfigLine::Empty { source_file });
+        self.lines.insert(index + 2, block);
+        Some(index + 2)
+    }
+
+    // Turns the `Match host` block at `index` into a Host block, see
+    // `match_to_host`. Returns false if it tests anything else.
+    pub fn convert_match_to_host(&mut self, index: usize) -> bool {
+        match self.lines.get(index).and_then(match_to_host) {
+            Some(host) => {
+                self.lines[index] = host;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Indices of the host entries with a pattern matched by `glob`, itself a
+    // pattern list like `web-* !web-test`. Negated patterns of the hosts
+    // don't count, so `Host * !bastion` only matches a glob matching `*`.
+    pub fn hosts_matching(&self, glob: &str) -> Vec<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| match line {
+                ConfigLine::HostEntry { pattern, .. } => pattern::split_patterns(pattern)
+                    .filter(|p| !p.starts_with('!'))
+                    .any(|p| pattern::matches_pattern_list(glob, p)),
+                _ => false,
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    // Moves the host entries at `indices` to the end of `target_file`,
+    // keeping their order. A blank line before a host moves with it.
+    // Returns their new indices, in the same order as `indices`; anything
+    // that isn't a host entry is left alone.
+    pub fn move_hosts_to_file(&mut self, indices: &[usize], target_file: &Path) -> Vec<usize> {
+        let mut sorted: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&idx| matches!(self.lines.get(idx), Some(ConfigLine::HostEntry { .. })))
+            .collect();
+        sorted.sort_unstable();
+        sorted.dedup();
+
+        let mut moved = Vec::new();
+        for &idx in sorted.iter().rev() {
+            let mut line = self.lines.remove(idx);
+            let blank_before = idx > 0
+                && matches!(&self.lines[idx - 1], ConfigLine::Empty { source_file } if source_file == line.source_file());
+            if blank_before {
+                self.lines.remove(idx - 1);
+            }
+            if let ConfigLine::HostEntry { source_file, .. } = &mut line {
+                *source_file = target_file.to_path_buf();
+            }
+            moved.push((idx, blank_before, line));
+        }
+        moved.reverse();
+
+        let mut at = self
+            .lines
+            .iter()
+            .rposition(|line| line.source_file() == target_file)
+            .map_or(self.lines.len(), |idx| idx + 1);
+        let old_indices: Vec<usize> = moved.iter().map(|(idx, _, _)| *idx).collect();
+        let mut new_indices = Vec::new();
+        for (_, blank_before, line) in moved {
+            // Not at the very top of the file
+            if blank_before && at > 0 && self.lines[at - 1].source_file() == target_file {
+                self.lines.insert(at, ConfigLine::Empty { source_file: target_file.to_path_buf() });
+                at += 1;
+            }
+            self.lines.insert(at, line);
+            new_indices.push(at);
+            at += 1;
+        }
+
+        indices
+            .iter()
+            .filter_map(|idx| old_indices.iter().position(|old| old == idx))
+            .map(|offset| new_indices[offset])
+            .collect()
+    }
+
+    // Main file first, then the other files sorted by path: the included
+    // files plus any file a line was moved or added to, even if it wasn't
+    // there when the config was parsed
+    pub fn save_order(&self, main_path: &Path) -> Vec<PathBuf> {
+        let mut other_paths: Vec<&Path> = self
+            .included_files
+            .keys()
+            .map(PathBuf::as_path)
+            .chain(self.lines.iter().map(ConfigLine::source_file))
+            .filter(|path| *path != main_path)
+            .collect();
+        other_paths.sort();
+        other_paths.dedup();
+
+        let mut order = vec![main_path.to_path_buf()];
+        order.extend(other_paths.into_iter().map(Path::to_path_buf));
+        order
+    }
+
+    // What `save_all` would write: the content of every managed file, keyed
+    // by path. Nothing is written to disk.
+    pub fn save_all_to_map(&self, main_path: &Path) -> HashMap<PathBuf, String> {
+        self.save_order(main_path)
+            .into_iter()
+            .map(|path| {
+                let content = self.to_string(&path);
+                (path, content)
+            })
+            .collect()
+    }
+
+    // Writes the main file and every edited or not yet existing file in
+    // `save_order`, creating new files as needed. A file that can't be
+    // written (e.g. a read-only system include, or one whose directory
+    // doesn't exist) is skipped and reported instead of stopping the others
+    // from being saved; missing directories are left to the caller to
+    // create, see `ensure_ssh_dir`. The written text is remembered so
+    // `changed_on_disk` compares against it.
+    //
+    // Files in `lossy_files` keep their original bytes: they're only written
+    // once edited, and even then reported as skipped, see `save_all_with`.
+    pub fn save_all(&mut self, main_path: &Path) -> SaveReport {
+        self.save_all_with(main_path, false)
+    }
+
+    // Like `save_all`; with `write_lossy` edited files in `lossy_files` are
+    // written too, U+FFFD and all, once the user agreed to lose their
+    // invalid bytes (see `modified_lossy_files`)
+    pub fn save_all_with(&mut self, main_path: &Path, write_lossy: bool) -> SaveReport {
+        let order = self.save_order(main_path);
+        let mut contents = self.save_all_to_map(main_path);
+        let mut report = SaveReport::default();
+
+        for path in order {
+            let content = contents.remove(&path).unwrap_or_default();
+            let modified = self.file_modified(&path);
+            if self.lossy_files.contains(&path) {
+                if !modified {
+                    continue;
+                }
+                if !write_lossy {
+                    let reason = "contains invalid UTF-8 that saving would replace with U+FFFD".to_string();
+                    report.skipped.push((path, reason));
+                    continue;
+                }
+            }
+            // Included files nobody touched are left alone, so one that
+            // can't be written (say a read-only system include) doesn't get
+            // in the way of saving
+            if !modified && path != main_path && path.exists() {
+                continue;
+            }
+            let written = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => {
+                    Err(format!("directory {} does not exist", dir.display()))
+                }
+                _ => Ok(()),
+            }
+            .and_then(|()| check_writable(&path))
+            .and_then(|()| fs::write(&path, &content).map_err(|e| e.to_string()));
+            if let Err(reason) = written {
+                report.skipped.push((path, reason));
+                continue;
+            }
+            self.lossy_files.remove(&path);
+            match self.included_files.get_mut(&path) {
+                Some(data) => data.content = content,
+                None if path == main_path => self.main_content = content,
+                // A file that only existed as the source of new lines is
+                // managed like an included one from now on
+                None => {
+                    self.included_files.insert(
+                        path.clone(),
+                        IncludedFileData {
+                            content,
+                            lines: Vec::new(),
+                        },
+                    );
+                }
+            }
+            if !self.file_order.contains(&path) {
+                self.file_order.push(path.clone());
+            }
+            report.written.push(path);
+        }
+        self.store_file_lines(&report.written);
+
+        report
+    }
+
+    // Edited files in `lossy_files`, which saving would write with U+FFFD in
+    // place of their invalid bytes
+    pub fn modified_lossy_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .lossy_files
+            .iter()
+            .filter(|path| self.file_modified(path))
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    // Lines of the main file or an included file as they were last loaded or
+    // saved, or None if `path` is neither
+    pub fn file_lines(&self, path: &Path) -> Option<&[ConfigLine]> {
+        if self.file_order.first().is_some_and(|main| main == path) {
+            return Some(&self.main_lines);
+        }
+        self.included_files.get(path).map(|data| data.lines.as_slice())
+    }
+
+    // Whether a file's lines differ from `file_lines`. A file that was never
+    // loaded or saved, like a new target for moved hosts, counts as modified.
+    pub fn file_modified(&self, path: &Path) -> bool {
+        self.file_lines(path).is_none_or(|stored| {
+            !self
+                .lines
+                .iter()
+                .filter(|line| line.source_file() == path)
+                .eq(stored.iter())
+        })
+    }
+
+    // Remembers the current lines of `files` as they were loaded or saved,
+    // see `file_lines`
+    fn store_file_lines(&mut self, files: &[PathBuf]) {
+        for file in files {
+            let lines: Vec<ConfigLine> = self
+                .lines
+                .iter()
+                .filter(|line| line.source_file() == file)
+                .cloned()
+                .collect();
+            if self.file_order.first() == Some(file) {
+                self.main_lines = lines;
+            } else if let Some(data) = self.included_files.get_mut(file) {
+                data.lines = lines;
+            }
+        }
+    }
+}
+
+// Host entry for an `ssh://[user@]host[:port]` URL (RFC 4248 style, as
+// shown by many hosting dashboards). The alias is the first label of the
+// host name, or the whole address for IPs. None if `url` isn't such a URL.
+pub fn host_from_ssh_url(url: &str, source_file: &Path) -> Option<ConfigLine> {
+    let url = url.trim();
+    let scheme = "ssh://";
+    if !url.get(..scheme.len())?.eq_ignore_ascii_case(scheme) {
+        return None;
+    }
+    let rest = &url[scheme.len()..];
+    // Anything after the authority (a path) means nothing to ssh
+    let authority = rest.split('/').next().unwrap_or(rest);
+
+    let (user, host_port) = match authority.rsplit_once('@') {
+        // Drop connection parameters like `;fingerprint=...`
+        Some((user, host_port)) => (Some(user.split(';').next().unwrap_or(user)), host_port),
+        None => (None, authority),
+    };
+    let (host, port) = if let Some(bracketed) = host_port.strip_prefix('[') {
+        let (host, after) = bracketed.split_once(']')?;
+        (host, after.strip_prefix(':'))
+    } else {
+        match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        }
+    };
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return None;
+    }
+    let port = match port {
+        Some(port) => Some(port.parse::<u16>().ok()?),
+        None => None,
+    };
+
+    let is_ip = host.contains(':') || host.parse::<std::net::Ipv4Addr>().is_ok();
+    let alias = if is_ip { host } else { host.split('.').next().unwrap_or(host) };
+
+    let mut options = vec![("HostName".to_string(), host.to_string())];
+    if let Some(user) = user.filter(|u| !u.is_empty()) {
+        options.push(("User".to_string(), user.to_string()));
+    }
+    if let Some(port) = port {
+        options.push(("Port".to_string(), port.to_string()));
+    }
+    Some(ConfigLine::HostEntry {
+        pattern: alias.to_string(),
+        comment: None,
+        tags: Vec::new(),
+        options,
+        option_comments: Vec::new(),
+        source_file: source_file.to_path_buf(),
+    })
+}
+
+// Match block testing `host` against the patterns of a Host block, with its
+// comment, tags and options: `Host web !web-test` becomes
+// `Match host web,!web-test`. ssh tests `Match host` against the name after
+// HostName is applied, so for a host whose HostName differs from its alias
+// the block may stop applying. None if `line` isn't a host entry.
+pub fn host_to_match(line: &ConfigLine) -> Option<ConfigLine> {
+    let ConfigLine::HostEntry {
+        pattern,
+        comment,
+        tags,
+        options,
+        option_comments,
+        source_file,
+    } = line
+    else {
+        return None;
+    };
+    let patterns: Vec<&str> = pattern::split_patterns(pattern).collect();
+    if patterns.is_empty() {
+        return None;
+    }
+    Some(ConfigLine::MatchEntry {
+        criteria: format!("host {}", patterns.join(",")),
+        comment: comment.clone(),
+        tags: tags.clone(),
+        options: options.clone(),
+        option_comments: option_comments.clone(),
+        source_file: source_file.clone(),
+    })
+}
+
+// Host block for a Match block whose only criterion is `host`, the inverse
+// of `host_to_match`. None for any other line or criteria.
+pub fn match_to_host(line: &ConfigLine) -> Option<ConfigLine> {
+    let ConfigLine::MatchEntry {
+        criteria,
+        comment,
+        tags,
+        options,
+        option_comments,
+        source_file,
+    } = line
+    else {
+        return None;
+    };
+    Some(ConfigLine::HostEntry {
+        pattern: match_host_patterns(criteria)?,
+        comment: comment.clone(),
+        tags: tags.clone(),
+        options: options.clone(),
+        option_comments: option_comments.clone(),
+        source_file: source_file.clone(),
+    })
+}
+
+// Host patterns of Match criteria that are just `host <list>`, separated by
+// spaces like on a Host line: `web web-2` for `host web,web-2`
+pub fn match_host_patterns(criteria: &str) -> Option<String> {
+    let [keyword, list] = criteria.split_whitespace().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    if !keyword.eq_ignore_ascii_case("host") {
+        return None;
+    }
+    let patterns: Vec<&str> = pattern::split_patterns(list.trim_matches('"')).collect();
+    (!patterns.is_empty()).then(|| patterns.join(" "))
+}
+
+// Flattens text pasted into a single-line field so it can't break the line
+// it ends up on: line breaks are dropped and tabs or other control characters
+// become spaces. None if there was nothing to change.
+pub fn sanitize_single_line(text: &str) -> Option<String> {
+    if !text.contains(char::is_control) {
+        return None;
+    }
+    Some(
+        text.chars()
+            .filter(|c| !matches!(c, '\n' | '\r'))
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect(),
+    )
+}
+
+// Fails with the reason if an existing file can't be opened for writing.
+// Opening without truncating leaves the file untouched.
+pub fn check_writable(path: &Path) -> Result<(), String> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => Err("file is read-only".to_string()),
+        Ok(_) => fs::OpenOptions::new().write(true).open(path).map(|_| ()).map_err(|e| e.to_string()),
+        Err(_) => Ok(()),
+    }
+}
+
+// Where ssh reads its machine-wide client config from, after the user's
+pub fn system_config_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("ssh").join("ssh_config"))
+    } else {
+        Some(PathBuf::from("/etc/ssh/ssh_config"))
+    }
+}
+
+// Guess whether `path` is machine-wide config rather than the user's own:
+// anything under /etc (%ProgramData% on Windows) or any absolute path outside
+// `home`. Relative paths, and every path when the home directory is unknown,
+// count as the user's.
+pub fn is_system_path(path: &Path, home: Option<&Path>) -> bool {
+    if !path.is_absolute() {
+        return false;
+    }
+    let system_dir = if cfg!(windows) {
+        std::env::var_os("ProgramData").map(PathBuf::from)
+    } else {
+        Some(PathBuf::from("/etc"))
+    };
+    if system_dir.is_some_and(|dir| path.starts_with(dir)) {
+        return true;
+    }
+    home.is_some_and(|home| !path.starts_with(home))
+}
+
+// Checks the shape of an `apply_json` document, and that every `file` is
+// one of `managed`. Errors name the offending element, e.g.
+// `hosts[2].options.Port: expected a string`.
+fn parse_json_hosts(value: &serde_json::Value, managed: &[&Path]) -> Result<Vec<JsonHost>, String> {
+    let hosts = value
+        .get("hosts")
+        .ok_or("expected an object with a \"hosts\" list")?
+        .as_array()
+        .ok_or("hosts: expected a list")?;
+
+    let mut parsed = Vec::new();
+    for (idx, host) in hosts.iter().enumerate() {
+        let at = format!("hosts[{}]", idx);
+        if !host.is_object() {
+            return Err(format!("{}: expected an object", at));
+        }
+        let pattern = host
+            .get("pattern")
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| format!("{}.pattern: expected a string", at))?
+            .trim();
+        pattern::check_pattern_list(pattern).map_err(|e| format!("{}.pattern: {}", at, e))?;
+        let file = match host.get("file") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(file) => {
+                let file = Path::new(file.as_str().ok_or_else(|| format!("{}.file: expected a string", at))?);
+                if !managed.contains(&file) {
+                    return Err(format!("{}.file: {} is not the main file or an included file", at, file.display()));
+                }
+                Some(file.to_path_buf())
+            }
+        };
+
+        let options: Vec<(String, String)> = match host.get("options") {
+            None | Some(serde_json::Value::Null) => Vec::new(),
+            Some(serde_json::Value::Object(map)) => map
+                .iter()
+                .map(|(key, value)| {
+                    let value = value.as_str().ok_or_else(|| format!("{}.options.{}: expected a string", at, key))?;
+                    Ok((key.clone(), value.to_string()))
+                })
+                .collect::<Result<_, String>>()?,
+            Some(serde_json::Value::Array(pairs)) => pairs
+                .iter()
+                .enumerate()
+                .map(|(pair_idx, pair)| match pair.as_array().map(Vec::as_slice) {
+                    Some([serde_json::Value::String(key), serde_json::Value::String(value)]) => {
+                        Ok((key.clone(), value.clone()))
+                    }
+                    _ => Err(format!("{}.options[{}]: expected [key, value] strings", at, pair_idx)),
+                })
+                .collect::<Result<_, String>>()?,
+            Some(_) => return Err(format!("{}.options: expected an object or a list of [key, value]", at)),
+        };
+        for (key, value) in &options {
+            if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == '=') {
+                return Err(format!("{}.options: '{}' is not a keyword", at, key));
+            }
+            if value.contains(['\n', '\r']) {
+                return Err(format!("{}.options.{}: values can't span lines", at, key));
+            }
+        }
+
+        parsed.push(JsonHost {
+            pattern: pattern.to_string(),
+            file,
+            options: options.into_iter().map(|(key, value)| (key, trim_value(&value).to_string())).collect(),
+        });
+    }
+    Ok(parsed)
+}
+
+// Keeps a host's `option_comments` lined up after inserting an option at
+// `index`
+pub fn insert_option_comment(option_comments: &mut Vec<String>, index: usize, comment: String) {
+    if index <= option_comments.len() {
+        option_comments.insert(index, comment);
+    } else if !comment.is_empty() {
+        option_comments.resize(index, String::new());
+        option_comments.push(comment);
+    }
+}
+
+// Keeps a host's `option_comments` lined up after removing the option at
+// `index`, returning the removed option's comment
+pub fn remove_option_comment(option_comments: &mut Vec<String>, index: usize) -> String {
+    if index < option_comments.len() { option_comments.remove(index) } else { String::new() }
+}
+
+fn lines_by_file(lines: &[ConfigLine]) -> HashMap<&Path, Vec<&ConfigLine>> {
+    let mut by_file: HashMap<&Path, Vec<&ConfigLine>> = HashMap::new();
+    for line in lines {
+        by_file.entry(line.source_file()).or_default().push(line);
+    }
+    by_file
+}
+
+// Files matched by an include path, which may be a glob pattern, and what
+// went wrong while matching (a malformed pattern, unreadable directories)
+fn resolve_include_files(include_path: &Path) -> (Vec<PathBuf>, Vec<String>) {
+    let pattern_str = include_path.to_string_lossy().to_string();
+    match glob::glob(&pattern_str) {
+        Ok(paths) => {
+            let mut files = Vec::new();
+            let mut problems = Vec::new();
+            for entry in paths {
+                match entry {
+                    Ok(path) if path.is_file() => files.push(path),
+                    Ok(_) => {}
+                    Err(e) => problems.push(format!("can't read {}: {}", e.path().display(), e.error())),
+                }
+            }
+            (files, problems)
+        }
+        // A file whose name merely looks like a broken pattern is fine
+        Err(_) if include_path.is_file() => (vec![include_path.to_path_buf()], Vec::new()),
+        Err(e) => (Vec::new(), vec![format!("invalid pattern ({})", e.msg)]),
+    }
+}
+
+// Creates `dir` (and any missing parents) if it doesn't exist. On unix the
+// directory is restricted to its owner (0700), as OpenSSH expects for
+// `~/.ssh`. Returns whether the directory had to be created.
+pub fn ensure_ssh_dir(dir: &Path) -> Result<bool, String> {
+    if dir.is_dir() {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+
+    Ok(true)
+}
+
+// Describes the problem if `dir` is accessible to group or others
+#[cfg(unix)]
+pub fn loose_permissions_warning(dir: &Path) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(dir).ok()?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        Some(format!(
+            "Directory permissions are {:o}; SSH recommends 700 (chmod 700 {})",
+            mode,
+            dir.display()
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+pub fn loose_permissions_warning(_dir: &Path) -> Option<String> {
+    None
+}
+
+// Local path an IdentityFile value points at. `~/` and relative paths are
+// taken from `home`; values using `%` tokens or environment variables can't
+// be resolved without ssh and are skipped.
+pub fn identity_file_path(value: &str, home: Option<&Path>) -> Option<PathBuf> {
+    if keywords::is_none_value(value) {
+        return None;
+    }
+    let value = value.trim_matches('"');
+    if value.is_empty() || value.contains('%') || value.contains('$') {
+        return None;
+    }
+
+    let home = home?;
+    let path = match value.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None => PathBuf::from(value),
+    };
+    Some(if path.is_relative() { home.join(path) } else { path })
+}
+
+// Permission bits of a private key that group or others can access, which
+// makes ssh refuse to use it. None if the key is fine or doesn't exist.
+#[cfg(unix)]
+pub fn open_key_permissions(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 { Some(mode) } else { None }
+}
+
+#[cfg(not(unix))]
+pub fn open_key_permissions(_path: &Path) -> Option<u32> {
+    None
+}
+
+// Restricts a private key to its owner (0600)
+#[cfg(unix)]
+pub fn restrict_key_permissions(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(not(unix))]
+pub fn restrict_key_permissions(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+fn write_line(result: &mut String, line: &ConfigLine, format: &LineFormat, include_comments: bool) {
+    let no_comment = None;
+    let trailing = |comment| if include_comments { comment } else { &no_comment };
+    match line {
+        ConfigLine::Comment { .. } | ConfigLine::Empty { .. } if !include_comments => {}
+        ConfigLine::Comment { text, .. } => {
+            result.push_str(text);
+            result.push('\n');
+        }
+        ConfigLine::Empty { .. } => {
+            result.push('\n');
+        }
+        ConfigLine::Include { path, comment, .. } => {
+            push_key_value(result, "Include", path, format);
+            push_trailing_comment(result, trailing(comment));
+            result.push('\n');
+        }
+        ConfigLine::HostEntry {
+            pattern,
+            comment,
+            tags,
+            options,
+            option_comments,
+            ..
+        }
+        | ConfigLine::MatchEntry {
+            criteria: pattern,
+            comment,
+            tags,
+            options,
+            option_comments,
+            ..
+        } => {
+            if !tags.is_empty() && include_comments {
+                result.push_str(&tags::format_tags_comment(tags));
+                result.push('\n');
+            }
+            let keyword = if matches!(line, ConfigLine::MatchEntry { .. }) { "Match" } else { "Host" };
+            push_key_value(result, keyword, pattern, format);
+            push_trailing_comment(result, trailing(comment));
+            result.push('\n');
+            for (idx, (key, value)) in options.iter().enumerate() {
+                if let Some(option_comment) = option_comments.get(idx)
+                    && include_comments
+                {
+                    result.push_str(option_comment);
+                }
+                result.push_str(&format.indent);
+                push_key_value(result, key, value, format);
+                result.push('\n');
+            }
+        }
+        ConfigLine::GlobalOption { key, value, .. } => {
+            push_key_value(result, key, value, format);
+            result.push('\n');
+        }
+    }
+}
+
+// Writes `key value`, or just `key` for a bare keyword
+fn push_key_value(result: &mut String, key: &str, value: &str, format: &LineFormat) {
+    result.push_str(key);
+    if !value.is_empty() {
+        result.push_str(&format.separator);
+        result.push_str(value);
+    }
+}
+
+// Splits a line into its keyword and the rest after the separator, which is
+// whitespace, `=` or both (`Port 22`, `Port=22`, `Port = 22`) like in ssh.
+// The rest is returned untrimmed.
+pub fn split_keyword(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let (key, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    (key, rest.strip_prefix('=').unwrap_or(rest))
+}
+
+// Tab-separated files (`Port\t\t2222`) are written back with a single tab so
+// they stay tab-separated, and `Key=value` files keep their `=`; anything
+// else gets a single space.
+fn detect_separator(gap: &str) -> String {
+    if gap.contains('=') {
+        gap.to_string()
+    } else if gap.contains('\t') {
+        "\t".to_string()
+    } else {
+        " ".to_string()
+    }
+}
+
+// Trims the whitespace around an option value like ssh does, except that
+// text inside an unterminated double quote is kept byte for byte. Text inside
+// a closed quote is never touched since only the outer ends are trimmed.
+pub fn trim_value(value: &str) -> &str {
+    let value = value.trim_start();
+    let unterminated_quote = value.chars().filter(|&c| c == '"').count() % 2 == 1;
+    if unterminated_quote { value } else { value.trim_end() }
+}
+
+// Splits a header value like `web # main server` into the value and the
+// trailing comment text. A `#` only starts a comment at the beginning of a
+// word and outside of double quotes, matching how OpenSSH tokenizes lines.
+fn split_trailing_comment(value: &str) -> (&str, Option<&str>) {
+    let mut in_quotes = false;
+    let mut prev_is_space = true;
+
+    for (idx, c) in value.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '#' if !in_quotes && prev_is_space => {
+                let comment = value[idx + 1..].trim();
+                return (value[..idx].trim_end(), Some(comment));
+            }
+            _ => {}
+        }
+        prev_is_space = c.is_whitespace();
+    }
+
+    (value, None)
+}
+
+// Always written as ` # text`. Only the text is kept, since it's edited on
+// its own in the UI, so `Host web  #main` comes back as `Host web # main`;
+// the usual single-space form round-trips unchanged.
+fn push_trailing_comment(result: &mut String, comment: &Option<String>) {
+    if let Some(comment) = comment {
+        result.push_str(" #");
+        if !comment.is_empty() {
+            result.push(' ');
+            result.push_str(comment);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> SshConfig {
+        SshConfig::parse_str(text, "config").unwrap()
+    }
+
+    fn round_trip(text: &str) -> String {
+        parse(text).to_string(Path::new("config"))
+    }
+
+    // Writes `text` to `name` in `dir` and returns its path
+    fn write(dir: &Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn host_index(config: &SshConfig, pattern: &str) -> usize {
+        config.find_host_index(pattern).unwrap()
+    }
+
+    #[test]
+    fn host_trailing_comment_round_trips() {
+        let text = "Host web # main server\n    HostName web.example.com\n";
+        let config = parse(text);
+        let host = config.hosts().next().unwrap();
+        assert_eq!(host.pattern, "web");
+        assert_eq!(host.comment, Some("main server"));
+        assert_eq!(config.to_string(Path::new("config")), text);
+    }
+
+    #[test]
+    fn hash_inside_quoted_pattern_is_not_a_comment() {
+        let config = parse("Host \"we#b\" # note\n");
+        let host = config.hosts().next().unwrap();
+        assert_eq!(host.pattern, "\"we#b\"");
+        assert_eq!(host.comment, Some("note"));
+    }
+
+    #[test]
+    fn trailing_comment_spacing_is_normalized() {
+        assert_eq!(round_trip("Host web   #main\n"), "Host web # main\n");
+        assert_eq!(round_trip("Include extra #\n"), "Include extra #\n");
+    }
+
+    #[test]
+    fn moving_a_host_only_reorders_its_own_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = write(dir.path(), "extra", "Host x\nHost y\n");
+        let main = write(dir.path(), "config", &format!("Include {}\nHost a\nHost b\n", extra.display()));
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        let extra_before = config.to_string(&extra);
+
+        let b = host_index(&config, "b");
+        let moved = config.move_host(b, MoveDirection::Up).unwrap();
+
+        assert_eq!(moved, host_index(&config, "b"));
+        assert_eq!(config.to_string(&main), format!("Include {}\nHost b\nHost a\n", extra.display()));
+        assert_eq!(config.to_string(&extra), extra_before);
+        // Nothing above `b` in its own file once it's first
+        assert_eq!(config.move_host(moved, MoveDirection::Up), None);
+    }
+
+    fn merge(policy: MergePolicy) -> (String, MergeReport) {
+        let mut mine = parse("Host web\n    User me\n");
+        let team = parse("Host web\n    User team\nHost db\n    Port 2222\n");
+        let report = mine.merge_from(&team, Path::new("config"), policy);
+        (mine.to_string(Path::new("config")), report)
+    }
+
+    #[test]
+    fn merge_skip_keeps_existing_hosts() {
+        let (text, report) = merge(MergePolicy::Skip);
+        assert_eq!(text, "Host web\n    User me\nHost db\n    Port 2222\n");
+        assert_eq!(report, MergeReport { added: 1, skipped: 1, overwritten: 0, renamed: 0 });
+    }
+
+    #[test]
+    fn merge_overwrite_replaces_existing_options() {
+        let (text, report) = merge(MergePolicy::Overwrite);
+        assert_eq!(text, "Host web\n    User team\nHost db\n    Port 2222\n");
+        assert_eq!(report, MergeReport { added: 1, skipped: 0, overwritten: 1, renamed: 0 });
+    }
+
+    #[test]
+    fn merge_rename_adds_conflicting_hosts_under_a_new_pattern() {
+        let (text, report) = merge(MergePolicy::Rename);
+        assert_eq!(text, "Host web\n    User me\nHost web-2\n    User team\nHost db\n    Port 2222\n");
+        assert_eq!(report, MergeReport { added: 2, skipped: 0, overwritten: 0, renamed: 1 });
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_and_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        fs::write(&path, b"# caf\xe9\nHost web\n    Port 22\n").unwrap();
+
+        let mut config = SshConfig::parse_file(&path).unwrap();
+        assert_eq!(config.hosts().next().unwrap().pattern, "web");
+        assert!(config.lossy_files.contains(&path));
+        assert!(config.warnings.iter().any(|w| w.file == path && w.message.contains("UTF-8")));
+        assert_eq!(config.to_string(&path), "# caf\u{FFFD}\nHost web\n    Port 22\n");
+
+        // Unedited, the file keeps its bytes
+        let report = config.save_all(&path);
+        assert_eq!(report, SaveReport::default());
+        assert_eq!(fs::read(&path).unwrap(), b"# caf\xe9\nHost web\n    Port 22\n");
+
+        // Edited, it's only written once confirmed, with the replacement
+        // character, after which it's valid UTF-8 and no longer lossy
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("web") {
+            options[0].1 = "2222".to_string();
+        }
+        assert_eq!(config.modified_lossy_files(), std::slice::from_ref(&path));
+        let report = config.save_all(&path);
+        assert!(report.written.is_empty());
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(fs::read(&path).unwrap(), b"# caf\xe9\nHost web\n    Port 22\n");
+
+        let report = config.save_all_with(&path, true);
+        assert_eq!(report.written, std::slice::from_ref(&path));
+        assert!(config.lossy_files.is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# caf\u{FFFD}\nHost web\n    Port 2222\n");
+    }
+
+    #[test]
+    fn latin1_include_survives_saving_the_main_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let include = dir.path().join("latin1");
+        let bytes = b"# Jos\xe9's hosts\nHost old\n    User jos\xe9\n";
+        fs::write(&include, bytes).unwrap();
+        let main = write(dir.path(), "config", &format!("Include {}\nHost web\n", include.display()));
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        assert!(config.lossy_files.contains(&include));
+
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("web") {
+            options.push(("Port".to_string(), "2222".to_string()));
+        }
+        let report = config.save_all(&main);
+
+        assert_eq!(report, SaveReport { written: vec![main.clone()], skipped: Vec::new() });
+        assert_eq!(fs::read(&include).unwrap(), bytes);
+        assert_eq!(fs::read_to_string(&main).unwrap(), format!("Include {}\nHost web\n    Port 2222\n", include.display()));
+    }
+
+    #[test]
+    fn ensure_ssh_dir_creates_missing_directories_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let ssh_dir = dir.path().join("home").join(".ssh");
+
+        assert_eq!(ensure_ssh_dir(&ssh_dir), Ok(true));
+        assert!(ssh_dir.is_dir());
+        assert_eq!(ensure_ssh_dir(&ssh_dir), Ok(false));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&ssh_dir).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o700);
+            assert_eq!(loose_permissions_warning(&ssh_dir), None);
+        }
+    }
+
+    #[test]
+    fn save_all_skips_files_in_missing_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("config");
+        let missing = dir.path().join("conf.d").join("work");
+        let mut config = SshConfig::parse_str("Host web\n", &main).unwrap();
+        config.lines.push(ConfigLine::GlobalOption {
+            key: "User".to_string(),
+            value: "me".to_string(),
+            source_file: missing.clone(),
+        });
+
+        let report = config.save_all(&main);
+        assert_eq!(report.written, std::slice::from_ref(&main));
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, missing);
+        assert!(!missing.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn adding_an_include_pulls_in_its_hosts() {
+        let home = tempfile::tempdir().unwrap();
+        let ssh_dir = home.path().join(".ssh");
+        fs::create_dir_all(ssh_dir.join("conf.d")).unwrap();
+        let main = write(&ssh_dir, "config", "Host main\n");
+        let work = write(&ssh_dir.join("conf.d"), "work", "Host work\n    User me\n");
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        config.home = Some(home.path().to_path_buf());
+
+        let include = config.include_path_for(&work);
+        assert_eq!(include, "conf.d/work");
+        assert_eq!(config.add_include(&include, &main), Ok((0, 1)));
+
+        let work_host = config.hosts().find(|host| host.pattern == "work").unwrap();
+        assert_eq!(work_host.source_file, work);
+        assert_eq!(config.to_string(&main), "Include conf.d/work\nHost main\n");
+    }
+
+    #[test]
+    fn include_paths_are_spelled_relative_to_home() {
+        let mut config = SshConfig::new();
+        config.home = Some(PathBuf::from("/home/me"));
+        assert_eq!(config.include_path_for(Path::new("/home/me/.ssh/conf.d/*")), "conf.d/*");
+        assert_eq!(config.include_path_for(Path::new("/home/me/dotfiles/ssh")), "~/dotfiles/ssh");
+        assert_eq!(config.include_path_for(Path::new("/etc/ssh/extra")), "/etc/ssh/extra");
+    }
+
+    fn pattern_at(config: &SshConfig, index: usize) -> &str {
+        match &config.lines[index] {
+            ConfigLine::HostEntry { pattern, .. } => pattern,
+            other => panic!("not a host: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn host_handle_follows_its_host_through_mutations() {
+        let mut config = parse("Host a\nHost b\nHost c\n");
+        let handle = config.host_handle(host_index(&config, "b")).unwrap();
+
+        config.lines.insert(0, ConfigLine::Empty { source_file: PathBuf::from("config") });
+        let index = config.resolve_host(&handle).unwrap();
+        assert_eq!(pattern_at(&config, index), "b");
+
+        let a = host_index(&config, "a");
+        config.move_host(a, MoveDirection::Down);
+        let index = config.resolve_host(&handle).unwrap();
+        assert_eq!(pattern_at(&config, index), "b");
+
+        config.lines.remove(index);
+        assert_eq!(config.resolve_host(&handle), None);
+    }
+
+    #[test]
+    fn host_handle_survives_reload_or_clears() {
+        let config = parse("Host x\nHost web\nHost x\n");
+        let second_x = config.host_handle(2).unwrap();
+        let web = config.host_handle(1).unwrap();
+
+        // Reloaded with a host added in front: both still found
+        let reloaded = parse("Host new\nHost x\nHost web\nHost x\n");
+        assert_eq!(reloaded.resolve_host(&second_x), Some(3));
+        assert_eq!(pattern_at(&reloaded, reloaded.resolve_host(&web).unwrap()), "web");
+
+        // Reloaded without the second `x` or `web`: cleared rather than
+        // pointing at the first `x`
+        let reloaded = parse("Host x\nHost db\n");
+        assert_eq!(reloaded.resolve_host(&second_x), None);
+        assert_eq!(reloaded.resolve_host(&web), None);
+    }
+
+    #[test]
+    fn quoted_values_keep_inner_whitespace_while_unquoted_ones_are_trimmed() {
+        let config = parse("Host h\n    Foo \"bar \"\n    Port 22   \n    RemoteCommand \"echo hi \n");
+        let host = config.hosts().next().unwrap();
+        assert_eq!(
+            host.options,
+            [
+                ("Foo".to_string(), "\"bar \"".to_string()),
+                ("Port".to_string(), "22".to_string()),
+                ("RemoteCommand".to_string(), "\"echo hi ".to_string()),
+            ]
+        );
+        assert_eq!(trim_value("  \"a b \"  "), "\"a b \"");
+    }
+
+    #[test]
+    fn leading_bom_is_stripped_and_not_written_back() {
+        let config = parse("\u{FEFF}Host web\n    Port 22\n");
+        assert_eq!(config.hosts().next().unwrap().pattern, "web");
+        assert_eq!(config.to_string(Path::new("config")), "Host web\n    Port 22\n");
+    }
+
+    #[test]
+    fn hosts_differing_only_in_case_are_duplicates() {
+        let config = parse("Host Web\n    Port 22\nHost web\n    Port 2222\n");
+        assert_eq!(config.files_with_duplicate_hosts(), [PathBuf::from("config")]);
+        assert_eq!(config.find_host_index("WEB"), Some(0));
+        // The original casing is kept
+        assert_eq!(config.to_string(Path::new("config")), "Host Web\n    Port 22\nHost web\n    Port 2222\n");
+    }
+
+    #[test]
+    fn included_files_inherit_and_restore_the_block_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = write(dir.path(), "extra", "User team\nHost other\n    Port 2222\n");
+        let main = write(
+            dir.path(),
+            "config",
+            &format!("Host web\nInclude {}\nCompression yes\n\nHost db\n", extra.display()),
+        );
+        let config = SshConfig::parse_file(&main).unwrap();
+        let option = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+        // Included inside `Host web`, the file's leading global only applies
+        // to web, and once it's read web's block goes on, not `Host other`'s
+        assert_eq!(config.effective_options("web"), [option("User", "team"), option("Compression", "yes")]);
+        assert_eq!(config.effective_options("db"), []);
+        assert_eq!(config.effective_options("other"), [option("Port", "2222")]);
+    }
+
+    #[test]
+    fn system_config_starts_outside_of_any_host_block() {
+        let user = parse("Host db\n    Port 2222\n");
+        let system = SshConfig::parse_str("User admin\nHost *\n    Port 22\n", "/etc/ssh/ssh_config").unwrap();
+        assert_eq!(
+            user.effective_options_with_system("web", Some(&system)),
+            [("User".to_string(), "admin".to_string()), ("Port".to_string(), "22".to_string())]
+        );
+        assert_eq!(
+            user.effective_options_with_system("db", Some(&system)),
+            [("Port".to_string(), "2222".to_string()), ("User".to_string(), "admin".to_string())]
+        );
+    }
+
+    #[test]
+    fn user_host_options_win_over_system_globals() {
+        let user = parse("Host web\n    Port 2222\n    IdentityFile ~/.ssh/web\n\nHost *\n    User me\n");
+        let system = SshConfig::parse_str(
+            "Port 22\nUser admin\nIdentityFile ~/.ssh/id_ed25519\nHost *\n    ForwardAgent no\n",
+            "/etc/ssh/ssh_config",
+        )
+        .unwrap();
+        let effective = |alias| -> Vec<String> {
+            user.effective_options_with_system(alias, Some(&system))
+                .into_iter()
+                .map(|(key, value)| format!("{} {}", key, value))
+                .collect()
+        };
+        // Repeatable keywords add up, the user's first
+        assert_eq!(
+            effective("web"),
+            [
+                "Port 2222",
+                "IdentityFile ~/.ssh/web",
+                "User me",
+                "IdentityFile ~/.ssh/id_ed25519",
+                "ForwardAgent no"
+            ]
+        );
+        assert_eq!(effective("db"), ["User me", "Port 22", "IdentityFile ~/.ssh/id_ed25519", "ForwardAgent no"]);
+    }
+
+    #[test]
+    fn reload_only_rereads_the_changed_include() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(dir.path(), "a", "Host a\n    Port 1\n");
+        let b = write(dir.path(), "b", "Host b\n    Port 2\n");
+        let main = write(dir.path(), "config", &format!("Include {}\nInclude {}\n", a.display(), b.display()));
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        assert_eq!(config.changed_on_disk(), Vec::<PathBuf>::new());
+
+        // An unsaved edit to `b`, which a full reparse would lose
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("b") {
+            options[0].1 = "22".to_string();
+        }
+        fs::write(&a, "Host a\n    Port 11\nHost a2\n").unwrap();
+
+        assert_eq!(config.reload_changed(), Ok(vec![a.clone()]));
+        assert_eq!(config.to_string(&a), "Host a\n    Port 11\nHost a2\n");
+        assert_eq!(config.to_string(&b), "Host b\n    Port 22\n");
+        assert_eq!(config.file_lines(&a).unwrap().len(), 2);
+        assert_eq!(config.changed_on_disk(), Vec::<PathBuf>::new());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_include_is_skipped_while_the_main_file_saves() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let system = write(dir.path(), "system", "Host sys\n    Port 22\n");
+        let main = write(dir.path(), "config", &format!("Include {}\nHost web\n", system.display()));
+        fs::set_permissions(&system, fs::Permissions::from_mode(0o444)).unwrap();
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        for pattern in ["sys", "web"] {
+            if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern(pattern) {
+                options.push(("User".to_string(), "me".to_string()));
+            }
+        }
+
+        assert_eq!(check_writable(&system), Err("file is read-only".to_string()));
+        let report = config.save_all(&main);
+        assert_eq!(report.written, std::slice::from_ref(&main));
+        assert_eq!(report.skipped, [(system.clone(), "file is read-only".to_string())]);
+        assert!(fs::read_to_string(&main).unwrap().ends_with("Host web\n    User me\n"));
+        assert_eq!(fs::read_to_string(&system).unwrap(), "Host sys\n    Port 22\n");
+    }
+
+    #[test]
+    fn exported_json_round_trips() {
+        let text = "# team hosts\nCompression yes\n\n# @tags: prod\nHost web # main\n    HostName web.example.com\n    LocalForward 8080 localhost:80\n    LocalForward 8443 localhost:443\nHost db\n    Port 2222\n";
+        let config = parse(text);
+        let json = serde_json::to_value(&config.lines).unwrap();
+
+        let lines: Vec<ConfigLine> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(lines, config.lines);
+
+        // The exported host objects are also accepted by `apply_json`
+        let hosts: Vec<serde_json::Value> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|line| line["type"] == "HostEntry")
+            .cloned()
+            .collect();
+        let mut imported = SshConfig::new();
+        let report = imported
+            .apply_json(&serde_json::json!({ "hosts": hosts }), Path::new("config"), UpsertPolicy::Update)
+            .unwrap();
+        assert_eq!(report, UpsertReport { created: 2, updated: 0, unchanged: 0 });
+        let patterns_and_options = |config: &SshConfig| -> Vec<(String, Vec<(String, String)>)> {
+            config.hosts().map(|host| (host.pattern.to_string(), host.options.to_vec())).collect()
+        };
+        assert_eq!(patterns_and_options(&imported), patterns_and_options(&config));
+    }
+
+    #[test]
+    fn blank_lines_after_a_host_block_stay_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = "Host a\n    User me\n\n# next section\nHost b\n    Port 2222\n\n\n# trailing\n";
+        let path = write(dir.path(), "config", fixture);
+
+        let mut config = SshConfig::parse_file(&path).unwrap();
+        assert_eq!(config.to_string(&path), fixture);
+        assert!(config.save_all(&path).skipped.is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), fixture);
+    }
+
+    #[test]
+    fn hosts_in_a_new_include_file_are_saved() {
+        let home = tempfile::tempdir().unwrap();
+        let ssh_dir = home.path().join(".ssh");
+        fs::create_dir_all(ssh_dir.join("conf.d")).unwrap();
+        let main = write(&ssh_dir, "config", "Host main\n");
+        let new_file = ssh_dir.join("conf.d").join("new");
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        config.home = Some(home.path().to_path_buf());
+
+        assert_eq!(config.add_include("conf.d/new", &main), Ok((0, 1)));
+        config.lines.push(ConfigLine::HostEntry {
+            pattern: "fresh".to_string(),
+            comment: None,
+            tags: Vec::new(),
+            options: vec![("HostName".to_string(), "fresh.example.com".to_string())],
+            option_comments: Vec::new(),
+            source_file: new_file.clone(),
+        });
+        // Also a file only referenced by a line, never included
+        let stray = ssh_dir.join("stray");
+        config.lines.push(ConfigLine::GlobalOption {
+            key: "User".to_string(),
+            value: "me".to_string(),
+            source_file: stray.clone(),
+        });
+
+        assert_eq!(config.save_order(&main), [main.clone(), new_file.clone(), stray.clone()]);
+        let report = config.save_all(&main);
+        assert!(report.skipped.is_empty());
+        assert_eq!(fs::read_to_string(&main).unwrap(), "Include conf.d/new\nHost main\n");
+        assert_eq!(fs::read_to_string(&new_file).unwrap(), "Host fresh\n    HostName fresh.example.com\n");
+        assert_eq!(fs::read_to_string(&stray).unwrap(), "User me\n");
+    }
+
+    fn apply(config: &mut SshConfig, json: serde_json::Value, policy: UpsertPolicy) -> Result<UpsertReport, String> {
+        config.apply_json(&json, Path::new("config"), policy)
+    }
+
+    #[test]
+    fn apply_json_creates_missing_hosts() {
+        let mut config = parse("Host web\n    User me\n");
+        let report = apply(
+            &mut config,
+            serde_json::json!({"hosts": [
+                {"pattern": "db", "options": [["HostName", "db.internal"], ["Port", "2222"]]},
+                {"pattern": "cache", "file": "config"},
+            ]}),
+            UpsertPolicy::Update,
+        );
+        assert_eq!(report, Ok(UpsertReport { created: 2, updated: 0, unchanged: 0 }));
+        assert_eq!(
+            config.to_string(Path::new("config")),
+            "Host web\n    User me\nHost db\n    HostName db.internal\n    Port 2222\nHost cache\n"
+        );
+    }
+
+    #[test]
+    fn apply_json_updates_existing_hosts_by_policy() {
+        let json = serde_json::json!({"hosts": [
+            {"pattern": "WEB", "options": {"User": "deploy", "IdentityFile": "~/.ssh/deploy"}},
+            {"pattern": "db", "options": {"Port": "2222"}},
+        ]});
+        let text = "Host web\n    User me\n    IdentityFile ~/.ssh/id\nHost db\n    Port 2222\n";
+
+        let mut config = parse(text);
+        let report = apply(&mut config, json.clone(), UpsertPolicy::Update);
+        assert_eq!(report, Ok(UpsertReport { created: 0, updated: 1, unchanged: 1 }));
+        assert_eq!(
+            config.to_string(Path::new("config")),
+            "Host web\n    User deploy\n    IdentityFile ~/.ssh/id\n    IdentityFile ~/.ssh/deploy\nHost db\n    Port 2222\n"
+        );
+
+        let mut config = parse(text);
+        apply(&mut config, json.clone(), UpsertPolicy::Replace).unwrap();
+        // Keys of a JSON object come in alphabetical order
+        assert!(config.to_string(Path::new("config")).starts_with("Host web\n    IdentityFile ~/.ssh/deploy\n    User deploy\nHost db"));
+
+        let mut config = parse(text);
+        let report = apply(&mut config, json, UpsertPolicy::KeepExisting);
+        assert_eq!(report, Ok(UpsertReport { created: 0, updated: 0, unchanged: 2 }));
+        assert_eq!(config.to_string(Path::new("config")), text);
+    }
+
+    #[test]
+    fn apply_json_rejects_malformed_documents_without_changes() {
+        let cases = [
+            (serde_json::json!([]), "expected an object with a \"hosts\" list"),
+            (serde_json::json!({"hosts": {}}), "hosts: expected a list"),
+            (serde_json::json!({"hosts": ["web"]}), "hosts[0]: expected an object"),
+            (serde_json::json!({"hosts": [{"options": {}}]}), "hosts[0].pattern: expected a string"),
+            (serde_json::json!({"hosts": [{"pattern": "a"}, {"pattern": "b", "options": {"Port": 22}}]}), "hosts[1].options.Port: expected a string"),
+            (serde_json::json!({"hosts": [{"pattern": "a", "options": [["Port"]]}]}), "hosts[0].options[0]: expected [key, value] strings"),
+            (serde_json::json!({"hosts": [{"pattern": "a", "options": {"Local Command": "x"}}]}), "hosts[0].options: 'Local Command' is not a keyword"),
+        ];
+        for (json, error) in cases {
+            let mut config = parse("Host web\n");
+            assert_eq!(apply(&mut config, json, UpsertPolicy::Update), Err(error.to_string()));
+            assert_eq!(config.to_string(Path::new("config")), "Host web\n");
+        }
+    }
+
+    #[test]
+    fn apply_json_only_writes_to_managed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = write(dir.path(), "work", "Host work\n");
+        let main = write(dir.path(), "config", &format!("Include {}\n", work.display()));
+        let mut config = SshConfig::parse_file(&main).unwrap();
+
+        for file in ["/home/me/.bashrc", "work", "config"] {
+            let json = serde_json::json!({"hosts": [{"pattern": "evil", "file": file}]});
+            assert_eq!(
+                config.apply_json(&json, &main, UpsertPolicy::Update),
+                Err(format!("hosts[0].file: {} is not the main file or an included file", file))
+            );
+        }
+        assert_eq!(config.hosts().count(), 1);
+
+        let json = serde_json::json!({"hosts": [
+            {"pattern": "a", "file": work.to_str().unwrap()},
+            {"pattern": "b", "file": main.to_str().unwrap()},
+        ]});
+        assert_eq!(config.apply_json(&json, &main, UpsertPolicy::Update).map(|r| r.created), Ok(2));
+        assert_eq!(config.save_order(&main), [main.clone(), work.clone()]);
+    }
+
+    fn flattened_fixture() -> (tempfile::TempDir, PathBuf, SshConfig) {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = write(dir.path(), "extra", "# extra file\nHost db\n    User admin\n");
+        let main = write(
+            dir.path(),
+            "config",
+            &format!(
+                "# top comment\nCompression yes\n\nInclude {} # trailing\n# @tags: prod\nHost web # web box\n    # about hostname\n    HostName web.example.com\n\n    Port 22\n",
+                extra.display()
+            ),
+        );
+        let config = SshConfig::parse_file(&main).unwrap();
+        (dir, main, config)
+    }
+
+    #[test]
+    fn flattened_export_inlines_includes_with_comments() {
+        let (_dir, main, config) = flattened_fixture();
+        assert_eq!(
+            config.to_flattened_string(&main, true),
+            "# top comment\nCompression yes\n\n# extra file\nHost db\n    User admin\n# @tags: prod\nHost web # web box\n    # about hostname\n    HostName web.example.com\n\n    Port 22\n"
+        );
+    }
+
+    #[test]
+    fn flattened_export_without_comments_keeps_only_directives_in_order() {
+        let (_dir, main, config) = flattened_fixture();
+        assert_eq!(
+            config.to_flattened_string(&main, false),
+            "Compression yes\nHost db\n    User admin\nHost web\n    HostName web.example.com\n    Port 22\n"
+        );
+        let stripped = config.to_string_with(&main, false);
+        assert!(!stripped.contains('#') && !stripped.contains("\n\n"));
+        assert!(stripped.starts_with("Compression yes\nInclude "));
+        // The default keeps everything
+        assert_eq!(config.to_string(&main), fs::read_to_string(&main).unwrap());
+    }
+
+    #[test]
+    fn flattened_export_skips_a_file_including_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("config");
+        write(dir.path(), "config", &format!("Include {}\nHost a\n", main.display()));
+        let config = SshConfig::parse_file(&main).unwrap();
+        assert_eq!(config.to_flattened_string(&main, true), "Host a\n");
+    }
+
+    #[test]
+    fn tab_separated_files_stay_tab_separated() {
+        let text = "Host\tweb\n\tHostName\tweb.example.com\n\tPort\t\t2222\n";
+        let mut config = parse(text);
+        let host = config.hosts().next().unwrap();
+        assert_eq!(host.pattern, "web");
+        assert_eq!(host.options[1], ("Port".to_string(), "2222".to_string()));
+        // Runs of tabs collapse to one; the tab indent is kept
+        let single_tabs = "Host\tweb\n\tHostName\tweb.example.com\n\tPort\t2222\n";
+        assert_eq!(config.to_string(Path::new("config")), single_tabs);
+        assert_eq!(round_trip(single_tabs), single_tabs);
+
+        config.indent_override = Some(2);
+        assert_eq!(
+            config.to_string(Path::new("config")),
+            "Host\tweb\n  HostName\tweb.example.com\n  Port\t2222\n"
+        );
+    }
+
+    #[test]
+    fn normalized_lines_are_grouped_by_file_after_a_cross_file_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = write(dir.path(), "extra", "Host x\nHost y\n");
+        let main = write(dir.path(), "config", &format!("Host a\nInclude {}\nHost b\n", extra.display()));
+        let mut config = SshConfig::parse_file(&main).unwrap();
+
+        let a = host_index(&config, "a");
+        config.move_hosts_to_file(&[a], &extra);
+        let main_text = config.to_string(&main);
+        let extra_text = config.to_string(&extra);
+        assert_eq!(extra_text, "Host x\nHost y\nHost a\n");
+
+        config.normalize_line_order();
+        let files: Vec<&Path> = config.lines.iter().map(ConfigLine::source_file).collect();
+        assert_eq!(files, [main.as_path(), main.as_path(), extra.as_path(), extra.as_path(), extra.as_path()]);
+        // Only the order across files changed
+        assert_eq!(config.to_string(&main), main_text);
+        assert_eq!(config.to_string(&extra), extra_text);
+    }
+
+    #[test]
+    fn files_are_saved_main_first_then_sorted_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let [c, a, b] = ["c", "a", "b"].map(|name| write(dir.path(), name, &format!("Host {}\n", name)));
+        let includes: String = [&c, &a, &b].iter().map(|path| format!("Include {}\n", path.display())).collect();
+        let main = write(dir.path(), "config", &includes);
+        let expected = vec![main.clone(), a, b, c];
+
+        for _ in 0..3 {
+            let mut config = SshConfig::parse_file(&main).unwrap();
+            assert_eq!(config.save_order(&main), expected);
+            for line in &mut config.lines {
+                if let ConfigLine::HostEntry { options, .. } = line {
+                    options.push(("Port".to_string(), "2222".to_string()));
+                }
+            }
+            assert_eq!(config.save_all(&main).written, expected);
+        }
+    }
+
+    #[test]
+    fn unindented_option_after_a_gap_is_a_global_kept_in_place() {
+        // Directly below the Host line, indented or not, it's the host's
+        let config = parse("Host web\nUser me\n");
+        assert_eq!(config.hosts().next().unwrap().options, [("User".to_string(), "me".to_string())]);
+        assert_eq!(config.globals().count(), 0);
+
+        // After a blank line an indented option still continues the block
+        let config = parse("Host web\n    User me\n\n    Port 2222\n");
+        assert_eq!(config.hosts().next().unwrap().options.len(), 2);
+        assert_eq!(config.globals().count(), 0);
+
+        // An unindented one ends it, stays where it was written and is flagged
+        let text = "Host web\n    User me\n\nCompression yes\nHost db\n";
+        let config = parse(text);
+        assert_eq!(config.hosts().next().unwrap().options.len(), 1);
+        assert_eq!(config.globals().map(|(key, value, _)| (key, value)).collect::<Vec<_>>(), [("Compression", "yes")]);
+        assert!(config.warnings.iter().any(|w| w.message.contains("'Compression' appears after a Host block")));
+        assert_eq!(config.to_string(Path::new("config")), text);
+    }
+
+    #[test]
+    fn bare_keyword_is_kept_and_flagged() {
+        let text = "Compression\nHost web\n    ForwardAgent\n    RemoteCommand \"\"\n";
+        let config = parse(text);
+        assert_eq!(config.globals().next().map(|(key, value, _)| (key, value)), Some(("Compression", "")));
+        assert_eq!(
+            config.hosts().next().unwrap().options,
+            [("ForwardAgent".to_string(), String::new()), ("RemoteCommand".to_string(), "\"\"".to_string())]
+        );
+        let flagged: Vec<&str> = config.warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(flagged, ["'Compression' has no value", "'ForwardAgent' has no value"]);
+        assert_eq!(config.to_string(Path::new("config")), text);
+    }
+
+    #[test]
+    fn relative_include_resolves_under_dot_ssh_before_the_including_file() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir(home.path().join(".ssh")).unwrap();
+        write(&home.path().join(".ssh"), "extra", "Host from-dot-ssh\n");
+        let elsewhere = tempfile::tempdir().unwrap();
+        write(elsewhere.path(), "extra", "Host next-to-config\n");
+        let main = write(elsewhere.path(), "config", "Host web\n");
+
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        config.home = Some(home.path().to_path_buf());
+        config.add_include("extra", &main).unwrap();
+        assert!(config.find_host_index("from-dot-ssh").is_some());
+        assert!(config.find_host_index("next-to-config").is_none());
+
+        // Only found next to the config, it's taken from there
+        write(elsewhere.path(), "local-only", "Host local\n");
+        config.add_include("local-only", &main).unwrap();
+        assert!(config.find_host_index("local").is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn group_readable_key_is_flagged_and_restricted() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let key = write(dir.path(), "id_ed25519", "key\n");
+        fs::set_permissions(&key, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(open_key_permissions(&key), Some(0o644));
+
+        restrict_key_permissions(&key).unwrap();
+        assert_eq!(fs::metadata(&key).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(open_key_permissions(&key), None);
+        // A missing key is the existence check's business
+        assert_eq!(open_key_permissions(&dir.path().join("missing")), None);
+    }
+
+    #[test]
+    fn identity_file_none_points_at_no_file() {
+        let home = Some(Path::new("/home/me"));
+        assert_eq!(identity_file_path("none", home), None);
+        assert_eq!(identity_file_path("\"None\"", home), None);
+        assert!(identity_file_path("/keys/none", home).is_some());
+        assert_eq!(identity_file_path("~/.ssh/id", home), Some(PathBuf::from("/home/me/.ssh/id")));
+        assert_eq!(identity_file_path("~/.ssh/id", None), None);
+    }
+
+    #[test]
+    fn edited_host_reverts_to_its_saved_version() {
+        let mut config = parse("Host web\n    User me\n\nHost db\n    Port 2222\n");
+        let saved = config.lines.clone();
+        let web = host_index(&config, "web");
+        if let ConfigLine::HostEntry { pattern, options, .. } = &mut config.lines[web] {
+            *pattern = "www".to_string();
+            options.push(("Port".to_string(), "2222".to_string()));
+        }
+        assert_eq!(config.changed_hosts(&saved), HashSet::from([web]));
+
+        assert!(config.revert_host(web, &saved));
+        assert_eq!(config.lines, saved);
+        assert!(config.changed_hosts(&saved).is_empty());
+        assert!(config.changed_files(&saved).is_empty());
+
+        // A host added since has nothing to revert to
+        config.lines.push(ConfigLine::HostEntry {
+            pattern: "new".to_string(),
+            comment: None,
+            tags: Vec::new(),
+            options: Vec::new(),
+            option_comments: Vec::new(),
+            source_file: PathBuf::from("config"),
+        });
+        let new = host_index(&config, "new");
+        assert!(!config.revert_host(new, &saved));
+    }
+
+    #[test]
+    fn editing_an_included_host_only_changes_its_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = write(dir.path(), "extra", "Host x\n");
+        let other = write(dir.path(), "other", "Host y\n");
+        let main = write(
+            dir.path(),
+            "config",
+            &format!("Include {}\nInclude {}\nHost web\n", extra.display(), other.display()),
+        );
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        let saved = config.lines.clone();
+
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("x") {
+            options.push(("User".to_string(), "me".to_string()));
+        }
+        assert_eq!(config.changed_files(&saved), HashSet::from([extra.clone()]));
+        assert!(config.file_modified(&extra));
+        assert!(!config.file_modified(&other) && !config.file_modified(&main));
+
+        // Saved, the file is clean again
+        config.save_all(&main);
+        assert!(!config.file_modified(&extra));
+    }
+
+    #[test]
+    fn host_lines_match_their_position_in_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = write(dir.path(), "extra", "# team hosts\n\nHost x\n");
+        let text = format!("# personal\nHost web\n    User me\n\nInclude {}\n# @tags: prod\nHost web\n", extra.display());
+        let main = write(dir.path(), "config", &text);
+        let mut config = SshConfig::parse_file(&main).unwrap();
+
+        let lines: Vec<(&str, Option<usize>)> = config
+            .hosts()
+            .map(|host| (host.pattern, config.host_line(host.index)))
+            .collect();
+        assert_eq!(lines, [("web", Some(2)), ("x", Some(3)), ("web", Some(7))]);
+
+        // A renamed host has no parsed line any more
+        let x = host_index(&config, "x");
+        if let ConfigLine::HostEntry { pattern, .. } = &mut config.lines[x] {
+            *pattern = "renamed".to_string();
+        }
+        assert_eq!(config.host_line(x), None);
+    }
+
+    #[test]
+    fn ssh_urls_become_hosts() {
+        let host = |url| match host_from_ssh_url(url, Path::new("config")) {
+            Some(ConfigLine::HostEntry { pattern, options, .. }) => Some((pattern, options)),
+            _ => None,
+        };
+        let options = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+
+        assert_eq!(
+            host("ssh://user@host.example.com:2222"),
+            Some((
+                "host".to_string(),
+                options(&[("HostName", "host.example.com"), ("User", "user"), ("Port", "2222")])
+            ))
+        );
+        assert_eq!(
+            host(" SSH://host.example.com/repo "),
+            Some(("host".to_string(), options(&[("HostName", "host.example.com")])))
+        );
+        assert_eq!(
+            host("ssh://me;fingerprint=abc@[fe80::1]:22"),
+            Some(("fe80::1".to_string(), options(&[("HostName", "fe80::1"), ("User", "me"), ("Port", "22")])))
+        );
+        assert_eq!(host("ssh://10.0.0.1").unwrap().0, "10.0.0.1");
+        assert_eq!(host("https://host.example.com"), None);
+        assert_eq!(host("ssh://host:99999"), None);
+        assert_eq!(host("ssh://"), None);
+    }
+
+    #[test]
+    fn semicolon_lines_are_comments_only_when_enabled() {
+        let text = "; generated\nHost web\n    ; note\n    User me\n";
+
+        let strict = parse(text);
+        assert!(strict.warnings.iter().any(|w| w.message.starts_with("'; generated' starts with ';'")));
+        // Kept as a (bogus) option rather than dropped
+        assert_eq!(strict.to_string(Path::new("config")), text);
+
+        let options = ParseOptions { semicolon_comments: true };
+        let lenient = SshConfig::parse_str_with(text, "config", options).unwrap();
+        assert!(lenient.warnings.is_empty());
+        let host = lenient.hosts().next().unwrap();
+        assert_eq!(host.options, [("User".to_string(), "me".to_string())]);
+        // The `;` prefix survives saving
+        assert_eq!(lenient.to_string(Path::new("config")), text);
+    }
+
+    #[test]
+    fn relative_glob_include_is_saved_as_written() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("editor-test.d")).unwrap();
+        write(&dir.path().join("editor-test.d"), "a.conf", "Host a\n");
+        let main = write(dir.path(), "config", "Include editor-test.d/*.conf # team\nHost web\n");
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        assert!(config.find_host_index("a").is_some());
+
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("web") {
+            options.push(("User".to_string(), "me".to_string()));
+        }
+        config.save_all(&main);
+        assert_eq!(
+            fs::read_to_string(&main).unwrap(),
+            "Include editor-test.d/*.conf # team\nHost web\n    User me\n"
+        );
+    }
+
+    #[test]
+    fn includes_record_the_files_they_resolved_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("editor-test.d");
+        fs::create_dir(&conf_d).unwrap();
+        let b = write(&conf_d, "b.conf", "Host b\n");
+        let a = write(&conf_d, "a.conf", "Host a\n");
+        write(&conf_d, "notes.txt", "Host notes\n");
+        let text = format!("Include {}/*.conf\nInclude {}/none-*.conf\n", conf_d.display(), conf_d.display());
+        let main = write(dir.path(), "config", &text);
+        let config = SshConfig::parse_file(&main).unwrap();
+
+        let includes: Vec<&[PathBuf]> = config.includes().map(|(_, resolved, _)| resolved).collect();
+        // Sorted like ssh reads them; the second one is dead
+        assert_eq!(includes, [&[a, b][..], &[]]);
+    }
+
+    #[test]
+    fn indent_width_applies_to_every_file_and_is_clamped() {
+        let mut config = parse("Host web\n\tUser me\n\tPort 2222\n");
+        config.indent_override = Some(2);
+        assert_eq!(config.to_string(Path::new("config")), "Host web\n  User me\n  Port 2222\n");
+
+        config.indent_override = Some(20);
+        assert_eq!(config.to_string(Path::new("config")), format!("Host web\n{0}User me\n{0}Port 2222\n", " ".repeat(8)));
+        config.indent_override = None;
+        assert_eq!(config.to_string(Path::new("config")), "Host web\n\tUser me\n\tPort 2222\n");
+    }
+
+    #[test]
+    fn pasted_line_breaks_and_tabs_are_flattened() {
+        assert_eq!(sanitize_single_line("web.example.com"), None);
+        assert_eq!(sanitize_single_line("web.example.com\n"), Some("web.example.com".to_string()));
+        assert_eq!(sanitize_single_line("ssh -W\t%h:%p bastion\r\n"), Some("ssh -W %h:%p bastion".to_string()));
+        assert_eq!(sanitize_single_line("web\u{7}db"), Some("web db".to_string()));
+    }
+
+    #[test]
+    fn included_file_lines_hold_the_hosts_of_that_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config", "Include extra\n\nHost main\n    User me\n");
+        let extra = write(dir.path(), "extra", "Host one\n    Port 1\n\nHost two\n    Port 2\n");
+        let mut config = SshConfig::parse_file(&main).unwrap();
+
+        let included = |config: &SshConfig| -> Vec<String> {
+            config.included_files[&extra]
+                .lines
+                .iter()
+                .filter_map(|line| match line {
+                    ConfigLine::HostEntry { pattern, .. } => Some(pattern.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(included(&config), ["one", "two"]);
+        assert!(!config.file_modified(&extra));
+
+        // Edits change the ordered lines only, until the file is saved
+        if let Some(ConfigLine::HostEntry { pattern, .. }) = config.host_mut_by_pattern("two") {
+            *pattern = "three".to_string();
+        }
+        assert_eq!(included(&config), ["one", "two"]);
+        assert!(config.file_modified(&extra));
+
+        let report = config.save_all(&main);
+        assert!(report.written.contains(&extra));
+        assert_eq!(included(&config), ["one", "three"]);
+        assert!(!config.file_modified(&extra));
+    }
+
+    #[test]
+    fn tilde_includes_need_a_home_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config", "Include ~/.ssh/extra\n\nHost web\n");
+        let config = SshConfig::parse_file_with_home(&main, ParseOptions::default(), None).unwrap();
+        assert_eq!(config.home(), None);
+        assert_eq!(
+            config.warnings,
+            [ParseWarning {
+                file: main.clone(),
+                message: "Include ~/.ssh/extra: home directory unknown, can't expand ~".to_string(),
+            }]
+        );
+        assert!(config.find_host_index("web").is_some());
+    }
+
+    #[test]
+    fn reloading_keeps_the_given_home_directory() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir(home.path().join(".ssh")).unwrap();
+        write(&home.path().join(".ssh"), "extra", "Host from-home\n");
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config", "Include ~/.ssh/extra\n");
+        let mut config =
+            SshConfig::parse_file_with_home(&main, ParseOptions::default(), Some(home.path().to_path_buf())).unwrap();
+        assert!(config.find_host_index("from-home").is_some());
+
+        // The main file has includes, so it is parsed again from scratch
+        fs::write(&main, "Include ~/.ssh/extra\n\nHost web\n").unwrap();
+        assert_eq!(config.reload_changed(), Ok(vec![main.clone()]));
+        assert_eq!(config.home(), Some(home.path()));
+        assert!(config.find_host_index("from-home").is_some());
+        assert!(config.find_host_index("web").is_some());
+    }
+
+    #[test]
+    fn save_all_to_map_holds_every_file_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config", "Include a\nInclude b\n\nHost main\n    User me\n");
+        let a = write(dir.path(), "a", "Host a\n    Port 1\n");
+        let b = write(dir.path(), "b", "Host b\n\tPort 2\n");
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("a") {
+            options[0].1 = "10".to_string();
+        }
+
+        let map = config.save_all_to_map(&main);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&main], "Include a\nInclude b\n\nHost main\n    User me\n");
+        assert_eq!(map[&a], "Host a\n    Port 10\n");
+        assert_eq!(map[&b], "Host b\n\tPort 2\n");
+        assert_eq!(fs::read_to_string(&a).unwrap(), "Host a\n    Port 1\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unchanged_read_only_includes_are_left_alone() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let system = write(dir.path(), "system", "Host shared\n    User admin\n");
+        let main = write(dir.path(), "config", &format!("Include {}\n\nHost web\n", system.display()));
+        fs::set_permissions(&system, fs::Permissions::from_mode(0o444)).unwrap();
+        let mut config = SshConfig::parse_file(&main).unwrap();
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("web") {
+            options.push(("Port".to_string(), "2222".to_string()));
+        }
+
+        let report = config.save_all(&main);
+        assert_eq!(report.written, std::slice::from_ref(&main));
+        assert_eq!(report.skipped, []);
+        assert_eq!(fs::read_to_string(&system).unwrap(), "Host shared\n    User admin\n");
+        assert!(fs::read_to_string(&main).unwrap().ends_with("Host web\n    Port 2222\n"));
+    }
+
+    #[test]
+    fn comments_above_options_stay_with_their_option() {
+        let text = "Host web\n    # who we log in as\n    User deploy\n\n    # jump through the bastion\n    # (see wiki)\n    ProxyJump bastion\n    Port 22\n";
+        assert_eq!(round_trip(text), text);
+
+        let mut config = parse(text);
+        let Some(ConfigLine::HostEntry { options, option_comments, .. }) = config.host_mut_by_pattern("web") else {
+            panic!("web not parsed");
+        };
+        assert_eq!(
+            *option_comments,
+            ["    # who we log in as\n", "\n    # jump through the bastion\n    # (see wiki)\n", ""]
+        );
+
+        // Removing the first option takes its comment along; the others keep theirs
+        options.remove(0);
+        assert_eq!(remove_option_comment(option_comments, 0), "    # who we log in as\n");
+        options.insert(0, ("HostName".to_string(), "web.example.com".to_string()));
+        insert_option_comment(option_comments, 0, String::new());
+        assert_eq!(
+            config.to_string(Path::new("config")),
+            "Host web\n    HostName web.example.com\n\n    # jump through the bastion\n    # (see wiki)\n    ProxyJump bastion\n    Port 22\n"
+        );
+    }
+
+    #[test]
+    fn duplicate_hosts_without_conflicts_merge_into_the_first() {
+        let mut config = parse(
+            "Host web\n    User deploy\n    LocalForward 8080 localhost:80\n\nHost db\n\nHost web\n    Port 2222\n    LocalForward 9090 localhost:90\n    user deploy\n",
+        );
+        let report = config.merge_duplicate_hosts(Path::new("config"));
+        assert_eq!(report, DuplicateMergeReport { merged: 1, conflicts: Vec::new() });
+        assert_eq!(
+            config.to_string(Path::new("config")),
+            "Host web\n    User deploy\n    LocalForward 8080 localhost:80\n    Port 2222\n    LocalForward 9090 localhost:90\n\nHost db\n"
+        );
+    }
+
+    #[test]
+    fn conflicting_duplicate_options_keep_the_first_value() {
+        let mut config = parse("Host web\n    Port 22\n\nHost web\n    Port 2222\n    User deploy\n\nHost web\n    PORT 23\n");
+        let report = config.merge_duplicate_hosts(Path::new("config"));
+        assert_eq!(report.merged, 2);
+        assert_eq!(
+            report.conflicts,
+            [
+                "Host web: dropped 'Port 2222', keeping 'Port 22'",
+                "Host web: dropped 'PORT 23', keeping 'Port 22'",
+            ]
+        );
+        assert_eq!(config.to_string(Path::new("config")), "Host web\n    Port 22\n    User deploy\n");
+    }
+
+    #[test]
+    fn system_paths_are_under_etc_or_outside_home() {
+        let home = Some(Path::new("/home/me"));
+        assert!(!is_system_path(Path::new("/home/me/.ssh/config"), home));
+        assert!(!is_system_path(Path::new("/home/me/.ssh/conf.d/work"), home));
+        assert!(!is_system_path(Path::new("config"), home));
+        assert!(is_system_path(Path::new("/opt/shared/ssh_config"), home));
+        assert!(is_system_path(Path::new("/home/other/.ssh/config"), home));
+        assert!(!is_system_path(Path::new("/opt/shared/ssh_config"), None));
+        if cfg!(unix) {
+            assert!(is_system_path(Path::new("/etc/ssh/ssh_config"), home));
+            assert!(is_system_path(Path::new("/etc/ssh/ssh_config.d/10-defaults.conf"), None));
+        }
+    }
+
+    #[test]
+    fn only_changed_system_files_need_confirming() {
+        let home = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        let system = write(shared.path(), "shared", "Host shared\n    User admin\n");
+        let main = write(home.path(), "config", &format!("Include {}\n\nHost web\n", system.display()));
+        let mut config =
+            SshConfig::parse_file_with_home(&main, ParseOptions::default(), Some(home.path().to_path_buf())).unwrap();
+        let saved = config.lines.clone();
+        assert!(config.is_system_file(&system));
+        assert!(!config.is_system_file(&main));
+
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("web") {
+            options.push(("Port".to_string(), "2222".to_string()));
+        }
+        assert_eq!(config.changed_system_files(&saved), Vec::<PathBuf>::new());
+
+        if let Some(ConfigLine::HostEntry { options, .. }) = config.host_mut_by_pattern("shared") {
+            options[0].1 = "root".to_string();
+        }
+        assert_eq!(config.changed_system_files(&saved), [system]);
+    }
+
+    #[test]
+    fn malformed_include_globs_are_reported() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(dir.path(), "config", "Include [abc\n\nHost web\n");
+        let config =
+            SshConfig::parse_file_with_home(&main, ParseOptions::default(), Some(home.path().to_path_buf())).unwrap();
+        assert_eq!(
+            config.warnings,
+            [ParseWarning {
+                file: main.clone(),
+                message: "Include [abc: invalid pattern (invalid range pattern)".to_string(),
+            }]
+        );
+        assert!(config.find_host_index("web").is_some());
+    }
+
+    #[test]
+    fn match_blocks_are_lines_of_their_own() {
+        let text = "Host web\n    User me\n\n# @tags: prod\nMatch host db exec \"true\" # checks\n    # the db port\n    Port 2222\nHost *\n    ForwardAgent no\n";
+        assert_eq!(round_trip(text), text);
+
+        let config = parse(text);
+        assert!(matches!(&config.lines[0], ConfigLine::HostEntry { pattern, options, .. } if pattern == "web" && options.len() == 1));
+        let ConfigLine::MatchEntry { criteria, comment, tags, options, option_comments, .. } = &config.lines[2] else {
+            panic!("expected a Match block, got {:?}", config.lines[2]);
+        };
+        assert_eq!(criteria, "host db exec \"true\"");
+        assert_eq!(comment.as_deref(), Some("checks"));
+        assert_eq!(tags, &["prod"]);
+        assert_eq!(options, &[("Port".to_string(), "2222".to_string())]);
+        assert_eq!(option_comments, &["    # the db port\n"]);
+        assert_eq!(config.hosts().map(|host| host.pattern).collect::<Vec<_>>(), ["web", "*"]);
+        assert_eq!(config.warnings, []);
+    }
+
+    #[test]
+    fn hosts_and_match_blocks_convert_both_ways() {
+        let web = ConfigLine::HostEntry {
+            pattern: "web  !web-test".to_string(),
+            comment: Some("front".to_string()),
+            tags: vec!["prod".to_string()],
+            options: vec![("User".to_string(), "deploy".to_string())],
+            option_comments: vec!["    # login\n".to_string()],
+            source_file: PathBuf::from("config"),
+        };
+        let block = host_to_match(&web).unwrap();
+        let ConfigLine::MatchEntry { criteria, .. } = &block else {
+            panic!("expected a Match block, got {:?}", block);
+        };
+        assert_eq!(criteria, "host web,!web-test");
+        let ConfigLine::HostEntry { pattern, .. } = match_to_host(&block).unwrap() else {
+            panic!("expected a host");
+        };
+        assert_eq!(pattern, "web !web-test");
+        assert_eq!(host_to_match(&block), None);
+        assert_eq!(match_to_host(&web), None);
+
+        assert_eq!(match_host_patterns("HOST a,b"), Some("a b".to_string()));
+        assert_eq!(match_host_patterns("host \"a,b\""), Some("a b".to_string()));
+        assert_eq!(match_host_patterns("host a user b"), None);
+        assert_eq!(match_host_patterns("originalhost a"), None);
+        assert_eq!(match_host_patterns("all"), None);
+    }
+
+    #[test]
+    fn converting_a_host_keeps_or_replaces_it() {
+        let text = "Host web db\n    User deploy\n\nHost other\n";
+        let mut config = parse(text);
+        assert_eq!(config.convert_host_to_match(0, false), Some(0));
+        assert_eq!(config.to_string(Path::new("config")), "Match host web,db\n    User deploy\n\nHost other\n");
+        assert_eq!(config.effective_options("db"), [("User".to_string(), "deploy".to_string())]);
+        assert!(config.convert_match_to_host(0));
+        assert_eq!(config.to_string(Path::new("config")), text);
+
+        assert_eq!(config.convert_host_to_match(0, true), Some(2));
+        assert_eq!(
+            config.to_string(Path::new("config")),
+            "Host web db\n    User deploy\n\nMatch host web,db\n    User deploy\n\nHost other\n"
+        );
+        assert_eq!(config.convert_host_to_match(2, true), None);
+        assert!(!config.convert_match_to_host(0));
+    }
+
+    #[test]
+    fn match_blocks_apply_where_their_criteria_can_be_told() {
+        let config = parse(
+            "Match host web*\n    User deploy\nMatch exec \"true\"\n    Port 1\nMatch all\n    Port 22\n    User nobody\n",
+        );
+        assert_eq!(
+            config.effective_options("web-1"),
+            [("User".to_string(), "deploy".to_string()), ("Port".to_string(), "22".to_string())]
+        );
+        assert_eq!(
+            config.effective_options("db"),
+            [("Port".to_string(), "22".to_string()), ("User".to_string(), "nobody".to_string())]
+        );
+    }
+
+    #[test]
+    fn moved_hosts_are_written_at_the_end_of_the_target_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = write(
+            dir.path(),
+            "config",
+            "Include extra\n\nHost web\n    User me\n\nHost db\n    Port 2\n\nHost app\n    # keep\n    User app\n",
+        );
+        let extra = write(dir.path(), "extra", "Host one\n    Port 1\n");
+        let mut config = SshConfig::parse_file(&main).unwrap();
+
+        let (web, app) = (host_index(&config, "web"), host_index(&config, "app"));
+        let moved = config.move_hosts_to_file(&[app, web], &extra);
+        assert_eq!(moved.len(), 2);
+        assert!(matches!(&config.lines[moved[0]], ConfigLine::HostEntry { pattern, .. } if pattern == "app"));
+        assert!(matches!(&config.lines[moved[1]], ConfigLine::HostEntry { pattern, .. } if pattern == "web"));
+
+        assert_eq!(config.to_string(&main), "Include extra\n\nHost db\n    Port 2\n");
+        assert_eq!(
+            config.to_string(&extra),
+            "Host one\n    Port 1\n\nHost web\n    User me\n\nHost app\n    # keep\n    User app\n"
+        );
+    }
+
+    #[test]
+    fn proxy_command_with_nested_options_is_one_option() {
+        let text = "Host web\n    ProxyCommand ssh -W %h:%p -o \"ProxyJump none\" bastion\n";
+        let config = parse(text);
+        let command = "ssh -W %h:%p -o \"ProxyJump none\" bastion";
+        let Some(ConfigLine::HostEntry { pattern, options, .. }) = config.lines.first() else {
+            panic!("expected the host");
+        };
+        assert_eq!(options, &[("ProxyCommand".to_string(), command.to_string())]);
+        assert_eq!(config.warnings, []);
+        assert_eq!(round_trip(text), text);
+
+        // Listed under the host whole, whichever part of it matched
+        let found = |query| crate::search::host_search_matches(pattern, options, query, true);
+        let listed = Some(vec![format!("ProxyCommand {}", command)]);
+        assert_eq!(found("proxyjump none"), listed);
+        assert_eq!(found("%h:%p -o"), listed);
+        assert_eq!(found("\"proxyjump none\" bastion"), listed);
     }
 }
@@ -0,0 +1,42 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_HISTORY: usize = 300;
+
+// Current status bar message plus a capped history of every message shown
+// during the session
+#[derive(Default)]
+pub struct StatusLog {
+    message: String,
+    history: VecDeque<(SystemTime, String)>,
+}
+
+impl StatusLog {
+    pub fn set(&mut self, message: impl Into<String>) {
+        self.message = message.into();
+        self.history.push_back((SystemTime::now(), self.message.clone()));
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    // Oldest first
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &(SystemTime, String)> {
+        self.history.iter()
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+// Formats a timestamp as `HH:MM:SS` (UTC)
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let day_secs = secs % 86_400;
+    format!("{:02}:{:02}:{:02}", day_secs / 3600, (day_secs % 3600) / 60, day_secs % 60)
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::keywords;
+use crate::ssh_config::SshConfig;
+
+// A keyword whose value in this editor's resolution differs from what
+// `ssh -G` reports. None on either side means the keyword is missing there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub key: String,
+    pub ours: Option<String>,
+    pub ssh: Option<String>,
+}
+
+// Whether an `ssh` binary can be run at all
+pub fn ssh_available() -> bool {
+    Command::new("ssh").arg("-V").output().is_ok_and(|output| output.status.success())
+}
+
+// Runs `ssh -G` for `alias` and returns the keywords it resolved. With a
+// `config_path` ssh reads only that file (and its includes), without one it
+// reads the user's default config followed by the system-wide one.
+pub fn resolve_with_ssh(config_path: Option<&Path>, alias: &str) -> Result<Vec<(String, String)>, String> {
+    let mut command = Command::new("ssh");
+    command.arg("-G");
+    if let Some(config_path) = config_path {
+        command.arg("-F").arg(config_path);
+    }
+    // `--` so an alias starting with `-` isn't taken for an option
+    let output = command
+        .arg("--")
+        .arg(alias)
+        .output()
+        .map_err(|e| format!("Could not run ssh: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(parse_ssh_g(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// `ssh -G` prints one lowercase `keyword value` pair per line, repeating
+// the keyword for list options like identityfile
+pub fn parse_ssh_g(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once(' ')?;
+            Some((key.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+// Compares the keywords set in `config` (and `system`, if ssh read the
+// system-wide config too) for `alias` with ssh's resolution. Keywords only
+// ssh knows about (its defaults) aren't reported. Values are compared as
+// `normalize_value` spells them, and repeatable keywords as a whole list.
+pub fn compare(
+    config: &SshConfig,
+    system: Option<&SshConfig>,
+    alias: &str,
+    resolved: &[(String, String)],
+) -> Vec<Difference> {
+    let ours = config.effective_options_with_system(alias, system);
+    let values_of = |options: &[(String, String)], key: &str| -> Option<String> {
+        let values: Vec<&str> = options
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.trim_matches('"'))
+            .collect();
+        if values.is_empty() { None } else { Some(values.join(", ")) }
+    };
+
+    let mut differences: Vec<Difference> = Vec::new();
+    for (key, _) in &ours {
+        if differences.iter().any(|d| d.key.eq_ignore_ascii_case(key)) {
+            continue;
+        }
+        let our_value = values_of(&ours, key);
+        let ssh_value = values_of(resolved, key);
+        let same = match (&our_value, &ssh_value) {
+            (Some(a), Some(b)) => normalize_value(a, config.home()) == normalize_value(b, config.home()),
+            _ => false,
+        };
+        if !same {
+            differences.push(Difference {
+                key: keywords::canonical_keyword(key).unwrap_or(key).to_string(),
+                ours: our_value,
+                ssh: ssh_value,
+            });
+        }
+    }
+    differences
+}
+
+// A value (or `, `-joined list of values) the way `ssh -G` prints it:
+// unquoted, with a leading `~/` expanded to the home directory, and
+// lowercase, as ssh prints yes/no and most other values. Only meant for
+// comparing.
+pub fn normalize_value(value: &str, home: Option<&Path>) -> String {
+    value
+        .split(", ")
+        .map(|value| {
+            let value = value.trim_matches('"');
+            match (value.strip_prefix("~/"), home) {
+                (Some(rest), Some(home)) => home.join(rest).display().to_string(),
+                _ => value.to_string(),
+            }
+            .to_lowercase()
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssh_g_values_compare_after_normalizing() {
+        let home = Some(Path::new("/home/me"));
+        assert_eq!(normalize_value("Yes", home), "yes");
+        assert_eq!(normalize_value("\"~/.ssh/id_ed25519\"", home), "/home/me/.ssh/id_ed25519");
+        assert_eq!(
+            normalize_value("~/.ssh/a, ~/.ssh/b", home),
+            normalize_value("/home/me/.ssh/a, /home/me/.ssh/b", None)
+        );
+        assert_eq!(normalize_value("~/.ssh/a", None), "~/.ssh/a");
+    }
+
+    #[test]
+    fn compare_reports_only_real_differences() {
+        let config = SshConfig::parse_str("Host web\n    Compression Yes\n    Port 2222\n", "config").unwrap();
+        let resolved = parse_ssh_g("compression yes\nport 22\nuser me\n");
+        assert_eq!(
+            compare(&config, None, "web", &resolved),
+            [Difference {
+                key: "Port".to_string(),
+                ours: Some("2222".to_string()),
+                ssh: Some("22".to_string()),
+            }]
+        );
+    }
+}
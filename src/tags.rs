@@ -0,0 +1,60 @@
+// Host tags are stored as a structured comment directly above the Host line,
+// e.g. `# @tags: prod, eu`, so they survive in a plain ssh config and are
+// ignored by ssh itself.
+const TAGS_MARKER: &str = "@tags:";
+
+// Tags of a `# @tags: ...` comment line, or None for any other line. A marker
+// without any tags is left alone as an ordinary comment.
+pub fn parse_tags_comment(line: &str) -> Option<Vec<String>> {
+    let rest = line.trim().strip_prefix('#')?.trim_start();
+    let list = rest.strip_prefix(TAGS_MARKER)?;
+    let tags = parse_tag_list(list);
+    if tags.is_empty() { None } else { Some(tags) }
+}
+
+pub fn format_tags_comment(tags: &[String]) -> String {
+    format!("# {} {}", TAGS_MARKER, tags.join(", "))
+}
+
+// Splits a comma-separated tag list, dropping empty entries and duplicates
+pub fn parse_tag_list(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SshConfig;
+    use std::path::Path;
+
+    #[test]
+    fn tags_comment_round_trips() {
+        let tags = parse_tags_comment("  #@tags: prod,  eu, prod ,").unwrap();
+        assert_eq!(tags, ["prod", "eu"]);
+        assert_eq!(format_tags_comment(&tags), "# @tags: prod, eu");
+        assert_eq!(parse_tags_comment(&format_tags_comment(&tags)), Some(tags));
+
+        assert_eq!(parse_tags_comment("# @tags:  , "), None);
+        assert_eq!(parse_tags_comment("# tags: prod"), None);
+        assert_eq!(parse_tags_comment("Host prod"), None);
+    }
+
+    #[test]
+    fn tags_belong_to_the_host_below_and_untagged_hosts_get_no_comment() {
+        let text = "# @tags: prod, eu\nHost web\nHost db\n";
+        let config = SshConfig::parse_str(text, "config").unwrap();
+        let tags: Vec<&[String]> = config.hosts().map(|host| host.tags).collect();
+        assert_eq!(tags, [&["prod".to_string(), "eu".to_string()][..], &[]]);
+        assert_eq!(config.to_string(Path::new("config")), text);
+
+        // Not followed by a Host line, it stays an ordinary comment
+        let config = SshConfig::parse_str("# @tags: prod\n\nHost web\n", "config").unwrap();
+        assert!(config.hosts().next().unwrap().tags.is_empty());
+    }
+}
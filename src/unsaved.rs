@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+// An action that discards unsaved changes, held back until the user chose
+// to save or discard them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PendingAction {
+    Quit,
+    Open(PathBuf),
+    Reload,
+    CloseTab,
+}
+
+impl PendingAction {
+    pub fn verb(&self) -> &'static str {
+        match self {
+            PendingAction::Quit => "Quit",
+            PendingAction::Open(_) => "Open",
+            PendingAction::Reload => "Reload",
+            PendingAction::CloseTab => "Close",
+        }
+    }
+}
+
+// The user's answer to the unsaved-changes prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    // Saved, and nothing is left unsaved
+    Saved,
+    // Tried to save, but something is still unsaved (a file was skipped or
+    // saving waits for a missing directory to be created)
+    SaveFailed,
+    Discard,
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    // Show the prompt for the action
+    Ask(PendingAction),
+    Perform(PendingAction),
+    // Quitting: drop the active tab and its changes, then guard the quit
+    // again so the remaining tabs are asked about
+    CloseTabAndQuit,
+    // Quitting has to ask about another tab, but can't switch to it until
+    // the file being loaded has arrived
+    WaitForLoad,
+    // Keep the changes and forget the action
+    Idle,
+}
+
+// Runs `action` right away if nothing is unsaved, otherwise asks first
+pub fn guard(action: PendingAction, is_dirty: bool) -> Step {
+    if is_dirty { Step::Ask(action) } else { Step::Perform(action) }
+}
+
+// Guards quitting with several tabs open. `is_dirty` is for the active tab
+// and `other_dirty` for any other; asking about another tab means switching
+// to it, which has to wait while a file loads into the active one.
+pub fn guard_quit(is_dirty: bool, other_dirty: bool, loading: bool) -> Step {
+    if !is_dirty && other_dirty && loading {
+        Step::WaitForLoad
+    } else {
+        guard(PendingAction::Quit, is_dirty || other_dirty)
+    }
+}
+
+// What follows `choice` in the prompt for `action`
+pub fn after_choice(action: PendingAction, choice: Choice) -> Step {
+    match choice {
+        // Guarded again rather than performed, so a quit still asks about
+        // the other tabs
+        Choice::Saved => guard(action, false),
+        // Don't go on and lose changes that couldn't be saved
+        Choice::SaveFailed | Choice::Cancel => Step::Idle,
+        Choice::Discard if action == PendingAction::Quit => Step::CloseTabAndQuit,
+        Choice::Discard => Step::Perform(action),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_actions() -> [PendingAction; 4] {
+        [
+            PendingAction::Quit,
+            PendingAction::Open(PathBuf::from("/home/me/.ssh/other")),
+            PendingAction::Reload,
+            PendingAction::CloseTab,
+        ]
+    }
+
+    #[test]
+    fn clean_documents_act_right_away() {
+        for action in all_actions() {
+            assert_eq!(guard(action.clone(), false), Step::Perform(action));
+        }
+    }
+
+    #[test]
+    fn dirty_documents_ask_first() {
+        for action in all_actions() {
+            assert_eq!(guard(action.clone(), true), Step::Ask(action));
+        }
+    }
+
+    #[test]
+    fn quitting_waits_for_a_load_before_asking_about_another_tab() {
+        assert_eq!(guard_quit(false, false, true), Step::Perform(PendingAction::Quit));
+        assert_eq!(guard_quit(true, false, true), Step::Ask(PendingAction::Quit));
+        assert_eq!(guard_quit(true, true, true), Step::Ask(PendingAction::Quit));
+        assert_eq!(guard_quit(false, true, false), Step::Ask(PendingAction::Quit));
+        assert_eq!(guard_quit(false, true, true), Step::WaitForLoad);
+    }
+
+    #[test]
+    fn dirty_open_then_discard_loads_the_new_file() {
+        let open = PendingAction::Open(PathBuf::from("/home/me/.ssh/other"));
+        let Step::Ask(asked) = guard(open.clone(), true) else {
+            panic!("expected the prompt");
+        };
+        assert_eq!(after_choice(asked, Choice::Discard), Step::Perform(open));
+    }
+
+    #[test]
+    fn discarding_performs_the_action_except_quit_which_moves_to_the_next_tab() {
+        for action in all_actions() {
+            let expected = match action {
+                PendingAction::Quit => Step::CloseTabAndQuit,
+                _ => Step::Perform(action.clone()),
+            };
+            assert_eq!(after_choice(action, Choice::Discard), expected);
+        }
+    }
+
+    #[test]
+    fn saving_performs_the_action() {
+        for action in all_actions() {
+            assert_eq!(after_choice(action.clone(), Choice::Saved), Step::Perform(action));
+        }
+    }
+
+    #[test]
+    fn failed_save_or_cancel_keeps_the_changes() {
+        for action in all_actions() {
+            assert_eq!(after_choice(action.clone(), Choice::SaveFailed), Step::Idle);
+            assert_eq!(after_choice(action, Choice::Cancel), Step::Idle);
+        }
+    }
+}